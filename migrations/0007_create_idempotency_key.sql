@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS idempotency_key (
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    key VARCHAR(255) NOT NULL,
+    -- hash of the method, path and body of the first request
+    fingerprint TEXT NOT NULL,
+    -- the response columns are NULL while the first request is running
+    response_status INT,
+    response_headers JSONB,
+    response_body BYTEA,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    PRIMARY KEY (user_id, key)
+);
+
+CREATE INDEX IF NOT EXISTS idempotency_key_expires_at_idx ON idempotency_key (expires_at);
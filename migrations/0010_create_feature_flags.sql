@@ -0,0 +1,21 @@
+CREATE TABLE IF NOT EXISTS feature_flags (
+    name VARCHAR(100) PRIMARY KEY,
+    description TEXT NOT NULL DEFAULT '',
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    -- the share of users the flag is on for while enabled, see `feature_flags`
+    rollout_percentage INTEGER NOT NULL DEFAULT 100
+        CHECK (rollout_percentage BETWEEN 0 AND 100),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- take precedence over `enabled` and the rollout
+CREATE TABLE IF NOT EXISTS feature_flag_overrides (
+    flag_name VARCHAR(100) NOT NULL,
+    user_id INTEGER NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (flag_name, user_id),
+    CONSTRAINT fk_feature_flag FOREIGN KEY(flag_name) REFERENCES feature_flags(name) ON DELETE CASCADE,
+    CONSTRAINT fk_feature_flag_user FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,11 @@
+-- existing posts were public, so they stay published
+ALTER TABLE post ADD COLUMN IF NOT EXISTS status VARCHAR(16) NOT NULL DEFAULT 'published'
+    CONSTRAINT post_status_check CHECK (status IN ('draft', 'published', 'archived'));
+-- set the first time the post is published and kept when it is unpublished
+ALTER TABLE post ADD COLUMN IF NOT EXISTS published_at TIMESTAMP WITH TIME ZONE;
+
+UPDATE post SET published_at = created_at WHERE status = 'published' AND published_at IS NULL;
+
+CREATE INDEX IF NOT EXISTS post_published_at_idx ON post (published_at DESC, created_at DESC, id DESC)
+    WHERE status = 'published';
+CREATE INDEX IF NOT EXISTS post_user_id_idx ON post (user_id);
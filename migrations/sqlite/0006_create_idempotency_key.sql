@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS idempotency_key (
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    key TEXT NOT NULL,
+    -- hash of the method, path and body of the first request
+    fingerprint TEXT NOT NULL,
+    -- the response columns are NULL while the first request is running
+    response_status INTEGER,
+    response_headers TEXT,
+    response_body BLOB,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    expires_at TEXT NOT NULL,
+    PRIMARY KEY (user_id, key)
+);
+
+CREATE INDEX IF NOT EXISTS idempotency_key_expires_at_idx ON idempotency_key (expires_at);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS csp_report (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    document_uri TEXT NOT NULL,
+    violated_directive TEXT NOT NULL,
+    blocked_uri TEXT,
+    source_file TEXT,
+    line_number INTEGER,
+    -- `enforce` or `report`
+    disposition TEXT NOT NULL,
+    user_agent TEXT,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+CREATE INDEX IF NOT EXISTS csp_report_created_at_idx ON csp_report (created_at);
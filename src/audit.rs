@@ -0,0 +1,42 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use async_trait::async_trait;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts},
+};
+
+use crate::{model::UserModel, repository::Actor, AppState};
+
+// clients choose the header, so it is cut to this length before it is stored
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
+// the logged in user, if `auth_guard_middleware` ran, and the client address as the rate limiter
+// sees it, so trusted proxies are resolved the same way
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for Actor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        data: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        Ok(Actor {
+            user_id: parts.extensions.get::<UserModel>().map(|user| user.id),
+            ip: data
+                .rate_limiter
+                .client_ip(peer, &parts.headers)
+                .map(|ip| ip.to_string()),
+            user_agent: parts
+                .headers
+                .get(header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect()),
+        })
+    }
+}
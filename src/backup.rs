@@ -0,0 +1,419 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter, Lines};
+
+use crate::{
+    error::AppError,
+    model::{CategoryModel, PostModel, PostStatus, UserModel},
+    AppState,
+};
+
+// a gzipped tarball of `manifest.json` followed by one NDJSON file per table. Every line is a row
+// as serialized by its model, ids and timestamps included. Rows are streamed through temporary
+// files in both directions, so only a page of them is in memory at a time.
+//
+// Jobs and rate limit buckets are transient and not part of a backup.
+pub const FORMAT: &str = "blogrs-backup";
+// bumped on incompatible changes to the records; restore refuses newer versions. 2 added the post
+// status, which older versions would drop and publish every draft.
+pub const VERSION: u32 = 2;
+
+const MANIFEST: &str = "manifest.json";
+const USERS: &str = "users.ndjson";
+const CATEGORIES: &str = "categories.ndjson";
+const POSTS: &str = "posts.ndjson";
+// in restore order: posts reference users and categories
+const ENTRIES: [&str; 3] = [USERS, CATEGORIES, POSTS];
+
+const PAGE_SIZE: i64 = 500;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: String,
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    // the blogrs version that wrote the backup
+    pub app_version: String,
+    pub entries: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub name: String,
+    pub records: u64,
+    pub bytes: u64,
+    // hex-encoded sha-256 of the file
+    pub sha256: String,
+}
+
+impl Manifest {
+    pub fn entry(&self, name: &str) -> Option<&ManifestEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+}
+
+#[derive(Debug)]
+pub enum BackupError {
+    Io(io::Error),
+    // the archive is not a backup this version can restore, or fails its integrity check
+    Invalid(String),
+    // restore only writes into an empty database
+    NotEmpty,
+    Database(AppError),
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackupError::Io(e) => write!(f, "{e}"),
+            BackupError::Invalid(message) => write!(f, "invalid backup: {message}"),
+            BackupError::NotEmpty => write!(f, "the database already contains data"),
+            BackupError::Database(AppError::Database(e)) => write!(f, "database error: {e}"),
+            BackupError::Database(e) => write!(f, "{}", e.message()),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl From<io::Error> for BackupError {
+    fn from(e: io::Error) -> Self {
+        BackupError::Io(e)
+    }
+}
+
+impl From<AppError> for BackupError {
+    fn from(e: AppError) -> Self {
+        BackupError::Database(e)
+    }
+}
+
+// writes every user, category and post to a new archive at `path`
+pub async fn create(state: &AppState, path: &Path) -> Result<Manifest, BackupError> {
+    let dir = tempfile::tempdir()?;
+
+    // every table is read from the same snapshot, so the references between the files hold
+    let mut export = state.backups.begin_export().await?;
+
+    let mut users = NdjsonWriter::create(dir.path(), USERS).await?;
+    let mut after_id = 0;
+    loop {
+        let page = export.users(after_id, PAGE_SIZE).await?;
+        let Some(last) = page.last() else { break };
+        after_id = last.id;
+        for user in &page {
+            users.write(user).await?;
+        }
+    }
+
+    let mut categories = NdjsonWriter::create(dir.path(), CATEGORIES).await?;
+    for category in export.categories().await? {
+        categories.write(&category).await?;
+    }
+
+    let mut posts = NdjsonWriter::create(dir.path(), POSTS).await?;
+    let mut after_id = 0;
+    loop {
+        let page = export.posts(after_id, PAGE_SIZE).await?;
+        let Some(last) = page.last() else { break };
+        after_id = last.id;
+        for post in &page {
+            posts.write(post).await?;
+        }
+    }
+    drop(export);
+
+    let manifest = Manifest {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        created_at: Utc::now(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        entries: vec![
+            users.finish().await?,
+            categories.finish().await?,
+            posts.finish().await?,
+        ],
+    };
+
+    let archive = manifest.clone();
+    let target = path.to_path_buf();
+    let result = tokio::task::spawn_blocking(move || write_archive(&target, dir.path(), &archive))
+        .await
+        .map_err(io::Error::other)?;
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(path);
+        return Err(e.into());
+    }
+
+    Ok(manifest)
+}
+
+// restores an archive written by `create` into an empty database. Rows get new ids and the
+// references between them are remapped; timestamps and password hashes are kept. Everything is
+// written in one transaction, so a restore that fails leaves the database empty.
+pub async fn restore(state: &AppState, path: &Path) -> Result<Manifest, BackupError> {
+    let mut import = state.backups.begin_import().await?;
+    if !import.is_empty().await? {
+        return Err(BackupError::NotEmpty);
+    }
+
+    // unpacked and verified in full before the first row is written
+    let dir = tempfile::tempdir()?;
+    let source = path.to_path_buf();
+    let target = dir.path().to_path_buf();
+    let manifest = tokio::task::spawn_blocking(move || unpack(&source, &target))
+        .await
+        .map_err(io::Error::other)??;
+
+    let mut user_ids = HashMap::new();
+    let mut users = NdjsonReader::open(dir.path(), USERS).await?;
+    while let Some(user) = users.next::<UserModel>().await? {
+        let restored = import.user(&user).await?;
+        user_ids.insert(user.id, restored.id);
+    }
+
+    let mut category_ids = HashMap::new();
+    let mut categories = NdjsonReader::open(dir.path(), CATEGORIES).await?;
+    while let Some(category) = categories.next::<CategoryModel>().await? {
+        let restored = import.category(&category.name).await?;
+        category_ids.insert(category.id, restored.id);
+    }
+
+    let mut posts = NdjsonReader::open(dir.path(), POSTS).await?;
+    while let Some(post) = posts.next::<PostModel>().await? {
+        let user_id = *user_ids.get(&post.user_id).ok_or_else(|| {
+            BackupError::Invalid(format!(
+                "post `{}` references the missing user {}",
+                post.slug, post.user_id
+            ))
+        })?;
+        // the category may have been deleted after the post was written (`ON DELETE SET NULL`)
+        let category_id = post
+            .category_id
+            .and_then(|id| category_ids.get(&id).copied());
+
+        // backups from before posts had a status hold published posts without `published_at`
+        let published_at = match post.published_at {
+            None if post.status == PostStatus::Published => post.created_at,
+            published_at => published_at,
+        };
+
+        import
+            .post(&PostModel {
+                user_id,
+                category_id,
+                published_at,
+                ..post
+            })
+            .await?;
+    }
+
+    import.commit().await?;
+    Ok(manifest)
+}
+
+// reads the records of an unpacked entry one line at a time
+struct NdjsonReader {
+    name: &'static str,
+    lines: Lines<BufReader<tokio::fs::File>>,
+    line_number: usize,
+}
+
+impl NdjsonReader {
+    async fn open(dir: &Path, name: &'static str) -> io::Result<Self> {
+        let file = tokio::fs::File::open(dir.join(name)).await?;
+        Ok(Self {
+            name,
+            lines: BufReader::new(file).lines(),
+            line_number: 0,
+        })
+    }
+
+    async fn next<T: DeserializeOwned>(&mut self) -> Result<Option<T>, BackupError> {
+        let Some(line) = self.lines.next_line().await? else {
+            return Ok(None);
+        };
+        self.line_number += 1;
+
+        serde_json::from_str(&line).map(Some).map_err(|e| {
+            BackupError::Invalid(format!("{}, line {}: {e}", self.name, self.line_number))
+        })
+    }
+}
+
+struct NdjsonWriter {
+    name: &'static str,
+    file: BufWriter<tokio::fs::File>,
+    hasher: Sha256,
+    records: u64,
+    bytes: u64,
+}
+
+impl NdjsonWriter {
+    async fn create(dir: &Path, name: &'static str) -> io::Result<Self> {
+        let file = tokio::fs::File::create(dir.join(name)).await?;
+        Ok(Self {
+            name,
+            file: BufWriter::new(file),
+            hasher: Sha256::new(),
+            records: 0,
+            bytes: 0,
+        })
+    }
+
+    async fn write<T: Serialize>(&mut self, record: &T) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        self.file.write_all(&line).await?;
+        self.hasher.update(&line);
+        self.records += 1;
+        self.bytes += line.len() as u64;
+        Ok(())
+    }
+
+    async fn finish(mut self) -> io::Result<ManifestEntry> {
+        self.file.flush().await?;
+        Ok(ManifestEntry {
+            name: self.name.to_string(),
+            records: self.records,
+            bytes: self.bytes,
+            sha256: hex(&self.hasher.finalize()),
+        })
+    }
+}
+
+fn write_archive(path: &Path, dir: &Path, manifest: &Manifest) -> io::Result<()> {
+    let file = File::create(path)?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    // the manifest comes first so a reader knows what to expect before the data
+    let manifest = serde_json::to_vec_pretty(manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    archive.append_data(&mut header, MANIFEST, manifest.as_slice())?;
+
+    for name in ENTRIES {
+        archive.append_path_with_name(dir.join(name), name)?;
+    }
+
+    archive.into_inner()?.finish()?.sync_all()
+}
+
+// copies the entries to `dir`, checking them against the manifest
+fn unpack(path: &Path, dir: &Path) -> Result<Manifest, BackupError> {
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(path)?));
+    let mut entries = archive.entries()?;
+
+    let mut first = entries
+        .next()
+        .ok_or_else(|| BackupError::Invalid("the archive is empty".to_string()))??;
+    if first.path()?.as_ref() != Path::new(MANIFEST) {
+        return Err(BackupError::Invalid(format!(
+            "expected {MANIFEST} as the first entry"
+        )));
+    }
+    let mut manifest = String::new();
+    first.read_to_string(&mut manifest)?;
+    let manifest: Manifest = serde_json::from_str(&manifest)
+        .map_err(|e| BackupError::Invalid(format!("{MANIFEST}: {e}")))?;
+
+    if manifest.format != FORMAT {
+        return Err(BackupError::Invalid(format!(
+            "unknown format `{}`",
+            manifest.format
+        )));
+    }
+    if manifest.version > VERSION {
+        return Err(BackupError::Invalid(format!(
+            "version {} is newer than the supported version {VERSION}",
+            manifest.version
+        )));
+    }
+
+    let mut unpacked = Vec::new();
+    for entry in entries {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let (Some(name), Some(expected)) = (
+            ENTRIES.iter().find(|known| **known == name),
+            manifest.entry(&name),
+        ) else {
+            return Err(BackupError::Invalid(format!("unexpected entry `{name}`")));
+        };
+
+        let mut target = HashingWriter::new(File::create(dir.join(name))?);
+        io::copy(&mut entry, &mut target)?;
+        let (sha256, bytes) = target.finish()?;
+        if sha256 != expected.sha256 || bytes != expected.bytes {
+            return Err(BackupError::Invalid(format!(
+                "{name} does not match its checksum"
+            )));
+        }
+        unpacked.push(*name);
+    }
+
+    if let Some(missing) = ENTRIES.iter().find(|name| !unpacked.contains(name)) {
+        return Err(BackupError::Invalid(format!("{missing} is missing")));
+    }
+    Ok(manifest)
+}
+
+struct HashingWriter {
+    file: File,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl HashingWriter {
+    fn new(file: File) -> Self {
+        Self {
+            file,
+            hasher: Sha256::new(),
+            bytes: 0,
+        }
+    }
+
+    fn finish(mut self) -> io::Result<(String, u64)> {
+        self.file.flush()?;
+        Ok((hex(&self.hasher.finalize()), self.bytes))
+    }
+}
+
+impl Write for HashingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+// the path the archive is written to when none is given, e.g. `blogrs-20240115T103000Z.tar.gz`
+pub fn default_path() -> PathBuf {
+    PathBuf::from(format!(
+        "blogrs-{}.tar.gz",
+        Utc::now().format("%Y%m%dT%H%M%SZ")
+    ))
+}
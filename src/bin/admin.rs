@@ -0,0 +1,522 @@
+use std::{
+    io::{BufRead, IsTerminal},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+use blogrs::{
+    backup,
+    config::Config,
+    error::AppError,
+    fixtures,
+    model::JobState,
+    password::hash_password,
+    repository::{Actor, Backend, NewUser, PostFilter},
+    AppState,
+};
+
+// categories created by `category seed` when no names are given; id 1 is the default category
+// of new posts
+const DEFAULT_CATEGORIES: &[&str] = &["General"];
+
+// the only non-interactive way to pass a password to `user create` and `user reset-password`
+const PASSWORD_ENV: &str = "BLOGRS_ADMIN_PASSWORD";
+
+// how the writes of this tool are recorded in the audit log
+const ACTOR: &str = "blogrs-admin";
+
+/// Operator tool for a blogrs database. Reads the same configuration as the server
+/// (`blogrs.toml`, `BLOGRS_CONFIG`, `DATABASE_URL`, ...).
+#[derive(Parser)]
+#[command(name = "blogrs-admin", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create users and reset passwords
+    #[command(subcommand)]
+    User(UserCommand),
+    /// List, reassign and delete posts
+    #[command(subcommand)]
+    Post(PostCommand),
+    /// List and seed categories
+    #[command(subcommand)]
+    Category(CategoryCommand),
+    /// Load seed data
+    #[command(subcommand)]
+    Fixtures(FixturesCommand),
+    /// Export the database to an archive, or restore one into an empty database
+    #[command(subcommand)]
+    Backup(BackupCommand),
+    /// Inspect and retry background jobs
+    #[command(subcommand)]
+    Job(JobCommand),
+    /// Inspect the Content Security Policy violations reported by browsers
+    #[command(subcommand)]
+    CspReport(CspReportCommand),
+    /// Run or check the database migrations
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+}
+
+#[derive(Subcommand)]
+enum UserCommand {
+    /// Create a user. The password is taken from `BLOGRS_ADMIN_PASSWORD`, or prompted for
+    Create {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        email: String,
+        /// Grant admin rights
+        #[arg(long)]
+        admin: bool,
+    },
+    /// Replace the password of a user. The password is taken from `BLOGRS_ADMIN_PASSWORD`, or
+    /// prompted for
+    ResetPassword {
+        #[arg(long)]
+        email: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum PostCommand {
+    /// List posts in any status, never published ones first, then most recently published first
+    List {
+        #[arg(long, default_value_t = 50)]
+        limit: i64,
+        #[arg(long, default_value_t = 0)]
+        offset: i64,
+    },
+    /// Move a post to another author
+    Reassign {
+        slug: String,
+        /// Email of the new author
+        #[arg(long)]
+        to: String,
+    },
+    /// Delete a post
+    Delete { slug: String },
+}
+
+#[derive(Subcommand)]
+enum CategoryCommand {
+    /// List categories
+    List,
+    /// Create the given categories (default: General), skipping existing ones
+    Seed { names: Vec<String> },
+}
+
+#[derive(Subcommand)]
+enum FixturesCommand {
+    /// Load YAML or JSON fixture files in order, skipping records that exist
+    Load {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackupCommand {
+    /// Write every user, category and post to a .tar.gz archive
+    Create {
+        /// Defaults to `blogrs-<timestamp>.tar.gz` in the working directory
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Restore an archive written by `backup create`; the database must be empty
+    Restore { path: PathBuf },
+}
+
+#[derive(Subcommand)]
+enum CspReportCommand {
+    /// List reported violations, newest first
+    List {
+        #[arg(long, default_value_t = 50)]
+        limit: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum JobCommand {
+    /// List jobs in a state, oldest first
+    List {
+        #[arg(long, value_enum, default_value = "dead")]
+        state: JobStateArg,
+        #[arg(long, default_value_t = 50)]
+        limit: i64,
+    },
+    /// Queue a dead job again with fresh attempts
+    Retry { id: i64 },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum JobStateArg {
+    Queued,
+    Running,
+    Dead,
+}
+
+impl From<JobStateArg> for JobState {
+    fn from(state: JobStateArg) -> Self {
+        match state {
+            JobStateArg::Queued => JobState::Queued,
+            JobStateArg::Running => JobState::Running,
+            JobStateArg::Dead => JobState::Dead,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum MigrateCommand {
+    /// Apply the pending migrations
+    Run,
+    /// List the pending migrations; exits with 1 if there are any
+    Check,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(cli.command).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(command: Command) -> Result<ExitCode, String> {
+    let config = Config::load_for_cli().map_err(|e| e.to_string())?;
+    if config.database.url.is_none() {
+        return Err("database.url (or DATABASE_URL) must be set".to_string());
+    }
+
+    let backend = Backend::connect(&config.database)
+        .await
+        .map_err(|e| format!("failed to connect to the database: {e}"))?;
+
+    if let Command::Migrate(MigrateCommand::Run) = command {
+        backend
+            .migrate()
+            .await
+            .map_err(|e| format!("migrations failed: {e}"))?;
+        println!("migrations are up to date");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let state = backend.into_app_state(config);
+    match command {
+        Command::User(command) => user(&state, command).await?,
+        Command::Post(command) => post(&state, command).await?,
+        Command::Category(command) => category(&state, command).await?,
+        Command::Fixtures(FixturesCommand::Load { paths }) => {
+            let report = fixtures::load_paths(&state, &paths)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("{report}");
+        }
+        Command::Backup(command) => backup(&state, command).await?,
+        Command::Job(command) => job(&state, command).await?,
+        Command::CspReport(command) => csp_report(&state, command).await?,
+        Command::Migrate(MigrateCommand::Check) => return check_migrations(&state).await,
+        Command::Migrate(MigrateCommand::Run) => unreachable!("handled before the state is built"),
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn user(state: &AppState, command: UserCommand) -> Result<(), String> {
+    match command {
+        UserCommand::Create {
+            username,
+            email,
+            admin,
+        } => {
+            // stored lowercased, like `register_user_handler` does
+            let email = email.to_ascii_lowercase();
+            if state
+                .users
+                .exists(&email, &username)
+                .await
+                .map_err(describe)?
+            {
+                return Err(format!(
+                    "a user named {username} or with email {email} exists"
+                ));
+            }
+
+            let password = hash_password(&read_password()?).map_err(describe)?;
+            let user = state
+                .users
+                .create(
+                    NewUser {
+                        username,
+                        email,
+                        password,
+                        is_admin: admin,
+                    },
+                    &Actor::tool(ACTOR),
+                )
+                .await
+                .map_err(describe)?;
+
+            let role = if user.is_admin { "admin" } else { "user" };
+            println!(
+                "created {role} {} ({}) with id {}",
+                user.username, user.email, user.id
+            );
+        }
+        UserCommand::ResetPassword { email } => {
+            let email = email.to_ascii_lowercase();
+            let password = hash_password(&read_password()?).map_err(describe)?;
+            let user = state
+                .users
+                .set_password(&email, &password, &Actor::tool(ACTOR))
+                .await
+                .map_err(describe)?
+                .ok_or_else(|| format!("no user with email {email}"))?;
+
+            println!("reset the password of {} ({})", user.username, user.email);
+        }
+    }
+
+    Ok(())
+}
+
+async fn post(state: &AppState, command: PostCommand) -> Result<(), String> {
+    match command {
+        PostCommand::List { limit, offset } => {
+            let posts = state
+                .posts
+                .list(&PostFilter::default(), limit, offset)
+                .await
+                .map_err(describe)?;
+            println!("id\tslug\tuser_id\tcategory_id\tstatus\tcreated_at\tpublished_at\ttitle");
+            for post in posts {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    post.id,
+                    post.slug,
+                    post.user_id,
+                    post.category_id
+                        .map(|id| id.to_string())
+                        .unwrap_or_default(),
+                    post.status.as_str(),
+                    post.created_at
+                        .map(|created_at| created_at.to_rfc3339())
+                        .unwrap_or_default(),
+                    post.published_at
+                        .map(|published_at| published_at.to_rfc3339())
+                        .unwrap_or_default(),
+                    post.title
+                );
+            }
+        }
+        PostCommand::Reassign { slug, to } => {
+            let email = to.to_ascii_lowercase();
+            let user = state
+                .users
+                .find_by_email(&email)
+                .await
+                .map_err(describe)?
+                .ok_or_else(|| format!("no user with email {email}"))?;
+            state
+                .posts
+                .reassign(&slug, user.id, &Actor::tool(ACTOR))
+                .await
+                .map_err(describe)?
+                .ok_or_else(|| format!("no post with slug {slug}"))?;
+            // the running servers hear about it through the post cache bus
+            state.post_cache.invalidate(&[&slug]).await;
+
+            println!("reassigned {slug} to {} ({})", user.username, user.email);
+        }
+        PostCommand::Delete { slug } => {
+            state
+                .posts
+                .delete(&slug, Vec::new(), &Actor::tool(ACTOR))
+                .await
+                .map_err(describe)?
+                .ok_or_else(|| format!("no post with slug {slug}"))?;
+            state.post_cache.invalidate(&[&slug]).await;
+
+            println!("deleted {slug}");
+        }
+    }
+
+    Ok(())
+}
+
+async fn category(state: &AppState, command: CategoryCommand) -> Result<(), String> {
+    match command {
+        CategoryCommand::List => {
+            println!("id\tname");
+            for category in state.categories.list().await.map_err(describe)? {
+                println!("{}\t{}", category.id, category.name);
+            }
+        }
+        CategoryCommand::Seed { names } => {
+            let names = if names.is_empty() {
+                DEFAULT_CATEGORIES
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect()
+            } else {
+                names
+            };
+
+            // a category created concurrently is reported as existing rather than failing the run
+            for name in names {
+                match state.categories.create(&name, &Actor::tool(ACTOR)).await {
+                    Ok(category) => println!("created {} with id {}", category.name, category.id),
+                    Err(AppError::CategoryAlreadyExists) => println!("{name} already exists"),
+                    Err(e) => return Err(describe(e)),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn backup(state: &AppState, command: BackupCommand) -> Result<(), String> {
+    let (verb, path, manifest) = match command {
+        BackupCommand::Create { output } => {
+            let path = output.unwrap_or_else(backup::default_path);
+            let manifest = backup::create(state, &path)
+                .await
+                .map_err(|e| e.to_string())?;
+            ("wrote", path, manifest)
+        }
+        BackupCommand::Restore { path } => {
+            let manifest = backup::restore(state, &path)
+                .await
+                .map_err(|e| e.to_string())?;
+            ("restored", path, manifest)
+        }
+    };
+
+    println!("{verb} {}", path.display());
+    for entry in manifest.entries {
+        println!("  {}: {} records", entry.name, entry.records);
+    }
+    Ok(())
+}
+
+async fn job(state: &AppState, command: JobCommand) -> Result<(), String> {
+    match command {
+        JobCommand::List {
+            state: job_state,
+            limit,
+        } => {
+            let jobs = state
+                .jobs
+                .list(job_state.into(), limit)
+                .await
+                .map_err(describe)?;
+            println!("id\tkind\tattempts\trun_at\tlast_error");
+            for job in jobs {
+                println!(
+                    "{}\t{}\t{}/{}\t{}\t{}",
+                    job.id,
+                    job.kind,
+                    job.attempts,
+                    job.max_attempts,
+                    job.run_at.to_rfc3339(),
+                    job.last_error.unwrap_or_default()
+                );
+            }
+        }
+        JobCommand::Retry { id } => {
+            let job = state
+                .jobs
+                .requeue(id)
+                .await
+                .map_err(describe)?
+                .ok_or_else(|| format!("no dead job with id {id}"))?;
+
+            println!("queued {} job {} again", job.kind, job.id);
+        }
+    }
+
+    Ok(())
+}
+
+async fn csp_report(state: &AppState, command: CspReportCommand) -> Result<(), String> {
+    match command {
+        CspReportCommand::List { limit } => {
+            let reports = state.csp_reports.list(limit).await.map_err(describe)?;
+            println!("created_at\tdisposition\tdirective\tblocked_uri\tdocument_uri");
+            for report in reports {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    report.created_at.to_rfc3339(),
+                    report.disposition,
+                    report.violated_directive,
+                    report.blocked_uri.unwrap_or_default(),
+                    report.document_uri
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn check_migrations(state: &AppState) -> Result<ExitCode, String> {
+    let pending = state
+        .database
+        .pending_migrations()
+        .await
+        .map_err(describe)?;
+
+    if pending.is_empty() {
+        println!("migrations are up to date");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    println!("{} pending migration(s):", pending.len());
+    for migration in pending {
+        println!("  {migration}");
+    }
+    Ok(ExitCode::FAILURE)
+}
+
+// never taken as an argument, where it would show up in `ps` and the shell history
+fn read_password() -> Result<String, String> {
+    let password = match std::env::var(PASSWORD_ENV) {
+        Ok(password) => password,
+        // prompted for without echo on a terminal, otherwise read as a line from stdin
+        Err(_) if std::io::stdin().is_terminal() => rpassword::prompt_password("password: ")
+            .map_err(|e| format!("could not read the password: {e}"))?,
+        Err(_) => {
+            let mut line = String::new();
+            std::io::stdin()
+                .lock()
+                .read_line(&mut line)
+                .map_err(|e| format!("could not read the password: {e}"))?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+
+    if password.is_empty() {
+        return Err("the password must not be empty".to_string());
+    }
+    Ok(password)
+}
+
+fn describe(e: AppError) -> String {
+    match e {
+        AppError::Database(e) => format!("database error: {e}"),
+        AppError::Internal(e) => e,
+        e => e.message(),
+    }
+}
@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use tokio::net::TcpListener;
+
+use blogrs::{config::Config, fixtures, logging, repository::Backend, server};
+
+#[tokio::main]
+async fn main() {
+    let config = Config::load().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    logging::init(&config.log);
+
+    if config.database.url.is_none() {
+        eprintln!("database.url (or DATABASE_URL) must be set");
+        std::process::exit(1);
+    }
+
+    let backend = Backend::connect(&config.database)
+        .await
+        .expect("Failed to connect to the database");
+    tracing::info!("Using the {:?} backend", backend.kind());
+
+    backend.migrate().await.expect("Migrations failed :(");
+
+    let bind_addr = config.bind_addr();
+    let app_state = Arc::new(backend.into_app_state(config));
+
+    let paths = &app_state.config.fixtures.paths;
+    if let Err(e) = fixtures::load_paths(&app_state, paths).await {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+
+    let listener = TcpListener::bind(bind_addr)
+        .await
+        .expect("Failed to bind to server.bind_addr");
+    tracing::info!("Listening on {}", bind_addr);
+
+    server::serve(listener, app_state)
+        .await
+        .expect("Server error");
+}
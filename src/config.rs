@@ -0,0 +1,904 @@
+use std::{
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use axum::http::{HeaderName, HeaderValue, Method};
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::rate_limit::IpRange;
+
+// environment variables with this prefix override values from the config file, using `__` as
+// the section separator, e.g. `BLOGRS__AUTH__TOKEN_EXPIRES_IN_HOURS=12`
+const ENV_PREFIX: &str = "BLOGRS__";
+// path of the config file; when unset `blogrs.toml` is used if it exists
+const CONFIG_PATH_ENV: &str = "BLOGRS_CONFIG";
+const DEFAULT_CONFIG_PATH: &str = "blogrs.toml";
+
+// plain variables kept for compatibility with the shuttle secrets and existing deployments
+const ENV_ALIASES: [(&str, &str); 3] = [
+    ("DATABASE_URL", "database.url"),
+    ("JWT_SECRET", "auth.jwt_secret"),
+    ("BIND_ADDR", "server.bind_addr"),
+];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub auth: AuthConfig,
+    pub pagination: PaginationConfig,
+    pub cors: CorsConfig,
+    pub security_headers: SecurityHeadersConfig,
+    pub http_cache: HttpCacheConfig,
+    pub post_cache: PostCacheConfig,
+    pub feature_flags: FeatureFlagsConfig,
+    pub rate_limit: RateLimitConfig,
+    pub jobs: JobsConfig,
+    pub idempotency: IdempotencyConfig,
+    pub fixtures: FixturesConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_addr: String,
+    // how long in-flight requests and background workers get to finish on shutdown
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_addr: "0.0.0.0:8000".to_string(),
+            shutdown_timeout_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    // not needed on shuttle, where the pool is provisioned by the runtime
+    pub url: Option<String>,
+    pub max_connections: u32,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            max_connections: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatabaseKind {
+    Postgres,
+    Sqlite,
+}
+
+impl DatabaseKind {
+    // the backend is picked from the scheme of the url, e.g. `sqlite://blogrs.db`
+    pub fn from_url(url: &str) -> Option<Self> {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            Some(Self::Postgres)
+        } else if url.starts_with("sqlite:") {
+            Some(Self::Sqlite)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub jwt_secret: String,
+    pub token_expires_in_hours: i64,
+    pub cookie_max_age_hours: i64,
+    // `none` is needed for a frontend on another site to send the cookie, and requires `secure`
+    pub cookie_same_site: CookieSameSite,
+    pub cookie_secure: bool,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            jwt_secret: String::new(),
+            token_expires_in_hours: 24,
+            cookie_max_age_hours: 24 * 7,
+            cookie_same_site: CookieSameSite::Lax,
+            cookie_secure: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CookieSameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+// the secret must never end up in logs
+impl fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("jwt_secret", &"********")
+            .field("token_expires_in_hours", &self.token_expires_in_hours)
+            .field("cookie_max_age_hours", &self.cookie_max_age_hours)
+            .field("cookie_same_site", &self.cookie_same_site)
+            .field("cookie_secure", &self.cookie_secure)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaginationConfig {
+    pub default_page_size: usize,
+    pub max_page_size: usize,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        Self {
+            default_page_size: 10,
+            max_page_size: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    // `*` allows any origin, but never with credentials
+    pub allowed_origins: Vec<String>,
+    // regular expressions matched against the whole origin, e.g. for preview deployments
+    pub allowed_origin_patterns: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    // response headers readable by the frontend
+    pub expose_headers: Vec<String>,
+    // lets browsers send the `token` cookie; only applies to explicitly allowed origins
+    pub allow_credentials: bool,
+    // how long browsers may cache a preflight response
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec!["*".to_string()],
+            allowed_origin_patterns: Vec::new(),
+            allowed_methods: ["GET", "POST", "PATCH", "DELETE"]
+                .map(String::from)
+                .to_vec(),
+            allowed_headers: ["authorization", "accept", "content-type", "idempotency-key"]
+                .map(String::from)
+                .to_vec(),
+            expose_headers: [
+                "x-request-id",
+                "etag",
+                "deprecation",
+                "sunset",
+                "link",
+                "idempotent-replayed",
+            ]
+            .map(String::from)
+            .to_vec(),
+            allow_credentials: true,
+            max_age_secs: 3600,
+        }
+    }
+}
+
+impl CorsConfig {
+    pub fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|origin| origin == "*")
+    }
+
+    // anchored so a pattern cannot match a prefix or suffix of an unrelated origin
+    pub fn origin_patterns(&self) -> Result<Vec<Regex>, regex::Error> {
+        self.allowed_origin_patterns
+            .iter()
+            .map(|pattern| Regex::new(&format!("^(?:{pattern})$")))
+            .collect()
+    }
+}
+
+// the response headers set by `security_headers`; an empty value leaves the header out
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecurityHeadersConfig {
+    pub enabled: bool,
+    // the policy of json and every other response that is not html
+    pub content_security_policy: String,
+    // the policy of html pages such as `/api/docs`
+    pub html_content_security_policy: String,
+    // sends the policies as `Content-Security-Policy-Report-Only`, reporting violations without
+    // blocking anything
+    pub csp_report_only: bool,
+    // appended to the policies as `report-uri`; `/csp-report` stores the reports
+    pub csp_report_uri: String,
+    // `Strict-Transport-Security`, left out when 0; browsers ignore it over plain http
+    pub hsts_max_age_secs: u64,
+    pub hsts_include_subdomains: bool,
+    pub hsts_preload: bool,
+    pub referrer_policy: String,
+    pub permissions_policy: String,
+    // `X-Frame-Options`, `DENY` or `SAMEORIGIN`
+    pub frame_options: String,
+    // overrides for the paths under `path`; the longest match wins
+    pub routes: Vec<SecurityHeadersRoute>,
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            content_security_policy: "default-src 'none'; frame-ancestors 'none'".to_string(),
+            html_content_security_policy:
+                "default-src 'self'; script-src 'self' https://unpkg.com; \
+                style-src 'self' 'unsafe-inline'; img-src 'self' data:; frame-ancestors 'none'; \
+                base-uri 'none'; form-action 'self'"
+                    .to_string(),
+            csp_report_only: false,
+            csp_report_uri: "/csp-report".to_string(),
+            hsts_max_age_secs: 365 * 24 * 60 * 60,
+            hsts_include_subdomains: true,
+            hsts_preload: false,
+            referrer_policy: "no-referrer".to_string(),
+            permissions_policy: "camera=(), microphone=(), geolocation=(), payment=()".to_string(),
+            frame_options: "DENY".to_string(),
+            routes: Vec::new(),
+        }
+    }
+}
+
+// the fields left out keep the global value
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SecurityHeadersRoute {
+    // a path prefix matched on segments, e.g. `/api/docs`
+    pub path: String,
+    pub content_security_policy: Option<String>,
+    pub referrer_policy: Option<String>,
+    pub permissions_policy: Option<String>,
+    pub frame_options: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpCacheConfig {
+    // `Cache-Control` of the public post reads; they always carry an `ETag`
+    pub public_cache_control: String,
+}
+
+impl Default for HttpCacheConfig {
+    fn default() -> Self {
+        Self {
+            public_cache_control: "public, max-age=0, must-revalidate".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PostCacheConfig {
+    // caches the post list pages and details in the process, see `post_cache`
+    pub enabled: bool,
+    // also bounds how stale a replica can be when an invalidation from another one is lost
+    pub ttl_secs: u64,
+    // list pages and details together
+    pub max_entries: usize,
+}
+
+impl Default for PostCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_secs: 60,
+            max_entries: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureFlagsConfig {
+    // how long the flags read by a replica are used before they are read again; changes reach
+    // the other replicas sooner through postgres `NOTIFY`
+    pub ttl_secs: u64,
+}
+
+impl Default for FeatureFlagsConfig {
+    fn default() -> Self {
+        Self { ttl_secs: 60 }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub store: RateLimitStoreKind,
+    // peers allowed to set `X-Forwarded-For`, as addresses or CIDR ranges, e.g. "10.0.0.0/8"
+    pub trusted_proxies: Vec<String>,
+    // `/auth/register` and `/auth/login`
+    pub auth: RateLimitRule,
+    // creating, updating and deleting posts
+    pub write: RateLimitRule,
+    // `/csp-report`, which browsers call without credentials
+    pub csp_report: RateLimitRule,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            store: RateLimitStoreKind::Memory,
+            trusted_proxies: Vec::new(),
+            auth: RateLimitRule {
+                key: RateLimitKey::Ip,
+                burst: 10,
+                per_minute: 5,
+            },
+            write: RateLimitRule {
+                key: RateLimitKey::User,
+                burst: 30,
+                per_minute: 30,
+            },
+            csp_report: RateLimitRule {
+                key: RateLimitKey::Ip,
+                burst: 20,
+                per_minute: 10,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitStoreKind {
+    // per process, so every replica has its own budget
+    Memory,
+    // shared between replicas, requires a postgres database
+    Postgres,
+}
+
+// a token bucket holding `burst` requests, refilled at `per_minute`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitRule {
+    pub key: RateLimitKey,
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitKey {
+    // the client address, see `trusted_proxies`
+    Ip,
+    // the authenticated user, falling back to the client address
+    User,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobsConfig {
+    // runs the worker pool in this process; jobs are still enqueued when disabled
+    pub enabled: bool,
+    pub workers: usize,
+    // how long an idle worker waits before looking for due jobs again
+    pub poll_interval_ms: u64,
+    // a job not finished within the lease is claimed again, e.g. after a crash
+    pub lease_secs: u64,
+    // failed jobs are retried after `backoff_base_secs * 2^(attempts - 1)`, capped at
+    // `backoff_max_secs`
+    pub backoff_base_secs: u64,
+    pub backoff_max_secs: u64,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            workers: 2,
+            poll_interval_ms: 1000,
+            lease_secs: 300,
+            backoff_base_secs: 10,
+            backoff_max_secs: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdempotencyConfig {
+    // how long the response to a request with an `Idempotency-Key` is replayed for retries
+    pub ttl_secs: u64,
+    // how long a request holds its key before it has a response; a retry after that runs the
+    // request again. Only reached when the server died mid-request, so keep it well above the
+    // slowest request.
+    pub lease_secs: u64,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 24 * 60 * 60,
+            lease_secs: 5 * 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FixturesConfig {
+    // YAML or JSON fixture files loaded on startup, after the migrations; see `fixtures`
+    pub paths: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Pretty,
+    Json,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    // an `EnvFilter` directive; `RUST_LOG` takes precedence
+    pub filter: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Pretty,
+            filter: "info".to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Parse {
+        location: String,
+        message: String,
+    },
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, source } => {
+                write!(f, "could not read config file {}: {source}", path.display())
+            }
+            ConfigError::Parse { location, message } => write!(f, "invalid {location}: {message}"),
+            ConfigError::Invalid(problems) => {
+                write!(f, "invalid configuration:")?;
+                for problem in problems {
+                    write!(f, "\n  - {problem}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    // loads `BLOGRS_CONFIG` (or `blogrs.toml` if present) and applies the process environment on top
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::load_with(std::iter::empty())
+    }
+
+    // same as `load` but with extra overrides (e.g. shuttle secrets) applied after the environment
+    pub fn load_with(
+        overrides: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let config = Self::read(overrides)?;
+        config.validate_for_server()?;
+        Ok(config)
+    }
+
+    // same as `load` without the checks that only matter to the server, for `blogrs-admin`
+    pub fn load_for_cli() -> Result<Self, ConfigError> {
+        let config = Self::read(std::iter::empty())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn read(overrides: impl IntoIterator<Item = (String, String)>) -> Result<Self, ConfigError> {
+        let path = match std::env::var(CONFIG_PATH_ENV) {
+            Ok(path) => Some(PathBuf::from(path)),
+            Err(_) => Some(PathBuf::from(DEFAULT_CONFIG_PATH)).filter(|path| path.exists()),
+        };
+
+        let contents = match &path {
+            Some(path) => std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+                path: path.to_owned(),
+                source,
+            })?,
+            None => String::new(),
+        };
+
+        Self::merge(
+            &contents,
+            path.as_deref(),
+            std::env::vars().chain(overrides),
+        )
+    }
+
+    // merges the file contents with the overrides and validates the result like `load` does
+    pub fn from_sources(
+        contents: &str,
+        path: Option<&Path>,
+        overrides: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let config = Self::merge(contents, path, overrides)?;
+        config.validate_for_server()?;
+        Ok(config)
+    }
+
+    fn merge(
+        contents: &str,
+        path: Option<&Path>,
+        overrides: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let location = path
+            .map(|path| format!("config file {}", path.display()))
+            .unwrap_or_else(|| "configuration".to_string());
+
+        let mut table: toml::Table =
+            contents
+                .parse()
+                .map_err(|e: toml::de::Error| ConfigError::Parse {
+                    location,
+                    message: e.to_string().trim().to_string(),
+                })?;
+
+        // the aliases are applied before the `BLOGRS__` variables, so the prefixed name of a
+        // setting wins whatever order the environment lists them in; within each kind a later
+        // override wins
+        let mut aliases = Vec::new();
+        let mut prefixed = Vec::new();
+        for (key, value) in overrides {
+            if let Some(path) = key.strip_prefix(ENV_PREFIX) {
+                let path = path
+                    .split("__")
+                    .map(|segment| segment.to_ascii_lowercase())
+                    .collect::<Vec<_>>();
+                prefixed.push((path, value));
+            } else if let Some((_, path)) = ENV_ALIASES.iter().find(|(alias, _)| *alias == key) {
+                aliases.push((path.split('.').map(String::from).collect::<Vec<_>>(), value));
+            }
+        }
+        for (path, value) in aliases {
+            // aliases are all string settings, so the value is never parsed
+            set_path(&mut table, &path, toml::Value::String(value));
+        }
+        for (path, value) in prefixed {
+            let value = parse_env_value(&path, &value);
+            set_path(&mut table, &path, value);
+        }
+
+        let config: Config =
+            toml::Value::Table(table)
+                .try_into()
+                .map_err(|e: toml::de::Error| ConfigError::Parse {
+                    // the merged file and environment, so no line information is available
+                    location: "configuration".to_string(),
+                    message: e.to_string().trim().to_string(),
+                })?;
+
+        Ok(config)
+    }
+
+    // the checks shared by the server and `blogrs-admin`
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        into_result(self.problems())
+    }
+
+    // `validate` plus the settings only the server needs
+    pub fn validate_for_server(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        if self.auth.jwt_secret.is_empty() {
+            problems.push("auth.jwt_secret (or JWT_SECRET) must be set".to_string());
+        }
+        problems.extend(self.problems());
+        into_result(problems)
+    }
+
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.server.bind_addr.parse::<SocketAddr>().is_err() {
+            problems.push(format!(
+                "server.bind_addr `{}` is not a valid socket address, e.g. 0.0.0.0:8000",
+                self.server.bind_addr
+            ));
+        }
+        if self.server.shutdown_timeout_secs == 0 {
+            problems.push("server.shutdown_timeout_secs must be greater than 0".to_string());
+        }
+        if let Some(url) = &self.database.url {
+            if DatabaseKind::from_url(url).is_none() {
+                problems.push(
+                    "database.url must be a postgres:// or sqlite:// connection string".to_string(),
+                );
+            }
+        }
+        if self.database.max_connections == 0 {
+            problems.push("database.max_connections must be greater than 0".to_string());
+        }
+        if self.auth.token_expires_in_hours <= 0 {
+            problems.push("auth.token_expires_in_hours must be greater than 0".to_string());
+        }
+        if self.auth.cookie_max_age_hours <= 0 {
+            problems.push("auth.cookie_max_age_hours must be greater than 0".to_string());
+        }
+        if self.auth.cookie_same_site == CookieSameSite::None && !self.auth.cookie_secure {
+            problems.push("auth.cookie_same_site = \"none\" requires auth.cookie_secure".into());
+        }
+        if self.pagination.default_page_size == 0 {
+            problems.push("pagination.default_page_size must be greater than 0".to_string());
+        }
+        if self.pagination.max_page_size < self.pagination.default_page_size {
+            problems.push(
+                "pagination.max_page_size must not be smaller than pagination.default_page_size"
+                    .to_string(),
+            );
+        }
+        for origin in &self.cors.allowed_origins {
+            if origin != "*" && HeaderValue::from_str(origin).is_err() {
+                problems.push(format!(
+                    "cors.allowed_origins: `{origin}` is not a valid origin"
+                ));
+            }
+        }
+        if self.cors.allows_any_origin()
+            && (self.cors.allowed_origins.len() > 1
+                || !self.cors.allowed_origin_patterns.is_empty())
+        {
+            problems.push("cors.allowed_origins: `*` cannot be combined with other origins".into());
+        }
+        for pattern in &self.cors.allowed_origin_patterns {
+            if let Err(e) = Regex::new(pattern) {
+                problems.push(format!(
+                    "cors.allowed_origin_patterns: `{pattern}` is not a valid regex: {e}"
+                ));
+            }
+        }
+        for method in &self.cors.allowed_methods {
+            if Method::from_bytes(method.as_bytes()).is_err() {
+                problems.push(format!(
+                    "cors.allowed_methods: `{method}` is not a valid method"
+                ));
+            }
+        }
+        for header in &self.cors.allowed_headers {
+            if HeaderName::from_bytes(header.as_bytes()).is_err() {
+                problems.push(format!(
+                    "cors.allowed_headers: `{header}` is not a valid header"
+                ));
+            }
+        }
+        for header in &self.cors.expose_headers {
+            if HeaderName::from_bytes(header.as_bytes()).is_err() {
+                problems.push(format!(
+                    "cors.expose_headers: `{header}` is not a valid header"
+                ));
+            }
+        }
+
+        let security = &self.security_headers;
+        let mut header_values = vec![
+            (
+                "content_security_policy".to_string(),
+                &security.content_security_policy,
+            ),
+            (
+                "html_content_security_policy".to_string(),
+                &security.html_content_security_policy,
+            ),
+            ("csp_report_uri".to_string(), &security.csp_report_uri),
+            ("referrer_policy".to_string(), &security.referrer_policy),
+            (
+                "permissions_policy".to_string(),
+                &security.permissions_policy,
+            ),
+        ];
+        let mut frame_options = vec![("frame_options".to_string(), &security.frame_options)];
+        for (i, route) in security.routes.iter().enumerate() {
+            if !route.path.starts_with('/') {
+                problems.push(format!(
+                    "security_headers.routes[{i}].path `{}` must start with /",
+                    route.path
+                ));
+            }
+            for (name, value) in [
+                ("content_security_policy", &route.content_security_policy),
+                ("referrer_policy", &route.referrer_policy),
+                ("permissions_policy", &route.permissions_policy),
+            ] {
+                if let Some(value) = value {
+                    header_values.push((format!("routes[{i}].{name}"), value));
+                }
+            }
+            if let Some(value) = &route.frame_options {
+                frame_options.push((format!("routes[{i}].frame_options"), value));
+            }
+        }
+        for (name, value) in header_values {
+            if HeaderValue::from_str(value).is_err() {
+                problems.push(format!(
+                    "security_headers.{name} `{value}` is not a valid header value"
+                ));
+            }
+        }
+        for (name, value) in frame_options {
+            if !["", "DENY", "SAMEORIGIN"].contains(&value.to_ascii_uppercase().as_str()) {
+                problems.push(format!(
+                    "security_headers.{name} must be DENY, SAMEORIGIN or empty, not `{value}`"
+                ));
+            }
+        }
+
+        if HeaderValue::from_str(&self.http_cache.public_cache_control).is_err() {
+            problems.push(format!(
+                "http_cache.public_cache_control `{}` is not a valid header value",
+                self.http_cache.public_cache_control
+            ));
+        }
+
+        for proxy in &self.rate_limit.trusted_proxies {
+            if proxy.parse::<IpRange>().is_err() {
+                problems.push(format!(
+                    "rate_limit.trusted_proxies: `{proxy}` is not an ip address or CIDR range"
+                ));
+            }
+        }
+        for (group, rule) in [
+            ("auth", &self.rate_limit.auth),
+            ("write", &self.rate_limit.write),
+            ("csp_report", &self.rate_limit.csp_report),
+        ] {
+            if rule.burst == 0 || rule.per_minute == 0 {
+                problems.push(format!(
+                    "rate_limit.{group}: burst and per_minute must be greater than 0"
+                ));
+            }
+        }
+        if self.rate_limit.store == RateLimitStoreKind::Postgres
+            && self
+                .database
+                .url
+                .as_deref()
+                .and_then(DatabaseKind::from_url)
+                == Some(DatabaseKind::Sqlite)
+        {
+            problems.push("rate_limit.store = \"postgres\" requires a postgres database".into());
+        }
+
+        let jobs = &self.jobs;
+        if jobs.enabled && jobs.workers == 0 {
+            problems.push("jobs.workers must be greater than 0 when jobs are enabled".into());
+        }
+        if jobs.poll_interval_ms == 0 || jobs.lease_secs == 0 || jobs.backoff_base_secs == 0 {
+            problems.push(
+                "jobs.poll_interval_ms, jobs.lease_secs and jobs.backoff_base_secs must be greater than 0"
+                    .into(),
+            );
+        }
+        if jobs.backoff_max_secs < jobs.backoff_base_secs {
+            problems
+                .push("jobs.backoff_max_secs must not be less than jobs.backoff_base_secs".into());
+        }
+
+        if self.post_cache.enabled
+            && (self.post_cache.ttl_secs == 0 || self.post_cache.max_entries == 0)
+        {
+            problems.push(
+                "post_cache.ttl_secs and post_cache.max_entries must be greater than 0".into(),
+            );
+        }
+        if self.feature_flags.ttl_secs == 0 {
+            problems.push("feature_flags.ttl_secs must be greater than 0".into());
+        }
+
+        if self.idempotency.ttl_secs == 0 || self.idempotency.lease_secs == 0 {
+            problems.push(
+                "idempotency.ttl_secs and idempotency.lease_secs must be greater than 0".into(),
+            );
+        }
+
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&self.log.filter) {
+            problems.push(format!("log.filter `{}` is invalid: {e}", self.log.filter));
+        }
+
+        problems
+    }
+
+    pub fn bind_addr(&self) -> SocketAddr {
+        // checked in `validate`
+        self.server
+            .bind_addr
+            .parse()
+            .expect("validated bind address")
+    }
+}
+
+// environment values are strings; numbers, booleans and arrays are parsed as toml literals when
+// the setting at `path` takes one, so `BLOGRS__AUTH__JWT_SECRET=123456` stays a string
+fn parse_env_value(path: &[String], value: &str) -> toml::Value {
+    let raw = toml::Value::String(value.to_string());
+    let Some(parsed) = format!("value = {value}")
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+    else {
+        return raw;
+    };
+    if parsed.is_str() {
+        return parsed;
+    }
+
+    // tried on a table holding only this setting, the sections filling in their defaults. A
+    // setting in a section without defaults (e.g. `rate_limit.auth`) fails either way and keeps
+    // the literal.
+    let accepts = |value: &toml::Value| {
+        let mut probe = toml::Table::new();
+        set_path(&mut probe, path, value.clone());
+        toml::Value::Table(probe).try_into::<Config>().is_ok()
+    };
+    if !accepts(&parsed) && accepts(&raw) {
+        raw
+    } else {
+        parsed
+    }
+}
+
+fn set_path(table: &mut toml::Table, path: &[String], value: toml::Value) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+
+    let mut current = table;
+    for segment in parents {
+        let entry = current
+            .entry(segment.to_owned())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if !entry.is_table() {
+            *entry = toml::Value::Table(toml::Table::new());
+        }
+        current = entry.as_table_mut().expect("just made a table");
+    }
+    current.insert(last.to_owned(), value);
+}
+
+fn into_result(problems: Vec<String>) -> Result<(), ConfigError> {
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid(problems))
+    }
+}
@@ -0,0 +1,90 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, HeaderName, HeaderValue},
+    middleware,
+    response::{IntoResponseParts, Response, ResponseParts},
+    routing::MethodRouter,
+};
+use chrono::{DateTime, Utc};
+
+use crate::http_cache::http_date;
+
+static DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+static SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+// signals that a route is deprecated (RFC 9745), optionally when it goes away (RFC 8594) and what
+// replaces it; return it from a handler alongside the body, or attach it to a whole route with
+// `deprecated`
+#[derive(Debug, Clone)]
+pub struct Deprecation {
+    pub since: DateTime<Utc>,
+    pub sunset: Option<DateTime<Utc>>,
+    // e.g. the path of the `/api/v2` equivalent
+    pub successor: Option<String>,
+}
+
+impl Deprecation {
+    pub fn new(since: DateTime<Utc>) -> Self {
+        Self {
+            since,
+            sunset: None,
+            successor: None,
+        }
+    }
+
+    pub fn sunset(mut self, sunset: DateTime<Utc>) -> Self {
+        self.sunset = Some(sunset);
+        self
+    }
+
+    pub fn successor(mut self, successor: impl Into<String>) -> Self {
+        self.successor = Some(successor.into());
+        self
+    }
+
+    fn apply(&self, headers: &mut HeaderMap) {
+        // a structured field date: `@` followed by the unix timestamp
+        if let Ok(value) = HeaderValue::from_str(&format!("@{}", self.since.timestamp())) {
+            headers.insert(DEPRECATION.clone(), value);
+        }
+        if let Some(value) = self
+            .sunset
+            .and_then(|sunset| HeaderValue::from_str(&http_date(sunset)).ok())
+        {
+            headers.insert(SUNSET.clone(), value);
+        }
+        if let Some(value) = self.successor.as_ref().and_then(|successor| {
+            HeaderValue::from_str(&format!("<{successor}>; rel=\"successor-version\"")).ok()
+        }) {
+            headers.append(axum::http::header::LINK, value);
+        }
+    }
+}
+
+impl IntoResponseParts for Deprecation {
+    type Error = std::convert::Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        self.apply(res.headers_mut());
+        Ok(res)
+    }
+}
+
+async fn add_deprecation_headers(
+    State(deprecation): State<Deprecation>,
+    mut response: Response,
+) -> Response {
+    deprecation.apply(response.headers_mut());
+    response
+}
+
+// marks every response of `route`, including errors, as deprecated
+pub fn deprecated<S>(route: MethodRouter<S>, deprecation: Deprecation) -> MethodRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    route.layer(middleware::map_response_with_state(
+        deprecation,
+        add_deprecation_headers,
+    ))
+}
@@ -0,0 +1,263 @@
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use sqlx::error::ErrorKind;
+use utoipa::ToSchema;
+
+use crate::logging;
+
+// SQLSTATE codes, see https://www.postgresql.org/docs/current/errcodes-appendix.html
+const UNIQUE_VIOLATION: &str = "23505";
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+
+const SQLITE_UNIQUE_VIOLATION_PREFIX: &str = "UNIQUE constraint failed: ";
+
+// every error returned by the api; `code` is part of the public contract and must never change
+#[derive(Debug)]
+pub enum AppError {
+    // auth
+    NotLoggedIn,
+    InvalidToken,
+    TokenUserNotFound,
+    InvalidCredentials,
+    UserAlreadyExists,
+    NotAdmin,
+    // post
+    PostNotFound(String),
+    PostSlugTaken,
+    PostNotOwner,
+    CategoryNotFound,
+    // category
+    CategoryAlreadyExists,
+    // rate limiting
+    RateLimited { retry_after_secs: u64 },
+    // request
+    Validation(String),
+    PayloadTooLarge,
+    // idempotency
+    InvalidIdempotencyKey,
+    IdempotencyKeyReused,
+    IdempotencyKeyInUse,
+    // security headers
+    InvalidCspReport,
+    // feature flags
+    FeatureFlagNotFound(String),
+    FeatureFlagOverrideNotFound,
+    FeatureFlagUserNotFound,
+    InvalidFeatureFlag(String),
+    // jobs
+    JobAlreadyQueued,
+    // generic
+    Database(sqlx::Error),
+    Internal(String),
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    #[schema(example = "fail")]
+    pub status: &'static str,
+    #[schema(example = "POST_SLUG_TAKEN")]
+    pub code: &'static str,
+    pub message: String,
+    // lets clients quote the id in bug reports
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotLoggedIn => "AUTH_NOT_LOGGED_IN",
+            AppError::InvalidToken => "AUTH_INVALID_TOKEN",
+            AppError::TokenUserNotFound => "AUTH_USER_NOT_FOUND",
+            AppError::InvalidCredentials => "AUTH_INVALID_CREDENTIALS",
+            AppError::UserAlreadyExists => "AUTH_USER_EXISTS",
+            AppError::NotAdmin => "AUTH_NOT_ADMIN",
+            AppError::PostNotFound(_) => "POST_NOT_FOUND",
+            AppError::PostSlugTaken => "POST_SLUG_TAKEN",
+            AppError::PostNotOwner => "POST_NOT_OWNER",
+            AppError::CategoryNotFound => "POST_CATEGORY_NOT_FOUND",
+            AppError::CategoryAlreadyExists => "CATEGORY_EXISTS",
+            AppError::RateLimited { .. } => "RATE_LIMITED",
+            AppError::Validation(_) => "VALIDATION_ERROR",
+            AppError::PayloadTooLarge => "PAYLOAD_TOO_LARGE",
+            AppError::InvalidIdempotencyKey => "IDEMPOTENCY_KEY_INVALID",
+            AppError::IdempotencyKeyReused => "IDEMPOTENCY_KEY_REUSED",
+            AppError::IdempotencyKeyInUse => "IDEMPOTENCY_KEY_IN_USE",
+            AppError::InvalidCspReport => "CSP_REPORT_INVALID",
+            AppError::FeatureFlagNotFound(_) => "FEATURE_FLAG_NOT_FOUND",
+            AppError::FeatureFlagOverrideNotFound => "FEATURE_FLAG_OVERRIDE_NOT_FOUND",
+            AppError::FeatureFlagUserNotFound => "FEATURE_FLAG_USER_NOT_FOUND",
+            AppError::InvalidFeatureFlag(_) => "FEATURE_FLAG_INVALID",
+            AppError::JobAlreadyQueued => "JOB_ALREADY_QUEUED",
+            AppError::Database(_) => "DATABASE_ERROR",
+            AppError::Internal(_) => "INTERNAL_ERROR",
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::NotLoggedIn
+            | AppError::InvalidToken
+            | AppError::TokenUserNotFound
+            | AppError::PostNotOwner => StatusCode::UNAUTHORIZED,
+            AppError::NotAdmin => StatusCode::FORBIDDEN,
+            AppError::InvalidCredentials
+            | AppError::Validation(_)
+            | AppError::InvalidIdempotencyKey
+            | AppError::InvalidCspReport
+            | AppError::InvalidFeatureFlag(_) => StatusCode::BAD_REQUEST,
+            AppError::UserAlreadyExists
+            | AppError::PostSlugTaken
+            | AppError::CategoryAlreadyExists
+            | AppError::IdempotencyKeyInUse
+            | AppError::JobAlreadyQueued => StatusCode::CONFLICT,
+            AppError::PostNotFound(_)
+            | AppError::FeatureFlagNotFound(_)
+            | AppError::FeatureFlagOverrideNotFound => StatusCode::NOT_FOUND,
+            AppError::CategoryNotFound
+            | AppError::IdempotencyKeyReused
+            | AppError::FeatureFlagUserNotFound => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Database(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            AppError::NotLoggedIn => "You are not logged in, please login and try again".into(),
+            AppError::InvalidToken => "Invalid token".into(),
+            AppError::TokenUserNotFound => {
+                "The user belonging to this token no longer exists".into()
+            }
+            AppError::InvalidCredentials => "Invalid email or password".into(),
+            AppError::UserAlreadyExists => "User already exists, please login".into(),
+            AppError::NotAdmin => "This action requires an admin account".into(),
+            AppError::PostNotFound(slug) => format!("Post item with slug: {} not found", slug),
+            AppError::PostSlugTaken => "Post with that slug already exists".into(),
+            AppError::PostNotOwner => "You are not authorized to modify this post".into(),
+            AppError::CategoryNotFound => "Category does not exist".into(),
+            AppError::CategoryAlreadyExists => "Category with that name already exists".into(),
+            AppError::RateLimited { retry_after_secs } => {
+                format!("Too many requests, please try again in {retry_after_secs}s")
+            }
+            AppError::Validation(reason) => reason.clone(),
+            AppError::PayloadTooLarge => "The request body is too large".into(),
+            AppError::InvalidIdempotencyKey => {
+                "The Idempotency-Key header must be 1 to 255 visible ASCII characters".into()
+            }
+            AppError::IdempotencyKeyReused => {
+                "This Idempotency-Key was already used for a different request".into()
+            }
+            AppError::IdempotencyKeyInUse => {
+                "A request with this Idempotency-Key is still being processed".into()
+            }
+            AppError::InvalidCspReport => "The body is not a csp violation report".into(),
+            AppError::FeatureFlagNotFound(name) => format!("Feature flag {name} not found"),
+            AppError::FeatureFlagOverrideNotFound => {
+                "The user has no override for this feature flag".into()
+            }
+            AppError::FeatureFlagUserNotFound => "User does not exist".into(),
+            AppError::InvalidFeatureFlag(reason) => reason.clone(),
+            AppError::JobAlreadyQueued => "A queued or running job has the same unique key".into(),
+            // internal details are logged, never sent to the client
+            AppError::Database(_) => "Database error".into(),
+            AppError::Internal(_) => "Something bad happened, please try again later".into(),
+        }
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        if let Some(db_error) = e.as_database_error() {
+            match (db_error.code().as_deref(), db_error.constraint()) {
+                (Some(UNIQUE_VIOLATION), Some("post_slug_key")) => return AppError::PostSlugTaken,
+                (Some(UNIQUE_VIOLATION), Some("users_email_key" | "users_username_key")) => {
+                    return AppError::UserAlreadyExists
+                }
+                (Some(UNIQUE_VIOLATION), Some("category_name_key")) => {
+                    return AppError::CategoryAlreadyExists
+                }
+                (Some(FOREIGN_KEY_VIOLATION), Some("fk_category")) => {
+                    return AppError::CategoryNotFound
+                }
+                (Some(FOREIGN_KEY_VIOLATION), Some("fk_users")) => {
+                    return AppError::TokenUserNotFound
+                }
+                (Some(FOREIGN_KEY_VIOLATION), Some("fk_feature_flag_user")) => {
+                    return AppError::FeatureFlagUserNotFound
+                }
+                (Some(UNIQUE_VIOLATION), Some("jobs_unique_key_idx")) => {
+                    return AppError::JobAlreadyQueued
+                }
+                _ => {}
+            }
+
+            // sqlite doesn't report constraint names, only the offending columns
+            if db_error.kind() == ErrorKind::UniqueViolation {
+                match db_error
+                    .message()
+                    .strip_prefix(SQLITE_UNIQUE_VIOLATION_PREFIX)
+                {
+                    Some("post.slug") => return AppError::PostSlugTaken,
+                    Some("users.email" | "users.username") => return AppError::UserAlreadyExists,
+                    Some("category.name") => return AppError::CategoryAlreadyExists,
+                    Some("jobs.unique_key") => return AppError::JobAlreadyQueued,
+                    _ => {}
+                }
+            }
+        }
+        AppError::Database(e)
+    }
+}
+
+// the rejections of `extract::Json`, `Query` and `Path`; axum's message says what was wrong
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return AppError::PayloadTooLarge;
+        }
+        AppError::Validation(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        AppError::Validation(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for AppError {
+    fn from(rejection: PathRejection) -> Self {
+        AppError::Validation(rejection.body_text())
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        match &self {
+            AppError::Database(e) => tracing::error!("Database error: {e:?}"),
+            AppError::Internal(e) => tracing::error!("Internal error: {e}"),
+            _ => {}
+        }
+
+        let error_response = ErrorResponse {
+            status: "fail",
+            code: self.code(),
+            message: self.message(),
+            request_id: logging::current_request_id(),
+        };
+        let mut response = (self.status_code(), Json(error_response)).into_response();
+        if let AppError::RateLimited { retry_after_secs } = self {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after_secs));
+        }
+        response
+    }
+}
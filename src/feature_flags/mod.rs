@@ -0,0 +1,214 @@
+pub mod postgres;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use axum::{extract::FromRequestParts, http::request::Parts};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::FeatureFlagsConfig, error::AppError, guard::MaybeUser,
+    repository::FeatureFlagRepository, AppState,
+};
+
+pub use postgres::PgFeatureFlagBus;
+
+// tells the other replicas that a flag changed; without one they see the change after
+// `feature_flags.ttl_secs`. The other replicas hear it through `notify::listen_worker`.
+#[async_trait]
+pub trait FeatureFlagBus: Send + Sync {
+    async fn publish(&self, name: &str) -> Result<(), AppError>;
+}
+
+struct Flag {
+    enabled: bool,
+    rollout_percentage: i32,
+    overrides: HashMap<i32, bool>,
+}
+
+impl Flag {
+    // an override wins; otherwise a disabled flag is off and an enabled one is on for
+    // `rollout_percentage` percent of the users. Anonymous requests only get flags rolled out to
+    // everyone.
+    fn is_enabled(&self, name: &str, user_id: Option<i32>) -> bool {
+        if let Some(enabled) = user_id.and_then(|id| self.overrides.get(&id)) {
+            return *enabled;
+        }
+        if !self.enabled {
+            return false;
+        }
+
+        match user_id {
+            _ if self.rollout_percentage >= 100 => true,
+            Some(user_id) => (bucket(name, user_id) as i32) < self.rollout_percentage,
+            None => false,
+        }
+    }
+}
+
+// 0 to 99, stable across replicas and restarts. A user stays in a rollout while its percentage
+// grows, and every flag picks its own users.
+fn bucket(name: &str, user_id: i32) -> u64 {
+    let digest = Sha256::digest(format!("{name}:{user_id}"));
+    let mut prefix = [0; 8];
+    prefix.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(prefix) % 100
+}
+
+struct Snapshot {
+    flags: HashMap<String, Flag>,
+    loaded_at: Instant,
+}
+
+#[derive(Default)]
+struct State {
+    snapshot: Option<Arc<Snapshot>>,
+    // bumped by every invalidation; a load that started before one is not kept, since it may
+    // have read the flags before the change
+    generation: u64,
+}
+
+// every flag with its overrides, read from the database in one go; the table holds a handful of
+// rows and is read on every request that asks for a flag
+pub struct FeatureFlagCache {
+    repository: Arc<dyn FeatureFlagRepository>,
+    ttl: Duration,
+    state: Mutex<State>,
+    bus: Option<Arc<dyn FeatureFlagBus>>,
+}
+
+impl FeatureFlagCache {
+    pub fn new(config: &FeatureFlagsConfig, repository: Arc<dyn FeatureFlagRepository>) -> Self {
+        Self {
+            repository,
+            ttl: Duration::from_secs(config.ttl_secs),
+            state: Mutex::default(),
+            bus: None,
+        }
+    }
+
+    pub fn with_bus(mut self, bus: Arc<dyn FeatureFlagBus>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    pub fn bus(&self) -> Option<&Arc<dyn FeatureFlagBus>> {
+        self.bus.as_ref()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // the state is valid after any panic while holding the lock
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn snapshot(&self) -> Result<Arc<Snapshot>, AppError> {
+        let generation = {
+            let state = self.state();
+            if let Some(snapshot) = &state.snapshot {
+                if snapshot.loaded_at.elapsed() < self.ttl {
+                    return Ok(snapshot.clone());
+                }
+            }
+            state.generation
+        };
+
+        let loaded_at = Instant::now();
+        let mut flags = self
+            .repository
+            .list()
+            .await?
+            .into_iter()
+            .map(|flag| {
+                let flag_state = Flag {
+                    enabled: flag.enabled,
+                    rollout_percentage: flag.rollout_percentage,
+                    overrides: HashMap::new(),
+                };
+                (flag.name, flag_state)
+            })
+            .collect::<HashMap<_, _>>();
+        for flag_override in self.repository.list_overrides().await? {
+            if let Some(flag) = flags.get_mut(&flag_override.flag_name) {
+                flag.overrides
+                    .insert(flag_override.user_id, flag_override.enabled);
+            }
+        }
+        let snapshot = Arc::new(Snapshot { flags, loaded_at });
+
+        let mut state = self.state();
+        if state.generation == generation {
+            state.snapshot = Some(snapshot.clone());
+        }
+        Ok(snapshot)
+    }
+
+    // the flags as seen by `user_id`, `None` for anonymous requests
+    pub async fn for_user(&self, user_id: Option<i32>) -> Result<FeatureFlags, AppError> {
+        Ok(FeatureFlags {
+            snapshot: self.snapshot().await?,
+            user_id,
+        })
+    }
+
+    // called after a flag or one of its overrides changed. The other replicas are told through
+    // the bus; if that fails they catch up within the ttl.
+    pub async fn invalidate(&self, name: &str) {
+        self.invalidate_local();
+
+        if let Some(bus) = &self.bus {
+            if let Err(e) = bus.publish(name).await {
+                tracing::warn!(name, "Publishing a feature flag change failed: {e:?}");
+            }
+        }
+    }
+
+    // the next request reads the flags again
+    pub fn invalidate_local(&self) {
+        let mut state = self.state();
+        state.generation += 1;
+        state.snapshot = None;
+    }
+}
+
+// the flags for the current request, e.g. `if flags.is_enabled("comments") { ... }`, for the user
+// of `MaybeUser`
+pub struct FeatureFlags {
+    snapshot: Arc<Snapshot>,
+    user_id: Option<i32>,
+}
+
+impl FeatureFlags {
+    // unknown flags are off
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.snapshot
+            .flags
+            .get(name)
+            .is_some_and(|flag| flag.is_enabled(name, self.user_id))
+    }
+
+    // every flag by name
+    pub fn all(&self) -> BTreeMap<&str, bool> {
+        self.snapshot
+            .flags
+            .iter()
+            .map(|(name, flag)| (name.as_str(), flag.is_enabled(name, self.user_id)))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for FeatureFlags {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        data: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let MaybeUser(user) = MaybeUser::from_request_parts(parts, data).await?;
+        data.feature_flags.for_user(user.map(|user| user.id)).await
+    }
+}
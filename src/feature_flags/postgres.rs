@@ -0,0 +1,36 @@
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+use crate::{error::AppError, notify};
+
+use super::{FeatureFlagBus, FeatureFlagCache};
+
+pub const CHANNEL: &str = "blogrs_feature_flags";
+
+// changes through `NOTIFY` on `CHANNEL` with the name of the flag. Every change drops all flags,
+// since they are read together anyway.
+#[derive(Clone)]
+pub struct PgFeatureFlagBus {
+    pool: PgPool,
+}
+
+impl PgFeatureFlagBus {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl FeatureFlagBus for PgFeatureFlagBus {
+    async fn publish(&self, name: &str) -> Result<(), AppError> {
+        notify::notify(&self.pool, CHANNEL, name).await
+    }
+}
+
+// drops the flags of `cache` for a payload of `CHANNEL`, see `notify::listen_worker`
+pub fn receive(cache: &FeatureFlagCache, name: Option<&str>) {
+    if let Some(name) = name {
+        tracing::debug!(name, "Feature flag changed");
+    }
+    cache.invalidate_local();
+}
@@ -0,0 +1,289 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    error::AppError,
+    model::PostStatus,
+    password::hash_password,
+    repository::{Actor, NewUser, PostData},
+    AppState,
+};
+
+// seed data for staging and demos. Records are matched by their natural key (username, category
+// name, post slug) and existing ones are left untouched, so loading the same file twice is a
+// no-op. Posts and users reference each other by those keys, never by id.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fixtures {
+    pub users: Vec<UserFixture>,
+    pub categories: Vec<CategoryFixture>,
+    pub posts: Vec<PostFixture>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserFixture {
+    pub username: String,
+    pub email: String,
+    // plaintext, hashed like on registration
+    pub password: String,
+    #[serde(default)]
+    pub is_admin: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CategoryFixture {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostFixture {
+    pub slug: String,
+    pub title: String,
+    #[serde(default)]
+    pub excerpt: String,
+    pub content: String,
+    // username of a user in the database or earlier in the fixtures
+    pub author: String,
+    // name of a category; the post is uncategorized without one
+    pub category: Option<String>,
+    // fixtures are mostly demo content, so published unless given
+    #[serde(default = "published")]
+    pub status: PostStatus,
+}
+
+fn published() -> PostStatus {
+    PostStatus::Published
+}
+
+#[derive(Debug)]
+pub enum FixtureError {
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        message: String,
+    },
+    // a post referencing a user or category that doesn't exist, or a user whose email belongs
+    // to another username
+    Invalid(String),
+    Database(AppError),
+}
+
+impl fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixtureError::Read { path, source } => {
+                write!(
+                    f,
+                    "could not read fixture file {}: {source}",
+                    path.display()
+                )
+            }
+            FixtureError::Parse { path, message } => {
+                write!(f, "invalid fixture file {}: {message}", path.display())
+            }
+            FixtureError::Invalid(message) => write!(f, "invalid fixtures: {message}"),
+            FixtureError::Database(AppError::Database(e)) => write!(f, "database error: {e}"),
+            FixtureError::Database(e) => write!(f, "{}", e.message()),
+        }
+    }
+}
+
+impl std::error::Error for FixtureError {}
+
+impl From<AppError> for FixtureError {
+    fn from(e: AppError) -> Self {
+        FixtureError::Database(e)
+    }
+}
+
+// what a load created; everything else already existed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LoadReport {
+    pub users: usize,
+    pub categories: usize,
+    pub posts: usize,
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "created {} users, {} categories and {} posts",
+            self.users, self.categories, self.posts
+        )
+    }
+}
+
+impl Fixtures {
+    // the format is picked by extension: `.yaml`, `.yml` or `.json`
+    pub fn from_path(path: &Path) -> Result<Self, FixtureError> {
+        let content = std::fs::read_to_string(path).map_err(|source| FixtureError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let parse_error = |message: String| FixtureError::Parse {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => {
+                serde_yaml::from_str(&content).map_err(|e| parse_error(e.to_string()))
+            }
+            Some("json") => serde_json::from_str(&content).map_err(|e| parse_error(e.to_string())),
+            _ => Err(parse_error(
+                "expected a .yaml, .yml or .json extension".to_string(),
+            )),
+        }
+    }
+
+    // users first, then categories, then posts, so posts can reference both. A failure leaves the
+    // records loaded so far in place; fix the file and load it again.
+    pub async fn load(&self, state: &AppState) -> Result<LoadReport, FixtureError> {
+        let mut report = LoadReport::default();
+
+        for user in &self.users {
+            if load_user(state, user).await? {
+                report.users += 1;
+            }
+        }
+
+        for category in &self.categories {
+            if find_category(state, &category.name).await?.is_some() {
+                continue;
+            }
+            match state
+                .categories
+                .create(&category.name, &Actor::default())
+                .await
+            {
+                Ok(_) => report.categories += 1,
+                // created concurrently, e.g. by another replica loading the same file
+                Err(AppError::CategoryAlreadyExists) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        for post in &self.posts {
+            if load_post(state, post).await? {
+                report.posts += 1;
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+// loads the files in order, so later files can reference records of earlier ones
+pub async fn load_paths(state: &AppState, paths: &[PathBuf]) -> Result<LoadReport, FixtureError> {
+    let mut total = LoadReport::default();
+    for path in paths {
+        let report = Fixtures::from_path(path)?.load(state).await?;
+        tracing::info!("Loaded fixtures from {}: {report}", path.display());
+
+        total.users += report.users;
+        total.categories += report.categories;
+        total.posts += report.posts;
+    }
+    Ok(total)
+}
+
+async fn load_user(state: &AppState, user: &UserFixture) -> Result<bool, FixtureError> {
+    if state
+        .users
+        .find_by_username(&user.username)
+        .await?
+        .is_some()
+    {
+        return Ok(false);
+    }
+
+    // stored lowercased, like `register_user_handler` does
+    let email = user.email.to_ascii_lowercase();
+    let new_user = NewUser {
+        username: user.username.to_owned(),
+        email: email.to_owned(),
+        password: hash_password(&user.password)?,
+        is_admin: user.is_admin,
+    };
+
+    match state.users.create(new_user, &Actor::default()).await {
+        Ok(_) => Ok(true),
+        Err(AppError::UserAlreadyExists) => {
+            if state
+                .users
+                .find_by_username(&user.username)
+                .await?
+                .is_some()
+            {
+                return Ok(false);
+            }
+            Err(FixtureError::Invalid(format!(
+                "user `{}`: the email {email} belongs to another user",
+                user.username
+            )))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn load_post(state: &AppState, post: &PostFixture) -> Result<bool, FixtureError> {
+    if state.posts.find_by_slug(&post.slug).await?.is_some() {
+        return Ok(false);
+    }
+
+    let author = state
+        .users
+        .find_by_username(&post.author)
+        .await?
+        .ok_or_else(|| {
+            FixtureError::Invalid(format!(
+                "post `{}`: no user named `{}`",
+                post.slug, post.author
+            ))
+        })?;
+
+    let category_id = match &post.category {
+        Some(name) => Some(find_category(state, name).await?.ok_or_else(|| {
+            FixtureError::Invalid(format!("post `{}`: no category named `{name}`", post.slug))
+        })?),
+        None => None,
+    };
+
+    let data = PostData {
+        title: post.title.to_owned(),
+        slug: post.slug.to_owned(),
+        excerpt: post.excerpt.to_owned(),
+        content: post.content.to_owned(),
+        category_id,
+    };
+    match state
+        .posts
+        .create(author.id, data, post.status, Vec::new(), &Actor::default())
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(AppError::PostSlugTaken) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn find_category(state: &AppState, name: &str) -> Result<Option<i32>, FixtureError> {
+    Ok(state
+        .categories
+        .list()
+        .await?
+        .into_iter()
+        .find(|category| category.name == name)
+        .map(|category| category.id))
+}
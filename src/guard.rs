@@ -1,37 +1,43 @@
 use std::sync::Arc;
 
+use async_trait::async_trait;
 use axum::{
     body::Body,
-    extract::State,
-    http::{header, Request, StatusCode},
+    extract::{FromRequestParts, State},
+    http::{header, request::Parts, HeaderMap, Request},
     middleware::Next,
     response::IntoResponse,
-    Json,
 };
 
 use axum_extra::extract::cookie::CookieJar;
 use jsonwebtoken::{decode, DecodingKey, Validation};
-use serde::Serialize;
 
-use crate::{model::UserModel, schema::TokenClaims, AppState};
-
-#[derive(Debug, Serialize)]
-pub struct ErrorResponse {
-    pub status: &'static str,
-    pub message: String,
-}
+use crate::{error::AppError, model::UserModel, schema::TokenClaims, AppState};
 
 pub async fn auth_guard_middleware(
     cookie_jar: CookieJar,
     State(data): State<Arc<AppState>>,
     mut req: Request<Body>,
     next: Next,
-) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<impl IntoResponse, AppError> {
+    let user = authenticate(&cookie_jar, req.headers(), &data).await?;
+
+    tracing::Span::current().record("user_id", user.id);
+    req.extensions_mut().insert(user);
+    Ok(next.run(req).await)
+}
+
+// the user of the `token` cookie or, without one, of the bearer token
+pub async fn authenticate(
+    cookie_jar: &CookieJar,
+    headers: &HeaderMap,
+    data: &AppState,
+) -> Result<UserModel, AppError> {
     let token = cookie_jar
         .get("token")
         .map(|cookie| cookie.value().to_string())
         .or_else(|| {
-            req.headers()
+            headers
                 .get(header::AUTHORIZATION)
                 .and_then(|auth_header| auth_header.to_str().ok())
                 .and_then(|auth_value| {
@@ -40,51 +46,56 @@ pub async fn auth_guard_middleware(
                         .map(|stripped| stripped.to_owned())
                 })
         })
-        .ok_or_else(|| {
-            let error_response = ErrorResponse {
-                status: "fail",
-                message: "You are not logged in, please login and try again".to_string(),
-            };
-            (StatusCode::UNAUTHORIZED, Json(error_response))
-        })?;
+        .ok_or(AppError::NotLoggedIn)?;
 
     let claims = decode::<TokenClaims>(
         &token,
-        &DecodingKey::from_secret(data.env.jwt_secret.as_ref()),
+        &DecodingKey::from_secret(data.config.auth.jwt_secret.as_ref()),
         &Validation::default(),
     )
-    .map_err(|_| {
-        let error_response = ErrorResponse {
-            status: "fail",
-            message: "Invalid token".to_string(),
-        };
-        (StatusCode::UNAUTHORIZED, Json(error_response))
-    })?
+    .map_err(|_| AppError::InvalidToken)?
     .claims;
 
-    let user = sqlx::query_as!(
-        UserModel,
-        "SELECT * FROM users WHERE email = $1",
-        claims.email
-    )
-    .fetch_optional(&data.db)
-    .await
-    .map_err(|e| {
-        tracing::error!("Error fetching user from database: {e:?}");
-        let error_response = ErrorResponse {
-            status: "fail",
-            message: "Error fetching user from database".to_string(),
-        };
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-    })?
-    .ok_or_else(|| {
-        let error_response = ErrorResponse {
-            status: "fail",
-            message: "The user belonging to this token no longer exists".to_string(),
-        };
-        (StatusCode::UNAUTHORIZED, Json(error_response))
-    })?;
+    data.users
+        .find_by_email(&claims.email)
+        .await?
+        .ok_or(AppError::TokenUserNotFound)
+}
+
+// the user of the request on routes that also serve anonymous requests: the one authenticated by
+// `auth_guard_middleware` or else a valid token or cookie. Anything else counts as anonymous.
+pub struct MaybeUser(pub Option<UserModel>);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for MaybeUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        data: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(user) = parts.extensions.get::<UserModel>() {
+            return Ok(Self(Some(user.clone())));
+        }
+
+        let cookie_jar = CookieJar::from_headers(&parts.headers);
+        let user = authenticate(&cookie_jar, &parts.headers, data).await.ok();
+        Ok(Self(user))
+    }
+}
+
+// only lets admins through; relies on `auth_guard_middleware` running first
+pub async fn admin_guard_middleware(
+    req: Request<Body>,
+    next: Next,
+) -> Result<impl IntoResponse, AppError> {
+    let is_admin = req
+        .extensions()
+        .get::<UserModel>()
+        .is_some_and(|user| user.is_admin);
+    if !is_admin {
+        return Err(AppError::NotAdmin);
+    }
 
-    req.extensions_mut().insert(user);
     Ok(next.run(req).await)
 }
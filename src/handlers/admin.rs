@@ -0,0 +1,282 @@
+use std::sync::Arc;
+
+use axum::{debug_handler, extract::State, response::IntoResponse};
+
+use crate::{
+    error::AppError,
+    extract::{Json, Path, Query},
+    repository::{Actor, AuditFilter, FeatureFlagData},
+    schema::{
+        AuditLogFilterOptions, FeatureFlagOverrideSchema, FeatureFlagSchema,
+        SetFeatureFlagOverrideSchema, UpsertFeatureFlagSchema,
+    },
+    AppState,
+};
+
+const MAX_FLAG_NAME_LENGTH: usize = 100;
+
+// names end up in code and urls, so they are kept to lowercase ascii like `new_editor` or
+// `search.v2`
+fn validate_flag_name(name: &str) -> Result<(), AppError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_FLAG_NAME_LENGTH
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b"_.-".contains(&b));
+    if !valid {
+        return Err(AppError::InvalidFeatureFlag(format!(
+            "Feature flag names must be 1 to {MAX_FLAG_NAME_LENGTH} characters of a-z, 0-9, `_`, `.` and `-`"
+        )));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/audit",
+    tag = "admin",
+    params(AuditLogFilterOptions),
+    responses(
+        (status = 200, description = "Audit log entries, newest first", body = AuditLogResponse),
+        (status = 400, description = "Invalid filter"),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "`AUTH_NOT_ADMIN`", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
+#[debug_handler]
+pub async fn fetch_audit_log_handler(
+    Query(opts): Query<AuditLogFilterOptions>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pagination = &data.config.pagination;
+    let limit = opts
+        .limit
+        .unwrap_or(pagination.default_page_size)
+        .min(pagination.max_page_size);
+    let offset = opts.page.unwrap_or(1).saturating_sub(1) * limit;
+
+    let filter = AuditFilter {
+        actor_id: opts.actor_id,
+        action: opts.action,
+        target_type: opts.target_type,
+        target_id: opts.target_id,
+        since: opts.since,
+        until: opts.until,
+    };
+    let entries = data
+        .audit
+        .list(&filter, limit as i64, offset as i64)
+        .await?;
+
+    let response = serde_json::json!({
+        "status": "success",
+        "data": entries,
+    });
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/feature_flags",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Every feature flag with its overrides, by name", body = FeatureFlagListResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "`AUTH_NOT_ADMIN`", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
+#[debug_handler]
+pub async fn fetch_feature_flags_handler(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let flags = data.feature_flag_store.list().await?;
+    let overrides = data.feature_flag_store.list_overrides().await?;
+
+    let flags = flags
+        .into_iter()
+        .map(|flag| FeatureFlagSchema {
+            overrides: overrides
+                .iter()
+                .filter(|o| o.flag_name == flag.name)
+                .map(|o| FeatureFlagOverrideSchema {
+                    user_id: o.user_id,
+                    enabled: o.enabled,
+                })
+                .collect(),
+            name: flag.name,
+            description: flag.description,
+            enabled: flag.enabled,
+            rollout_percentage: flag.rollout_percentage,
+            created_at: flag.created_at,
+            updated_at: flag.updated_at,
+        })
+        .collect::<Vec<_>>();
+
+    let response = serde_json::json!({
+        "status": "success",
+        "data": flags,
+    });
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/feature_flags/{name}",
+    tag = "admin",
+    params(("name" = String, Path, description = "Name of the flag")),
+    request_body = UpsertFeatureFlagSchema,
+    responses(
+        (status = 200, description = "The created or updated flag", body = FeatureFlagResponse),
+        (status = 400, description = "`FEATURE_FLAG_INVALID`", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "`AUTH_NOT_ADMIN`", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
+#[debug_handler]
+pub async fn upsert_feature_flag_handler(
+    Path(name): Path<String>,
+    State(data): State<Arc<AppState>>,
+    actor: Actor,
+    Json(payload): Json<UpsertFeatureFlagSchema>,
+) -> Result<impl IntoResponse, AppError> {
+    validate_flag_name(&name)?;
+    if payload
+        .rollout_percentage
+        .is_some_and(|percentage| !(0..=100).contains(&percentage))
+    {
+        return Err(AppError::InvalidFeatureFlag(
+            "rollout_percentage must be between 0 and 100".into(),
+        ));
+    }
+
+    let changes = FeatureFlagData {
+        description: payload.description,
+        enabled: payload.enabled,
+        rollout_percentage: payload.rollout_percentage,
+    };
+    let flag = data
+        .feature_flag_store
+        .upsert(&name, changes, &actor)
+        .await?;
+    data.feature_flags.invalidate(&name).await;
+
+    tracing::info!(
+        name,
+        enabled = flag.enabled,
+        rollout_percentage = flag.rollout_percentage,
+        "Feature flag updated"
+    );
+    let response = serde_json::json!({
+        "status": "success",
+        "data": flag,
+    });
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/feature_flags/{name}",
+    tag = "admin",
+    params(("name" = String, Path, description = "Name of the flag")),
+    responses(
+        (status = 200, description = "The flag and its overrides were deleted", body = StatusResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "`AUTH_NOT_ADMIN`", body = ErrorResponse),
+        (status = 404, description = "`FEATURE_FLAG_NOT_FOUND`", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
+#[debug_handler]
+pub async fn delete_feature_flag_handler(
+    Path(name): Path<String>,
+    State(data): State<Arc<AppState>>,
+    actor: Actor,
+) -> Result<impl IntoResponse, AppError> {
+    data.feature_flag_store
+        .delete(&name, &actor)
+        .await?
+        .ok_or_else(|| AppError::FeatureFlagNotFound(name.clone()))?;
+    data.feature_flags.invalidate(&name).await;
+
+    tracing::info!(name, "Feature flag deleted");
+    Ok(Json(serde_json::json!({"status": "success"})))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/feature_flags/{name}/overrides/{user_id}",
+    tag = "admin",
+    params(
+        ("name" = String, Path, description = "Name of the flag"),
+        ("user_id" = i32, Path, description = "Id of the user"),
+    ),
+    request_body = SetFeatureFlagOverrideSchema,
+    responses(
+        (status = 200, description = "The created or updated override", body = FeatureFlagOverrideResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "`AUTH_NOT_ADMIN`", body = ErrorResponse),
+        (status = 404, description = "`FEATURE_FLAG_NOT_FOUND`", body = ErrorResponse),
+        (status = 422, description = "`FEATURE_FLAG_USER_NOT_FOUND`", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
+#[debug_handler]
+pub async fn set_feature_flag_override_handler(
+    Path((name, user_id)): Path<(String, i32)>,
+    State(data): State<Arc<AppState>>,
+    actor: Actor,
+    Json(payload): Json<SetFeatureFlagOverrideSchema>,
+) -> Result<impl IntoResponse, AppError> {
+    let flag_override = data
+        .feature_flag_store
+        .set_override(&name, user_id, payload.enabled, &actor)
+        .await?;
+    data.feature_flags.invalidate(&name).await;
+
+    tracing::info!(
+        name,
+        user_id,
+        enabled = payload.enabled,
+        "Feature flag override set"
+    );
+    let response = serde_json::json!({
+        "status": "success",
+        "data": flag_override,
+    });
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/feature_flags/{name}/overrides/{user_id}",
+    tag = "admin",
+    params(
+        ("name" = String, Path, description = "Name of the flag"),
+        ("user_id" = i32, Path, description = "Id of the user"),
+    ),
+    responses(
+        (status = 200, description = "The user gets the flag's own value again", body = StatusResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "`AUTH_NOT_ADMIN`", body = ErrorResponse),
+        (status = 404, description = "`FEATURE_FLAG_OVERRIDE_NOT_FOUND`", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
+#[debug_handler]
+pub async fn delete_feature_flag_override_handler(
+    Path((name, user_id)): Path<(String, i32)>,
+    State(data): State<Arc<AppState>>,
+    actor: Actor,
+) -> Result<impl IntoResponse, AppError> {
+    data.feature_flag_store
+        .delete_override(&name, user_id, &actor)
+        .await?
+        .ok_or(AppError::FeatureFlagOverrideNotFound)?;
+    data.feature_flags.invalidate(&name).await;
+
+    tracing::info!(name, user_id, "Feature flag override deleted");
+    Ok(Json(serde_json::json!({"status": "success"})))
+}
@@ -1,95 +1,87 @@
 use std::sync::Arc;
 
-use argon2::{password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
 use axum::{
     debug_handler,
     extract::State,
-    http::{header, Response, StatusCode},
+    http::{header, Response},
     response::IntoResponse,
-    Extension, Json,
+    Extension,
 };
 
 use axum_extra::extract::cookie::{Cookie, SameSite};
 use jsonwebtoken::{encode, EncodingKey, Header};
-use rand_core::OsRng;
 use serde_json::json;
 
 use crate::{
-    model::UserModel,
+    config::CookieSameSite,
+    error::AppError,
+    extract::Json,
+    model::{AuditAction, UserModel},
+    monitoring,
+    password::{hash_password, verify_password},
+    repository::{Actor, NewAuditEntry, NewUser},
     schema::{LoginUserSchema, RegisterUserSchema, TokenClaims, UserDataSchema},
     AppState,
 };
 
+fn same_site(policy: CookieSameSite) -> SameSite {
+    match policy {
+        CookieSameSite::Strict => SameSite::Strict,
+        CookieSameSite::Lax => SameSite::Lax,
+        CookieSameSite::None => SameSite::None,
+    }
+}
+
 fn filter_user_data(user: &UserModel) -> UserDataSchema {
     UserDataSchema {
         id: user.id,
         name: user.name.to_owned(),
         username: user.username.to_string(),
         email: user.email.to_string(),
+        is_admin: user.is_admin,
         created_at: user.created_at.to_owned(),
         updated_at: user.updated_at.to_owned(),
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/register",
+    tag = "auth",
+    request_body = RegisterUserSchema,
+    responses(
+        (status = 200, description = "The registered user", body = UserResponse),
+        (status = 409, description = "`AUTH_USER_EXISTS`", body = ErrorResponse),
+    )
+)]
 #[debug_handler]
 pub async fn register_user_handler(
     State(data): State<Arc<AppState>>,
+    actor: Actor,
     Json(payload): Json<RegisterUserSchema>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let user_exists: Option<bool> =
-        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE email = $1 OR username = $2)")
-            .bind(payload.email.to_owned().to_ascii_lowercase())
-            .bind(payload.username.to_owned())
-            .fetch_one(&data.db)
-            .await
-            .map_err(|e| {
-                let error_response = serde_json::json!({
-                    "status": "fail",
-                    "message": format!("Database error: {}", e),
-                });
-                (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-            })?;
-
-    if let Some(exists) = user_exists {
-        if exists {
-            let error_response = serde_json::json!({
-                "status": "fail",
-                "message": "User already exists, please login",
-            });
-            return Err((StatusCode::CONFLICT, Json(error_response)));
-        }
+) -> Result<impl IntoResponse, AppError> {
+    let email = payload.email.to_ascii_lowercase();
+
+    if data.users.exists(&email, &payload.username).await? {
+        return Err(AppError::UserAlreadyExists);
     }
 
-    let salt = SaltString::generate(&mut OsRng);
-    let hashed_passwd = Argon2::default()
-        .hash_password(payload.password.as_bytes(), &salt)
-        .map_err(|e| {
-            tracing::error!("Error while hashing password: {e:?}");
-            let error_response = serde_json::json!({
-                "status": "fail",
-                "message": "Error while hashing password",
-            });
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-        })
-        .map(|hash| hash.to_string())?;
-
-    let user = sqlx::query_as!(
-        UserModel,
-        "INSERT INTO users (username,email,password) VALUES ($1, $2, $3) RETURNING *",
-        payload.username,
-        payload.email.to_ascii_lowercase(),
-        hashed_passwd
-    )
-    .fetch_one(&data.db)
-    .await
-    .map_err(|e| {
-        tracing::error!("Database error: {e:?}");
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": "Database error",
-        });
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-    })?;
+    let hashed_passwd = hash_password(&payload.password)?;
+
+    // a concurrent registration can still hit the unique constraints, which map to the same error
+    let user = data
+        .users
+        .create(
+            NewUser {
+                username: payload.username,
+                email,
+                password: hashed_passwd,
+                is_admin: false,
+            },
+            &actor,
+        )
+        .await?;
+    monitoring::record_registration();
 
     let response = serde_json::json!({"status": "success","data": serde_json::json!({
         "user": filter_user_data(&user)
@@ -97,75 +89,64 @@ pub async fn register_user_handler(
     Ok(Json(response))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/login",
+    tag = "auth",
+    request_body = LoginUserSchema,
+    responses(
+        (status = 200, description = "Token, also set as the httpOnly `token` cookie", body = LoginResponse),
+        (status = 400, description = "`AUTH_INVALID_CREDENTIALS`", body = ErrorResponse),
+    )
+)]
 #[debug_handler]
 pub async fn login_user_handler(
     State(data): State<Arc<AppState>>,
+    mut actor: Actor,
     Json(payload): Json<LoginUserSchema>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let user = sqlx::query_as!(
-        UserModel,
-        "SELECT * FROM users WHERE email = $1",
-        payload.email.to_ascii_lowercase()
-    )
-    .fetch_optional(&data.db)
-    .await
-    .map_err(|e| {
-        tracing::error!("Error fetching user from database: {e:?}");
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": "Error fetching user from database",
-        });
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-    })?
-    .ok_or_else(|| {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": "Invalid email or password",
-        });
-        (StatusCode::BAD_REQUEST, Json(error_response))
-    })?;
-
-    let is_valid_passwd = match PasswordHash::new(&user.password) {
-        Ok(parsed_hash) => Argon2::default()
-            .verify_password(payload.password.as_bytes(), &parsed_hash)
-            .map_or(false, |_| true),
-        Err(_) => false,
-    };
-
-    if !is_valid_passwd {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": "Invalid password",
-        });
-        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+) -> Result<impl IntoResponse, AppError> {
+    let user = data
+        .users
+        .find_by_email(&payload.email.to_ascii_lowercase())
+        .await?
+        .ok_or_else(|| {
+            monitoring::record_failed_login();
+            AppError::InvalidCredentials
+        })?;
+
+    if !verify_password(&payload.password, &user.password) {
+        monitoring::record_failed_login();
+        return Err(AppError::InvalidCredentials);
     }
 
     let claims = TokenClaims {
         iat: chrono::Utc::now().timestamp() as usize,
-        exp: (chrono::Utc::now() + chrono::Duration::hours(24)).timestamp() as usize,
+        exp: (chrono::Utc::now() + chrono::Duration::hours(data.config.auth.token_expires_in_hours))
+            .timestamp() as usize,
         email: user.email.to_owned(),
     };
 
     let token = encode(
         &Header::default(),
         &claims,
-        &EncodingKey::from_secret(data.env.jwt_secret.as_ref()),
+        &EncodingKey::from_secret(data.config.auth.jwt_secret.as_ref()),
     )
-    .map_err(|e| {
-        tracing::error!("Error while generating token: {e:?}");
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": "Error while generating token",
-        });
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
-    })?;
+    .map_err(|e| AppError::Internal(format!("Error while generating token: {e:?}")))?;
 
     let cookie = Cookie::build(("token", token.to_owned()))
         .path("/")
-        .max_age(time::Duration::hours(24 * 7))
-        .same_site(SameSite::Lax)
+        .max_age(time::Duration::hours(data.config.auth.cookie_max_age_hours))
+        .same_site(same_site(data.config.auth.cookie_same_site))
+        .secure(data.config.auth.cookie_secure)
         .http_only(true);
 
+    // the user is only known from here on
+    actor.user_id = Some(user.id);
+    data.audit
+        .record(NewAuditEntry::new(&actor, AuditAction::UserLogin, user.id))
+        .await?;
+
+    monitoring::record_login();
     let mut response = Response::new(json!({"status": "success", "token": token}).to_string());
     response
         .headers_mut()
@@ -174,13 +155,31 @@ pub async fn login_user_handler(
     Ok(response)
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/logout",
+    tag = "auth",
+    responses(
+        (status = 200, description = "The token cookie was cleared", body = StatusResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
 #[debug_handler]
 pub async fn logout_user_handler(
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    Extension(user): Extension<UserModel>,
+    State(data): State<Arc<AppState>>,
+    actor: Actor,
+) -> Result<impl IntoResponse, AppError> {
+    data.audit
+        .record(NewAuditEntry::new(&actor, AuditAction::UserLogout, user.id))
+        .await?;
+
     let cookie = Cookie::build(("token", ""))
         .path("/")
         .max_age(time::Duration::hours(-1))
-        .same_site(SameSite::Lax)
+        .same_site(same_site(data.config.auth.cookie_same_site))
+        .secure(data.config.auth.cookie_secure)
         .http_only(true);
 
     let mut response = Response::new(json!({"status": "success"}).to_string());
@@ -190,10 +189,20 @@ pub async fn logout_user_handler(
     Ok(response)
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/current_user",
+    tag = "auth",
+    responses(
+        (status = 200, description = "The logged in user", body = UserResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
 #[debug_handler]
 pub async fn current_user_handler(
     Extension(user): Extension<UserModel>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<impl IntoResponse, AppError> {
     let response = serde_json::json!({
         "status":  "success",
         "data": serde_json::json!({
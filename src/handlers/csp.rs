@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+};
+use serde::Deserialize;
+
+use crate::{error::AppError, repository::NewCspReport, AppState};
+
+// reports come from any page that loads the api, so whatever they contain is cut to this length
+const MAX_FIELD_LENGTH: usize = 2048;
+// the reporting api batches reports; anything beyond this is dropped
+const MAX_REPORTS_PER_REQUEST: usize = 20;
+
+// the `report-uri` format, sent as `application/csp-report`
+#[derive(Deserialize)]
+struct LegacyReport {
+    #[serde(rename = "csp-report")]
+    report: LegacyViolation,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct LegacyViolation {
+    #[serde(default)]
+    document_uri: String,
+    #[serde(default)]
+    violated_directive: String,
+    effective_directive: Option<String>,
+    blocked_uri: Option<String>,
+    source_file: Option<String>,
+    line_number: Option<i64>,
+    disposition: Option<String>,
+}
+
+// the reporting api format, sent as `application/reports+json`
+#[derive(Deserialize)]
+struct Report {
+    #[serde(rename = "type")]
+    kind: String,
+    body: Option<Violation>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Violation {
+    #[serde(default, rename = "documentURL")]
+    document_url: String,
+    #[serde(default)]
+    effective_directive: String,
+    #[serde(rename = "blockedURL")]
+    blocked_url: Option<String>,
+    source_file: Option<String>,
+    line_number: Option<i64>,
+    disposition: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ReportBody {
+    Legacy(LegacyReport),
+    Batch(Vec<Report>),
+}
+
+#[utoipa::path(
+    post,
+    path = "/csp-report",
+    tag = "security",
+    request_body(content = String, description = "A violation in the `application/csp-report` or `application/reports+json` format"),
+    responses(
+        (status = 204, description = "The violations were stored"),
+        (status = 400, description = "`CSP_REPORT_INVALID`", body = ErrorResponse),
+        (status = 429, description = "`RATE_LIMITED`", body = ErrorResponse),
+    )
+)]
+pub async fn csp_report_handler(
+    State(data): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, AppError> {
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(truncate);
+
+    let body = serde_json::from_slice::<ReportBody>(&body).map_err(|e| {
+        tracing::debug!("Invalid csp report: {e}");
+        AppError::InvalidCspReport
+    })?;
+    let reports = match body {
+        ReportBody::Legacy(LegacyReport { report }) => vec![NewCspReport {
+            document_uri: truncate(&report.document_uri),
+            violated_directive: truncate(
+                report
+                    .effective_directive
+                    .as_deref()
+                    .unwrap_or(&report.violated_directive),
+            ),
+            blocked_uri: report.blocked_uri.as_deref().map(truncate),
+            source_file: report.source_file.as_deref().map(truncate),
+            line_number: report.line_number,
+            disposition: disposition(report.disposition.as_deref()),
+            user_agent,
+        }],
+        ReportBody::Batch(reports) => reports
+            .into_iter()
+            .filter(|report| report.kind == "csp-violation")
+            .filter_map(|report| report.body)
+            .take(MAX_REPORTS_PER_REQUEST)
+            .map(|violation| NewCspReport {
+                document_uri: truncate(&violation.document_url),
+                violated_directive: truncate(&violation.effective_directive),
+                blocked_uri: violation.blocked_url.as_deref().map(truncate),
+                source_file: violation.source_file.as_deref().map(truncate),
+                line_number: violation.line_number,
+                disposition: disposition(violation.disposition.as_deref()),
+                user_agent: user_agent.clone(),
+            })
+            .collect(),
+    };
+
+    for report in reports {
+        data.csp_reports.create(report).await?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn disposition(value: Option<&str>) -> String {
+    match value {
+        Some("report") => "report",
+        _ => "enforce",
+    }
+    .to_string()
+}
+
+fn truncate(value: &str) -> String {
+    value.chars().take(MAX_FIELD_LENGTH).collect()
+}
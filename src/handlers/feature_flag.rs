@@ -0,0 +1,20 @@
+use axum::{debug_handler, response::IntoResponse, Json};
+
+use crate::feature_flags::FeatureFlags;
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/feature_flags",
+    tag = "feature_flags",
+    responses(
+        (status = 200, description = "Every flag and whether it is on for the caller, who is anonymous without a valid token", body = FeatureFlagValuesResponse),
+    ),
+    security((), ("bearer_auth" = []), ("cookie_auth" = []))
+)]
+#[debug_handler(state = std::sync::Arc<crate::AppState>)]
+pub async fn fetch_feature_flag_values_handler(flags: FeatureFlags) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "status": "success",
+        "data": flags.all(),
+    }))
+}
@@ -0,0 +1,219 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    error::AppError,
+    tasks::{WorkerState, WorkerStatus},
+    AppState,
+};
+
+// a check that takes longer than this is reported as failed
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+// pool usage above this ratio is reported as degraded; the instance stays in rotation
+const POOL_SATURATION_WARNING: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Degraded,
+    Fail,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CheckResult {
+    pub status: CheckStatus,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MigrationsCheck {
+    #[serde(flatten)]
+    pub result: CheckResult,
+    pub pending: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PoolCheck {
+    pub status: CheckStatus,
+    pub size: u32,
+    pub idle: u32,
+    pub in_use: u32,
+    pub max: u32,
+    pub saturation: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WorkersCheck {
+    pub status: CheckStatus,
+    pub workers: BTreeMap<String, WorkerStatus>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessChecks {
+    pub database: CheckResult,
+    pub migrations: MigrationsCheck,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool: Option<PoolCheck>,
+    pub workers: WorkersCheck,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    pub status: CheckStatus,
+    pub checks: ReadinessChecks,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LivenessResponse {
+    #[schema(example = "ok")]
+    pub status: &'static str,
+}
+
+async fn timed<T, F>(check: F) -> (Result<T, String>, f64)
+where
+    F: Future<Output = Result<T, AppError>>,
+{
+    let start = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(e)) => {
+            tracing::error!("Readiness check failed: {e:?}");
+            Err(e.message())
+        }
+        Err(_) => Err(format!("timed out after {}s", CHECK_TIMEOUT.as_secs())),
+    };
+    (result, start.elapsed().as_secs_f64() * 1000.0)
+}
+
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    responses(
+        (status = 200, description = "The process is alive", body = LivenessResponse),
+    )
+)]
+pub async fn liveness_handler() -> impl IntoResponse {
+    Json(LivenessResponse { status: "ok" })
+}
+
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, description = "Ready to serve traffic (`ok` or `degraded`)", body = ReadinessResponse),
+        (status = 503, description = "At least one check failed", body = ReadinessResponse),
+    )
+)]
+pub async fn readiness_handler(State(data): State<Arc<AppState>>) -> impl IntoResponse {
+    let (ping, latency_ms) = timed(data.database.ping()).await;
+    let database = CheckResult {
+        status: if ping.is_ok() {
+            CheckStatus::Ok
+        } else {
+            CheckStatus::Fail
+        },
+        latency_ms,
+        error: ping.err(),
+    };
+
+    let (pending, latency_ms) = timed(data.database.pending_migrations()).await;
+    let migrations = match pending {
+        Ok(pending) if pending.is_empty() => MigrationsCheck {
+            result: CheckResult {
+                status: CheckStatus::Ok,
+                latency_ms,
+                error: None,
+            },
+            pending,
+        },
+        Ok(pending) => MigrationsCheck {
+            result: CheckResult {
+                status: CheckStatus::Fail,
+                latency_ms,
+                error: Some(format!("{} migration(s) not applied", pending.len())),
+            },
+            pending,
+        },
+        Err(e) => MigrationsCheck {
+            result: CheckResult {
+                status: CheckStatus::Fail,
+                latency_ms,
+                error: Some(e),
+            },
+            pending: Vec::new(),
+        },
+    };
+
+    let pool = data.database.pool_status().map(|pool| {
+        let in_use = pool.size.saturating_sub(pool.idle);
+        let saturation = in_use as f64 / pool.max.max(1) as f64;
+        PoolCheck {
+            status: if saturation >= POOL_SATURATION_WARNING {
+                CheckStatus::Degraded
+            } else {
+                CheckStatus::Ok
+            },
+            size: pool.size,
+            idle: pool.idle,
+            in_use,
+            max: pool.max,
+            saturation,
+        }
+    });
+
+    // a worker waiting to be restarted is degraded rather than failed: the requests it does not
+    // serve still work
+    let workers = data.tasks.statuses();
+    let workers = WorkersCheck {
+        status: if workers
+            .values()
+            .any(|worker| worker.state == WorkerState::Restarting)
+        {
+            CheckStatus::Degraded
+        } else {
+            CheckStatus::Ok
+        },
+        workers,
+    };
+
+    let status = [
+        Some(database.status),
+        Some(migrations.result.status),
+        pool.as_ref().map(|pool| pool.status),
+        Some(workers.status),
+    ]
+    .into_iter()
+    .flatten()
+    .max()
+    .unwrap_or(CheckStatus::Ok);
+
+    let status_code = if status == CheckStatus::Fail {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+
+    let response = ReadinessResponse {
+        status,
+        checks: ReadinessChecks {
+            database,
+            migrations,
+            pool,
+            workers,
+        },
+    };
+    (status_code, Json(response))
+}
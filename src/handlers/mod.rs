@@ -1,2 +1,6 @@
+pub mod admin;
 pub mod auth;
+pub mod csp;
+pub mod feature_flag;
+pub mod health;
 pub mod post;
@@ -2,310 +2,514 @@ use std::sync::Arc;
 
 use axum::{
     debug_handler,
-    extract::{Path, Query, State},
-    http::StatusCode,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
     response::IntoResponse,
-    Extension, Json,
+    Extension,
 };
-use serde_json::json;
 
 use crate::{
-    model::{PostModel, UserModel},
-    schema::{CreatePostSchema, FetchAllPostSchema, FilterOptions, ParamOptions, UpdatePostSchema},
+    error::AppError,
+    extract::{Json, Path, Query},
+    guard::MaybeUser,
+    http_cache::cached_json,
+    jobs::PostCreated,
+    model::{PostStatus, UserModel},
+    monitoring,
+    repository::{Actor, NewJob, PostData, PostFilter},
+    schema::{
+        CreatePostSchema, FilterOptions, OwnPostFilterOptions, ParamOptions, UpdatePostSchema,
+    },
     AppState,
 };
 
+// unpublished posts are only served to their author, so shared caches must not keep them
+const PRIVATE_CACHE_CONTROL: &str = "private, no-cache";
+
+async fn ensure_category_exists(data: &AppState, category_id: i32) -> Result<(), AppError> {
+    data.categories
+        .find_by_id(category_id)
+        .await?
+        .map(|_| ())
+        .ok_or(AppError::CategoryNotFound)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/post",
+    tag = "post",
+    params(FilterOptions),
+    responses(
+        (status = 200, description = "Published posts, most recently published first", body = PostListResponse),
+        (status = 304, description = "Unchanged since `If-None-Match`"),
+    )
+)]
 pub async fn fetch_post_handler(
     opts: Option<Query<FilterOptions>>,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let Query(opts) = opts.unwrap_or_default();
-
-    let limit = opts.limit.unwrap_or(10);
-    let offset = (opts.page.unwrap_or(1) - 1) * limit;
-
-    let post_query = sqlx::query_as!(
-        FetchAllPostSchema,
-        r#"
-        SELECT id, title, slug, user_id, excerpt, category_id, created_at, updated_at FROM post
-        ORDER BY created_at DESC
-        LIMIT $1 OFFSET $2
-        "#,
-        limit as i32,
-        offset as i32
-    )
-    .fetch_all(&data.db)
-    .await;
-
-    if post_query.is_err() {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": "Something bad happened while fetching all note items",
-        });
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)));
-    }
-
-    let posts = post_query.unwrap();
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let opts = opts.map(|Query(opts)| opts).unwrap_or_default();
+
+    let pagination = &data.config.pagination;
+    let limit = opts
+        .limit
+        .unwrap_or(pagination.default_page_size)
+        .min(pagination.max_page_size);
+    let offset = opts.page.unwrap_or(1).saturating_sub(1) * limit;
+
+    let (limit, offset) = (limit as i64, offset as i64);
+    let filter = PostFilter::published();
+    let posts = data
+        .post_cache
+        .list(limit, offset, || data.posts.list(&filter, limit, offset))
+        .await?;
 
     let response = serde_json::json!({
         "status": "success",
-        "data": posts,
+        "data": posts.as_slice(),
     });
 
-    Ok((StatusCode::OK, Json(response)))
+    // no `Last-Modified`: deleting, unpublishing or archiving a post changes the list without
+    // raising the newest `updated_at` in it, so only the etag tells the versions apart
+    cached_json(
+        &headers,
+        &data.config.http_cache.public_cache_control,
+        None,
+        &response,
+    )
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/post/{slug}",
+    tag = "post",
+    params(("slug" = String, Path, description = "Slug of the post")),
+    responses(
+        (status = 200, description = "The post; drafts and archived posts only for their author", body = PostDetailResponse),
+        (status = 304, description = "Unchanged since `If-None-Match` / `If-Modified-Since`"),
+        (status = 404, description = "`POST_NOT_FOUND`", body = ErrorResponse),
+    )
+)]
 #[debug_handler]
 pub async fn fetch_post_detail_handler(
     Path(params): Path<ParamOptions>,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    MaybeUser(current_user): MaybeUser,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
     let post_slug = params.slug.unwrap();
 
-    let post_query = sqlx::query_as!(
-        PostModel,
-        r#"
-        SELECT * FROM post
-        WHERE slug = $1
-        "#,
-        post_slug
-    )
-    .fetch_one(&data.db)
-    .await;
-
-    if post_query.is_err() {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": format!("Post item with Slug: {} not found", post_slug),
-        });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
-    }
+    let post = data
+        .post_cache
+        .detail(&post_slug, || data.posts.find_by_slug(&post_slug))
+        .await?
+        .ok_or(AppError::PostNotFound(post_slug.to_owned()))?;
+    let last_modified = post.updated_at;
+
+    let cache_control = if post.status == PostStatus::Published {
+        data.config.http_cache.public_cache_control.as_str()
+    } else if current_user.is_some_and(|user| user.id == post.user_id) {
+        PRIVATE_CACHE_CONTROL
+    } else {
+        // the same answer as for a missing post, so drafts can't be probed for
+        return Err(AppError::PostNotFound(post_slug));
+    };
+
+    let response = serde_json::json!({
+        "status": "success",
+        "data": post.as_ref(),
+    });
+
+    cached_json(&headers, cache_control, last_modified, &response)
+}
 
-    let post = post_query.unwrap();
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/current_user/posts",
+    tag = "post",
+    params(OwnPostFilterOptions),
+    responses(
+        (status = 200, description = "Posts of the logged in user in any status; most recently published first, after the ones never published", body = PostListResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
+#[debug_handler]
+pub async fn fetch_own_posts_handler(
+    Query(opts): Query<OwnPostFilterOptions>,
+    Extension(current_user): Extension<UserModel>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let pagination = &data.config.pagination;
+    let limit = opts
+        .limit
+        .unwrap_or(pagination.default_page_size)
+        .min(pagination.max_page_size);
+    let offset = opts.page.unwrap_or(1).saturating_sub(1) * limit;
+
+    let filter = PostFilter {
+        user_id: Some(current_user.id),
+        status: opts.status,
+    };
+    let posts = data
+        .posts
+        .list(&filter, limit as i64, offset as i64)
+        .await?;
 
     let response = serde_json::json!({
         "status": "success",
-        "data": post,
+        "data": posts,
     });
+    Ok((
+        [(header::CACHE_CONTROL, PRIVATE_CACHE_CONTROL)],
+        Json(response),
+    ))
+}
 
-    Ok((StatusCode::OK, Json(response)))
+// shared by the create handlers of v1 and v2, which only differ in the status of a post that
+// doesn't set one
+async fn create_post(
+    data: &AppState,
+    current_user: &UserModel,
+    actor: &Actor,
+    payload: CreatePostSchema,
+    default_status: PostStatus,
+) -> Result<impl IntoResponse, AppError> {
+    let category_id = payload.category_id.unwrap_or(1);
+    ensure_category_exists(data, category_id).await?;
+
+    let post = PostData {
+        title: payload.title,
+        slug: payload.slug,
+        excerpt: payload.excerpt,
+        content: payload.content,
+        category_id: Some(category_id),
+    };
+    let status = payload.status.unwrap_or(default_status);
+    let job = PostCreated {
+        slug: post.slug.clone(),
+        user_id: current_user.id,
+    };
+    let jobs = vec![NewJob::new(&job)?.unique_key(format!("post_created:{}", post.slug))];
+    let created_post = data
+        .posts
+        .create(current_user.id, post, status, jobs, actor)
+        .await?;
+    data.post_cache.invalidate(&[&created_post.slug]).await;
+    monitoring::record_post_created();
+
+    tracing::info!("Successfully created post with slug: {}", created_post.slug);
+    let response = serde_json::json!({"status": "success","data": serde_json::json!({
+        "post": created_post
+    })});
+
+    Ok((StatusCode::CREATED, Json(response)))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/post/create",
+    tag = "post",
+    params(("Idempotency-Key" = Option<String>, Header, description = "Replays the first response to retries with the same key")),
+    request_body = CreatePostSchema,
+    responses(
+        (status = 201, description = "The created post, published unless the body sets `status`", body = PostResponse),
+        (status = 400, description = "`IDEMPOTENCY_KEY_INVALID`", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 409, description = "`POST_SLUG_TAKEN` or `IDEMPOTENCY_KEY_IN_USE`", body = ErrorResponse),
+        (status = 422, description = "`POST_CATEGORY_NOT_FOUND` or `IDEMPOTENCY_KEY_REUSED`", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
 #[debug_handler]
 pub async fn create_post_handler(
     Extension(current_user): Extension<UserModel>,
     State(data): State<Arc<AppState>>,
+    actor: Actor,
     Json(payload): Json<CreatePostSchema>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let title = payload.title;
-    let slug = payload.slug;
-    let excerpt = payload.excerpt;
-    let content = payload.content;
-    let category_id = payload.category_id.unwrap_or(1);
-    let user_id = current_user.id;
-
-    let create_query = sqlx::query_as!(
-        PostModel,
-        r#"
-        INSERT INTO post (title, slug, excerpt, content, category_id, user_id)
-        VALUES ($1, $2, $3, $4, $5, $6)
-        RETURNING *
-        "#,
-        title,
-        slug,
-        excerpt,
-        content,
-        category_id,
-        user_id
-    )
-    .fetch_one(&data.db)
-    .await;
-
-    match create_query {
-        Ok(created_post) => {
-            tracing::info!("Successfully created post with slug: {}", created_post.slug);
-            let response = serde_json::json!({"status": "success","data": serde_json::json!({
-                "post": created_post
-            })});
-
-            Ok((StatusCode::CREATED, Json(response)))
-        }
-        Err(e) => {
-            if e.to_string()
-                .contains("duplicate key value violates unique constraint")
-            {
-                let error_response = serde_json::json!({
-                    "status": "fail",
-                    "message": "Post with that slug already exists",
-                });
-                return Err((StatusCode::CONFLICT, Json(error_response)));
-            }
-            tracing::error!("Error creating post: {:?}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(
-                    json!({"status": "fail","message": "Something bad happened while creating the post"}),
-                ),
-            ))
-        }
-    }
+) -> Result<impl IntoResponse, AppError> {
+    // posts were always published before they had a status, and v1 clients rely on that
+    create_post(&data, &current_user, &actor, payload, PostStatus::Published).await
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v2/post/create",
+    tag = "post",
+    params(("Idempotency-Key" = Option<String>, Header, description = "Replays the first response to retries with the same key")),
+    request_body = CreatePostSchema,
+    responses(
+        (status = 201, description = "The created post, a draft unless the body sets `status`", body = PostResponse),
+        (status = 400, description = "`IDEMPOTENCY_KEY_INVALID`", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 409, description = "`POST_SLUG_TAKEN` or `IDEMPOTENCY_KEY_IN_USE`", body = ErrorResponse),
+        (status = 422, description = "`POST_CATEGORY_NOT_FOUND` or `IDEMPOTENCY_KEY_REUSED`", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
+#[debug_handler]
+pub async fn create_post_v2_handler(
+    Extension(current_user): Extension<UserModel>,
+    State(data): State<Arc<AppState>>,
+    actor: Actor,
+    Json(payload): Json<CreatePostSchema>,
+) -> Result<impl IntoResponse, AppError> {
+    create_post(&data, &current_user, &actor, payload, PostStatus::Draft).await
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/post/update/{slug}",
+    tag = "post",
+    params(
+        ("slug" = String, Path, description = "Current slug of the post"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Replays the first response to retries with the same key"),
+    ),
+    request_body = UpdatePostSchema,
+    responses(
+        (status = 200, description = "The updated post", body = PostResponse),
+        (status = 400, description = "`IDEMPOTENCY_KEY_INVALID`", body = ErrorResponse),
+        (status = 401, description = "Not logged in or `POST_NOT_OWNER`", body = ErrorResponse),
+        (status = 404, description = "`POST_NOT_FOUND`", body = ErrorResponse),
+        (status = 409, description = "`POST_SLUG_TAKEN` or `IDEMPOTENCY_KEY_IN_USE`", body = ErrorResponse),
+        (status = 422, description = "`POST_CATEGORY_NOT_FOUND` or `IDEMPOTENCY_KEY_REUSED`", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
 #[debug_handler]
 pub async fn update_post_handler(
     Path(params): Path<ParamOptions>,
     Extension(current_user): Extension<UserModel>,
     State(data): State<Arc<AppState>>,
+    actor: Actor,
     Json(payload): Json<UpdatePostSchema>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<impl IntoResponse, AppError> {
     let post_slug = params.slug.unwrap();
 
-    let post_query = sqlx::query_as!(
-        PostModel,
-        r#"
-        SELECT * FROM post
-        WHERE slug = $1
-        "#,
-        post_slug
-    )
-    .fetch_one(&data.db)
-    .await;
-
-    if post_query.is_err() {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": format!("Post item with slug: {} not found", post_slug),
-        });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
-    }
-
-    let post = post_query.unwrap();
+    let post = data
+        .posts
+        .find_by_slug(&post_slug)
+        .await?
+        .ok_or(AppError::PostNotFound(post_slug.to_owned()))?;
 
     if post.user_id != current_user.id {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": "You are not authorized to update this post",
-        });
-        return Err((StatusCode::UNAUTHORIZED, Json(error_response)));
+        return Err(AppError::PostNotOwner);
     }
 
-    let title = payload.title.unwrap_or(post.title);
-    let slug = payload.slug.unwrap_or(post.slug);
-    let excerpt = payload.excerpt.unwrap_or(post.excerpt);
-    let content = payload.content.unwrap_or(post.content);
     let category_id = payload.category_id.unwrap_or(post.category_id.unwrap_or(1));
-
-    let update_query = sqlx::query_as!(
-        PostModel,
-        r#"
-        UPDATE post
-        SET title = $1, slug = $2, excerpt = $3, content = $4, category_id = $5
-        WHERE slug = $6
-        RETURNING *
-        "#,
-        title,
-        slug,
-        excerpt,
-        content,
-        category_id,
-        post_slug
-    )
-    .fetch_one(&data.db)
-    .await;
-
-    match update_query {
-        Ok(updated_post) => {
-            tracing::info!("Successfully updated post with slug: {}", post_slug);
-            let response = serde_json::json!({"status": "success","data": serde_json::json!({
-                "post": updated_post
-            })});
-
-            Ok((StatusCode::OK, Json(response)))
-        }
-        Err(e) => {
-            if e.to_string()
-                .contains("duplicate key value violates unique constraint")
-            {
-                let error_response = serde_json::json!({
-                    "status": "fail",
-                    "message": "Post with that slug already exists",
-                });
-                return Err((StatusCode::CONFLICT, Json(error_response)));
-            }
-            tracing::error!("Error updating post: {:?}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(
-                    json!({"status": "fail","message": "Something bad happened while updating the post"}),
-                ),
-            ))
-        }
+    if post.category_id != Some(category_id) {
+        ensure_category_exists(&data, category_id).await?;
     }
+
+    let changes = PostData {
+        title: payload.title.unwrap_or(post.title),
+        slug: payload.slug.unwrap_or(post.slug),
+        excerpt: payload.excerpt.unwrap_or(post.excerpt),
+        content: payload.content.unwrap_or(post.content),
+        category_id: Some(category_id),
+    };
+    // the post can be deleted between the ownership check and the update
+    let updated_post = data
+        .posts
+        .update(&post_slug, changes, Vec::new(), &actor)
+        .await?
+        .ok_or(AppError::PostNotFound(post_slug.to_owned()))?;
+    data.post_cache
+        .invalidate(&[&post_slug, &updated_post.slug])
+        .await;
+
+    tracing::info!("Successfully updated post with slug: {}", post_slug);
+    let response = serde_json::json!({"status": "success","data": serde_json::json!({
+        "post": updated_post
+    })});
+
+    Ok((StatusCode::OK, Json(response)))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/v1/post/delete/{slug}",
+    tag = "post",
+    params(
+        ("slug" = String, Path, description = "Slug of the post"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Replays the first response to retries with the same key"),
+    ),
+    responses(
+        (status = 200, description = "The post was deleted", body = StatusResponse),
+        (status = 400, description = "`IDEMPOTENCY_KEY_INVALID`", body = ErrorResponse),
+        (status = 401, description = "Not logged in or `POST_NOT_OWNER`", body = ErrorResponse),
+        (status = 404, description = "`POST_NOT_FOUND`", body = ErrorResponse),
+        (status = 409, description = "`IDEMPOTENCY_KEY_IN_USE`", body = ErrorResponse),
+        (status = 422, description = "`IDEMPOTENCY_KEY_REUSED`", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
 #[debug_handler]
 pub async fn delete_post_handler(
     Path(params): Path<ParamOptions>,
     Extension(current_user): Extension<UserModel>,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    actor: Actor,
+) -> Result<impl IntoResponse, AppError> {
     let post_slug = params.slug.unwrap();
 
-    let post_query = sqlx::query_as!(
-        PostModel,
-        r#"
-        SELECT * FROM post
-        WHERE slug = $1
-        "#,
-        post_slug
-    )
-    .fetch_one(&data.db)
-    .await;
-
-    if post_query.is_err() {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": format!("Post item with slug: {} not found", post_slug),
-        });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
+    let post = data
+        .posts
+        .find_by_slug(&post_slug)
+        .await?
+        .ok_or(AppError::PostNotFound(post_slug.to_owned()))?;
+
+    if post.user_id != current_user.id {
+        return Err(AppError::PostNotOwner);
     }
 
-    let post = post_query.unwrap();
+    data.posts
+        .delete(&post_slug, Vec::new(), &actor)
+        .await?
+        .ok_or(AppError::PostNotFound(post_slug.to_owned()))?;
+    data.post_cache.invalidate(&[&post_slug]).await;
+
+    tracing::info!("Successfully deleted post with slug: {}", post_slug);
+    let response = serde_json::json!({"status": "success"});
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+// shared by the publish, unpublish and archive handlers, which only differ in `status`
+async fn set_post_status(
+    post_slug: String,
+    current_user: &UserModel,
+    data: &AppState,
+    actor: &Actor,
+    status: PostStatus,
+) -> Result<impl IntoResponse, AppError> {
+    let post = data
+        .posts
+        .find_by_slug(&post_slug)
+        .await?
+        .ok_or(AppError::PostNotFound(post_slug.to_owned()))?;
 
     if post.user_id != current_user.id {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": "You are not authorized to delete this post",
-        });
-        return Err((StatusCode::UNAUTHORIZED, Json(error_response)));
+        return Err(AppError::PostNotOwner);
     }
 
-    let delete_query = sqlx::query!(
-        r#"
-        DELETE FROM post
-        WHERE slug = $1
-        RETURNING *
-        "#,
-        post_slug
+    let updated_post = data
+        .posts
+        .set_status(&post_slug, status, Vec::new(), actor)
+        .await?
+        .ok_or(AppError::PostNotFound(post_slug.to_owned()))?;
+    data.post_cache.invalidate(&[&post_slug]).await;
+
+    tracing::info!(
+        "Successfully moved post with slug {} to {}",
+        post_slug,
+        status.as_str()
+    );
+    let response = serde_json::json!({"status": "success","data": serde_json::json!({
+        "post": updated_post
+    })});
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/post/publish/{slug}",
+    tag = "post",
+    params(
+        ("slug" = String, Path, description = "Slug of the post"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Replays the first response to retries with the same key"),
+    ),
+    responses(
+        (status = 200, description = "The published post; `published_at` is kept from its first publication", body = PostResponse),
+        (status = 400, description = "`IDEMPOTENCY_KEY_INVALID`", body = ErrorResponse),
+        (status = 401, description = "Not logged in or `POST_NOT_OWNER`", body = ErrorResponse),
+        (status = 404, description = "`POST_NOT_FOUND`", body = ErrorResponse),
+        (status = 409, description = "`IDEMPOTENCY_KEY_IN_USE`", body = ErrorResponse),
+        (status = 422, description = "`IDEMPOTENCY_KEY_REUSED`", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
+#[debug_handler]
+pub async fn publish_post_handler(
+    Path(params): Path<ParamOptions>,
+    Extension(current_user): Extension<UserModel>,
+    State(data): State<Arc<AppState>>,
+    actor: Actor,
+) -> Result<impl IntoResponse, AppError> {
+    let post_slug = params.slug.unwrap();
+    set_post_status(
+        post_slug,
+        &current_user,
+        &data,
+        &actor,
+        PostStatus::Published,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/post/unpublish/{slug}",
+    tag = "post",
+    params(
+        ("slug" = String, Path, description = "Slug of the post"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Replays the first response to retries with the same key"),
+    ),
+    responses(
+        (status = 200, description = "The post, back to a draft", body = PostResponse),
+        (status = 400, description = "`IDEMPOTENCY_KEY_INVALID`", body = ErrorResponse),
+        (status = 401, description = "Not logged in or `POST_NOT_OWNER`", body = ErrorResponse),
+        (status = 404, description = "`POST_NOT_FOUND`", body = ErrorResponse),
+        (status = 409, description = "`IDEMPOTENCY_KEY_IN_USE`", body = ErrorResponse),
+        (status = 422, description = "`IDEMPOTENCY_KEY_REUSED`", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
+#[debug_handler]
+pub async fn unpublish_post_handler(
+    Path(params): Path<ParamOptions>,
+    Extension(current_user): Extension<UserModel>,
+    State(data): State<Arc<AppState>>,
+    actor: Actor,
+) -> Result<impl IntoResponse, AppError> {
+    let post_slug = params.slug.unwrap();
+    set_post_status(post_slug, &current_user, &data, &actor, PostStatus::Draft).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/post/archive/{slug}",
+    tag = "post",
+    params(
+        ("slug" = String, Path, description = "Slug of the post"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Replays the first response to retries with the same key"),
+    ),
+    responses(
+        (status = 200, description = "The archived post", body = PostResponse),
+        (status = 400, description = "`IDEMPOTENCY_KEY_INVALID`", body = ErrorResponse),
+        (status = 401, description = "Not logged in or `POST_NOT_OWNER`", body = ErrorResponse),
+        (status = 404, description = "`POST_NOT_FOUND`", body = ErrorResponse),
+        (status = 409, description = "`IDEMPOTENCY_KEY_IN_USE`", body = ErrorResponse),
+        (status = 422, description = "`IDEMPOTENCY_KEY_REUSED`", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []), ("cookie_auth" = []))
+)]
+#[debug_handler]
+pub async fn archive_post_handler(
+    Path(params): Path<ParamOptions>,
+    Extension(current_user): Extension<UserModel>,
+    State(data): State<Arc<AppState>>,
+    actor: Actor,
+) -> Result<impl IntoResponse, AppError> {
+    let post_slug = params.slug.unwrap();
+    set_post_status(
+        post_slug,
+        &current_user,
+        &data,
+        &actor,
+        PostStatus::Archived,
     )
-    .fetch_one(&data.db)
-    .await;
-
-    match delete_query {
-        Ok(_) => {
-            tracing::info!("Successfully deleted post with slug: {}", post_slug);
-            let response = serde_json::json!({"status": "success"});
-
-            Ok((StatusCode::OK, Json(response)))
-        }
-        Err(e) => {
-            tracing::error!("Error deleting post: {:?}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(
-                    json!({"status": "fail","message": "Something bad happened while deleting the post"}),
-                ),
-            ))
-        }
-    }
+    .await
 }
@@ -0,0 +1,85 @@
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::error::AppError;
+
+// IMF-fixdate, the only format servers may send (RFC 9110 5.6.7)
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+// serialises `body` once and derives a strong etag from the bytes; the body carries `updated_at`,
+// so the tag changes with both the content and the version of the post. Answers `304 Not Modified`
+// when the validators sent by the client still match.
+pub fn cached_json<T: Serialize>(
+    request_headers: &HeaderMap,
+    cache_control: &str,
+    last_modified: Option<DateTime<Utc>>,
+    body: &T,
+) -> Result<Response, AppError> {
+    let bytes = serde_json::to_vec(body)
+        .map_err(|e| AppError::Internal(format!("Error while serialising response: {e}")))?;
+    let etag = etag(&bytes);
+
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        headers.insert(header::ETAG, value);
+    }
+    if let Some(value) = last_modified
+        .and_then(|last_modified| HeaderValue::from_str(&http_date(last_modified)).ok())
+    {
+        headers.insert(header::LAST_MODIFIED, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(cache_control) {
+        headers.insert(header::CACHE_CONTROL, value);
+    }
+
+    if is_fresh(request_headers, &etag, last_modified) {
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+    }
+
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    Ok((StatusCode::OK, headers, bytes).into_response())
+}
+
+fn etag(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    let hex = digest[..16]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    format!("\"{hex}\"")
+}
+
+pub fn http_date(date: DateTime<Utc>) -> String {
+    date.format(HTTP_DATE_FORMAT).to_string()
+}
+
+// `If-None-Match` takes precedence over `If-Modified-Since` (RFC 9110 13.2.2); it uses the weak
+// comparison, so `W/` tags added by proxies still match
+fn is_fresh(headers: &HeaderMap, etag: &str, last_modified: Option<DateTime<Utc>>) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+        return if_none_match.to_str().is_ok_and(|tags| {
+            tags.split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+        });
+    }
+
+    let since = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+
+    match (last_modified, since) {
+        // http dates have a resolution of one second
+        (Some(last_modified), Some(since)) => last_modified.timestamp() <= since.timestamp(),
+        _ => false,
+    }
+}
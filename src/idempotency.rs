@@ -0,0 +1,249 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{OriginalUri, State},
+    http::{header, HeaderName, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    error::AppError,
+    model::{IdempotencyKeyModel, UserModel},
+    repository::StoredResponse,
+    tasks::WorkerError,
+    AppState,
+};
+
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+// set on responses replayed from an earlier request
+pub const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+const MAX_KEY_LENGTH: usize = 255;
+// the default limit of axum's `Json` extractor, which would reject a larger body anyway
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 10);
+
+// makes retries of a write safe: the first response to a request with an `Idempotency-Key` is
+// stored per user and replayed verbatim for the same key until `idempotency.ttl_secs` runs out.
+// Reusing a key for a different request is rejected with 422, and a retry arriving while the first
+// request is still running with 409. Server errors are not stored, so those requests can be
+// retried, and neither are requests that never finish: a dropped request frees its key, and the
+// key of one lost with its server is free again after `idempotency.lease_secs`. Relies on
+// `auth_guard_middleware` running first; requests without the header pass through untouched.
+pub async fn idempotency_middleware(
+    State(data): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(key) = req.headers().get(IDEMPOTENCY_KEY) else {
+        return Ok(next.run(req).await);
+    };
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LENGTH)
+        .ok_or(AppError::InvalidIdempotencyKey)?
+        .to_string();
+    let Some(user_id) = req.extensions().get::<UserModel>().map(|user| user.id) else {
+        return Ok(next.run(req).await);
+    };
+
+    let (parts, body) = req.into_parts();
+    let body = to_bytes(body, MAX_BODY_BYTES)
+        .await
+        .map_err(|_| AppError::PayloadTooLarge)?;
+    // `nest` strips the prefix from `parts.uri`, which would hide the api version
+    let path = parts
+        .extensions
+        .get::<OriginalUri>()
+        .map_or(parts.uri.path(), |OriginalUri(uri)| uri.path());
+    let fingerprint = fingerprint(parts.method.as_str(), &versioned_path(path), &body);
+
+    let config = &data.config.idempotency;
+    let lease_until = chrono::Utc::now() + chrono::Duration::seconds(config.lease_secs as i64);
+    if let Some(existing) = data
+        .idempotency
+        .begin(user_id, &key, &fingerprint, lease_until)
+        .await?
+    {
+        return replay(existing, &fingerprint);
+    }
+    let reservation = Reservation {
+        data: data.clone(),
+        user_id,
+        key,
+        settled: false,
+    };
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if response.status().is_server_error() {
+        reservation.release().await;
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            reservation.release().await;
+            return Err(AppError::Internal(format!(
+                "Error while reading the response body: {e}"
+            )));
+        }
+    };
+
+    let stored = StoredResponse {
+        status: parts.status.as_u16(),
+        headers: parts
+            .headers
+            .iter()
+            .filter(|(name, _)| *name != header::CONTENT_LENGTH)
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+        body: body.to_vec(),
+    };
+    let expires_at = chrono::Utc::now() + chrono::Duration::seconds(config.ttl_secs as i64);
+    reservation.finish(&stored, expires_at).await;
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+// a key reserved by `begin` until it has a response. Dropped without one, because the client went
+// away and the request future with it or because the handler panicked, it frees the key in the
+// background; the lease covers the case where that never runs.
+struct Reservation {
+    data: Arc<AppState>,
+    user_id: i32,
+    key: String,
+    settled: bool,
+}
+
+impl Reservation {
+    async fn finish(mut self, response: &StoredResponse, expires_at: DateTime<Utc>) {
+        self.settled = true;
+        let (data, user_id, key) = (&self.data, self.user_id, self.key.as_str());
+        if let Err(e) = data
+            .idempotency
+            .finish(user_id, key, response, expires_at)
+            .await
+        {
+            // the request went through, but a retry will run it again
+            tracing::warn!(key, "Storing the idempotent response failed: {e:?}");
+            release(data, user_id, key).await;
+        }
+    }
+
+    async fn release(mut self) {
+        self.settled = true;
+        release(&self.data, self.user_id, &self.key).await;
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if self.settled {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let (data, user_id, key) = (
+            self.data.clone(),
+            self.user_id,
+            std::mem::take(&mut self.key),
+        );
+        runtime.spawn(async move { release(&data, user_id, &key).await });
+    }
+}
+
+fn replay(existing: IdempotencyKeyModel, fingerprint: &str) -> Result<Response, AppError> {
+    if existing.fingerprint != fingerprint {
+        return Err(AppError::IdempotencyKeyReused);
+    }
+    let Some(status) = existing.response_status else {
+        return Err(AppError::IdempotencyKeyInUse);
+    };
+
+    let status = u16::try_from(status)
+        .ok()
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .ok_or_else(|| AppError::Internal(format!("invalid stored status {status}")))?;
+    let mut response = Response::new(Body::from(existing.response_body.unwrap_or_default()));
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+    for (name, value) in existing
+        .response_headers
+        .map(|headers| headers.0)
+        .unwrap_or_default()
+    {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    headers.insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    Ok(response)
+}
+
+async fn release(data: &AppState, user_id: i32, key: &str) {
+    if let Err(e) = data.idempotency.release(user_id, key).await {
+        tracing::warn!(key, "Releasing the idempotency key failed: {e:?}");
+    }
+}
+
+// the unversioned `/api` paths are an alias of v1, so `/api/post/create` and
+// `/api/v1/post/create` count as the same request; the same path on another version doesn't, as it
+// can behave differently
+fn versioned_path(path: &str) -> String {
+    match path.strip_prefix("/api/") {
+        Some(rest) if !is_version(rest.split('/').next().unwrap_or_default()) => {
+            format!("/api/v1/{rest}")
+        }
+        _ => path.to_string(),
+    }
+}
+
+fn is_version(segment: &str) -> bool {
+    segment
+        .strip_prefix('v')
+        .is_some_and(|number| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn fingerprint(method: &str, path: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(path.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+// runs under the `TaskSupervisor`; an error restarts it with backoff
+pub async fn prune_worker(
+    data: Arc<AppState>,
+    shutdown: CancellationToken,
+) -> Result<(), WorkerError> {
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(PRUNE_INTERVAL) => {}
+            _ = shutdown.cancelled() => return Ok(()),
+        }
+
+        data.idempotency
+            .prune()
+            .await
+            .map_err(|e| format!("pruning the idempotency keys failed: {e:?}"))?;
+    }
+}
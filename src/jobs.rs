@@ -0,0 +1,219 @@
+use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+use crate::{
+    config::JobsConfig, error::AppError, model::JobModel, monitoring, tasks::WorkerError, AppState,
+};
+
+// a typed job; the payload is the serialized value, so changes to it must stay compatible with
+// the jobs already queued
+#[async_trait]
+pub trait Job: Serialize + DeserializeOwned + Send + Sync + 'static {
+    // stored in `jobs.kind`, must be unique and never change
+    const KIND: &'static str;
+    const MAX_ATTEMPTS: i32 = 5;
+
+    // an error is retried with backoff until `MAX_ATTEMPTS` is used up, then the job is
+    // dead-lettered
+    async fn run(self, state: Arc<AppState>) -> Result<(), WorkerError>;
+}
+
+type JobFuture = Pin<Box<dyn Future<Output = Result<(), WorkerError>> + Send>>;
+type Runner = Arc<dyn Fn(serde_json::Value, Arc<AppState>) -> JobFuture + Send + Sync>;
+
+// the job kinds the workers know how to run; jobs of other kinds are dead-lettered
+#[derive(Clone, Default)]
+pub struct JobRegistry {
+    runners: HashMap<&'static str, Runner>,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the jobs enqueued by the handlers
+    pub fn builtin() -> Self {
+        Self::new().register::<PostCreated>()
+    }
+
+    pub fn register<J: Job>(mut self) -> Self {
+        let runner: Runner = Arc::new(|payload, state| {
+            Box::pin(async move {
+                let job = serde_json::from_value::<J>(payload)?;
+                job.run(state).await
+            })
+        });
+        self.runners.insert(J::KIND, runner);
+        self
+    }
+}
+
+enum Outcome {
+    Completed,
+    Retry(String),
+    Dead(String),
+}
+
+// starts `jobs.workers` workers under the task supervisor
+pub fn spawn_workers(app_state: &Arc<AppState>, registry: JobRegistry) {
+    let registry = Arc::new(registry);
+    for i in 0..app_state.config.jobs.workers {
+        let state = app_state.clone();
+        let registry = registry.clone();
+        app_state
+            .tasks
+            .spawn(&format!("jobs-{i}"), move |shutdown| {
+                worker(state.clone(), registry.clone(), shutdown)
+            });
+    }
+}
+
+// a job already running when shutdown starts is finished; one aborted by the shutdown timeout is
+// picked up again once its lease runs out
+async fn worker(
+    state: Arc<AppState>,
+    registry: Arc<JobRegistry>,
+    shutdown: CancellationToken,
+) -> Result<(), WorkerError> {
+    let poll_interval = Duration::from_millis(state.config.jobs.poll_interval_ms);
+
+    while !shutdown.is_cancelled() {
+        let ran = run_next(&state, &registry)
+            .await
+            .map_err(|e| format!("the job queue failed: {e:?}"))?;
+        if ran {
+            continue;
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(poll_interval) => {}
+            _ = shutdown.cancelled() => {}
+        }
+    }
+
+    Ok(())
+}
+
+// claims and runs one due job; `false` if none was due
+pub async fn run_next(state: &Arc<AppState>, registry: &JobRegistry) -> Result<bool, AppError> {
+    let config = &state.config.jobs;
+    let Some(job) = state
+        .jobs
+        .claim(1, Duration::from_secs(config.lease_secs))
+        .await?
+        .into_iter()
+        .next()
+    else {
+        return Ok(false);
+    };
+
+    let span = tracing::info_span!("job", id = job.id, kind = %job.kind, attempt = job.attempts);
+    finish(state, registry, job).instrument(span).await?;
+
+    Ok(true)
+}
+
+async fn finish(
+    state: &Arc<AppState>,
+    registry: &JobRegistry,
+    job: JobModel,
+) -> Result<(), AppError> {
+    let config = &state.config.jobs;
+    let (held, outcome) = match run(state, registry, &job).await {
+        Outcome::Completed => (
+            state.jobs.complete(job.id, job.attempts).await?,
+            "completed",
+        ),
+        Outcome::Retry(error) => {
+            let delay = retry_delay(config, job.attempts);
+            tracing::warn!("Job failed, retrying in {}s: {error}", delay.as_secs());
+            let run_at = chrono::Utc::now() + chrono::Duration::seconds(delay.as_secs() as i64);
+            let held = state
+                .jobs
+                .retry(job.id, job.attempts, &error, run_at)
+                .await?;
+            (held, "retried")
+        }
+        Outcome::Dead(error) => {
+            tracing::error!("Job failed for good: {error}");
+            let held = state.jobs.dead_letter(job.id, job.attempts, &error).await?;
+            (held, "dead")
+        }
+    };
+
+    if held {
+        monitoring::record_job(&job.kind, outcome);
+    } else {
+        // the job outran its lease and another worker claimed it; that run reports the outcome
+        tracing::warn!("Job lease lost, the result is dropped");
+        monitoring::record_job(&job.kind, "lease_lost");
+    }
+
+    Ok(())
+}
+
+async fn run(state: &Arc<AppState>, registry: &JobRegistry, job: &JobModel) -> Outcome {
+    let Some(runner) = registry.runners.get(job.kind.as_str()) else {
+        return Outcome::Dead(format!("no job registered for kind `{}`", job.kind));
+    };
+
+    // a panicking job fails like one returning an error instead of taking the worker down
+    let result = tokio::spawn(runner(job.payload.0.clone(), state.clone())).await;
+    let error = match result {
+        Ok(Ok(())) => return Outcome::Completed,
+        Ok(Err(e)) => e.to_string(),
+        Err(e) => format!("panicked: {e}"),
+    };
+
+    if job.attempts >= job.max_attempts {
+        Outcome::Dead(error)
+    } else {
+        Outcome::Retry(error)
+    }
+}
+
+fn retry_delay(config: &JobsConfig, attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 31) as u32;
+    let secs = config
+        .backoff_base_secs
+        .saturating_mul(2u64.saturating_pow(exponent));
+    Duration::from_secs(secs.min(config.backoff_max_secs))
+}
+
+// enqueued by the create handlers in the same transaction as the post; this is where
+// notifications such as emails or webhooks hook in
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PostCreated {
+    pub slug: String,
+    pub user_id: i32,
+}
+
+#[async_trait]
+impl Job for PostCreated {
+    const KIND: &'static str = "post_created";
+
+    async fn run(self, state: Arc<AppState>) -> Result<(), WorkerError> {
+        let post = state
+            .posts
+            .find_by_slug(&self.slug)
+            .await
+            .map_err(|e| format!("{e:?}"))?;
+
+        // deleted or renamed before the job ran
+        let Some(post) = post else {
+            return Ok(());
+        };
+        tracing::info!(
+            post_id = post.id,
+            user_id = self.user_id,
+            "Post {} created",
+            post.slug
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+pub mod audit;
+pub mod backup;
+pub mod config;
+pub mod deprecation;
+pub mod error;
+pub mod extract;
+pub mod feature_flags;
+pub mod fixtures;
+pub mod guard;
+pub mod handlers;
+pub mod http_cache;
+pub mod idempotency;
+pub mod jobs;
+pub mod logging;
+pub mod model;
+pub mod monitoring;
+pub mod notify;
+pub mod openapi;
+pub mod password;
+pub mod post_cache;
+pub mod rate_limit;
+pub mod repository;
+pub mod route;
+pub mod schema;
+pub mod security_headers;
+pub mod server;
+pub mod tasks;
+
+use axum::{
+    extract::DefaultBodyLimit,
+    http::{HeaderName, HeaderValue, Method},
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use std::{sync::Arc, time::Duration};
+
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use utoipa::OpenApi;
+use utoipa_rapidoc::RapiDoc;
+
+use config::{Config, CorsConfig};
+use feature_flags::{FeatureFlagBus, FeatureFlagCache};
+use handlers::{
+    csp::csp_report_handler,
+    health::{liveness_handler, readiness_handler},
+};
+use logging::request_context_middleware;
+use monitoring::{metrics_handler, prometheus_handle, track_metrics};
+use notify::PgNotifyListener;
+use openapi::ApiDoc;
+use post_cache::{PostCache, PostCacheBus};
+use rate_limit::{
+    rate_limit_middleware, MemoryRateLimitStore, RateLimitGroup, RateLimitStore, RateLimiter,
+};
+use repository::{
+    AuditRepository, BackupRepository, CategoryRepository, CspReportRepository, DatabaseHealth,
+    FeatureFlagRepository, IdempotencyRepository, JobRepository, PostRepository, Repository,
+    UserRepository,
+};
+use route::api_routes;
+use security_headers::{security_headers_middleware, SecurityHeaders};
+use tasks::TaskSupervisor;
+
+// a report is a few hundred bytes; the reporting api batches a handful of them
+const CSP_REPORT_BODY_LIMIT: usize = 64 * 1024;
+
+pub struct AppState {
+    pub config: Config,
+    pub posts: Arc<dyn PostRepository>,
+    pub users: Arc<dyn UserRepository>,
+    pub categories: Arc<dyn CategoryRepository>,
+    pub jobs: Arc<dyn JobRepository>,
+    pub idempotency: Arc<dyn IdempotencyRepository>,
+    pub csp_reports: Arc<dyn CspReportRepository>,
+    pub audit: Arc<dyn AuditRepository>,
+    pub feature_flag_store: Arc<dyn FeatureFlagRepository>,
+    pub backups: Arc<dyn BackupRepository>,
+    pub database: Arc<dyn DatabaseHealth>,
+    pub post_cache: PostCache,
+    pub feature_flags: FeatureFlagCache,
+    pub rate_limiter: RateLimiter,
+    // hears the buses of the other replicas, see `notify::listen_worker`
+    pub notify_listener: Option<PgNotifyListener>,
+    pub tasks: TaskSupervisor,
+}
+
+impl AppState {
+    pub fn new<R: Repository>(config: Config, repository: R) -> Self {
+        let repository = Arc::new(repository);
+        let rate_limiter = RateLimiter::new(&config, Arc::new(MemoryRateLimitStore::new()));
+        let post_cache = PostCache::new(&config.post_cache);
+        let feature_flags = FeatureFlagCache::new(&config.feature_flags, repository.clone());
+        Self {
+            config,
+            posts: repository.clone(),
+            users: repository.clone(),
+            categories: repository.clone(),
+            jobs: repository.clone(),
+            idempotency: repository.clone(),
+            csp_reports: repository.clone(),
+            audit: repository.clone(),
+            feature_flag_store: repository.clone(),
+            backups: repository.clone(),
+            database: repository,
+            post_cache,
+            feature_flags,
+            rate_limiter,
+            notify_listener: None,
+            tasks: TaskSupervisor::new(),
+        }
+    }
+
+    // replaces the default in-process store, e.g. with one shared between replicas
+    pub fn with_rate_limit_store(mut self, store: Arc<dyn RateLimitStore>) -> Self {
+        self.rate_limiter = RateLimiter::new(&self.config, store);
+        self
+    }
+
+    // lets the post cache hear about the writes of other replicas
+    pub fn with_post_cache_bus(mut self, bus: Arc<dyn PostCacheBus>) -> Self {
+        self.post_cache = PostCache::new(&self.config.post_cache).with_bus(bus);
+        self
+    }
+
+    // lets the other replicas hear about flag changes right away
+    pub fn with_feature_flag_bus(mut self, bus: Arc<dyn FeatureFlagBus>) -> Self {
+        self.feature_flags = self.feature_flags.with_bus(bus);
+        self
+    }
+
+    // lets the caches with a bus hear the other replicas
+    pub fn with_notify_listener(mut self, listener: PgNotifyListener) -> Self {
+        self.notify_listener = Some(listener);
+        self
+    }
+}
+
+// builds the full application router; shared by every entry point (shuttle and standalone)
+pub fn app(app_state: Arc<AppState>) -> Router {
+    let cors = cors_layer(&app_state.config.cors);
+    let security_headers = Arc::new(SecurityHeaders::new(&app_state.config.security_headers));
+    // install the recorder before any request can record a metric
+    prometheus_handle();
+
+    // browsers post reports without credentials, so the endpoint is limited by address
+    let csp_report = Router::new()
+        .route("/csp-report", post(csp_report_handler))
+        .route_layer(middleware::from_fn_with_state(
+            (app_state.clone(), RateLimitGroup::CspReport),
+            rate_limit_middleware,
+        ))
+        .layer(DefaultBodyLimit::max(CSP_REPORT_BODY_LIMIT))
+        .with_state(app_state.clone());
+
+    Router::new()
+        .route("/", get(|| async { "Welcome to blogrs API!" }))
+        .route("/healthz", get(liveness_handler))
+        .route(
+            "/readyz",
+            get(readiness_handler).with_state(app_state.clone()),
+        )
+        .route(
+            "/metrics",
+            get(metrics_handler).with_state(app_state.clone()),
+        )
+        .merge(csp_report)
+        .nest("/api", api_routes(app_state))
+        .merge(RapiDoc::with_openapi("/api/openapi.json", ApiDoc::openapi()).path("/api/docs"))
+        .route_layer(middleware::from_fn(track_metrics))
+        .layer(cors)
+        .layer(middleware::from_fn_with_state(
+            security_headers,
+            security_headers_middleware,
+        ))
+        .layer(middleware::from_fn(request_context_middleware))
+}
+
+// the values are checked by `Config::validate` at startup
+fn cors_layer(config: &CorsConfig) -> CorsLayer {
+    let layer = CorsLayer::new()
+        .allow_methods(
+            config
+                .allowed_methods
+                .iter()
+                .filter_map(|method| Method::from_bytes(method.as_bytes()).ok())
+                .collect::<Vec<_>>(),
+        )
+        .allow_headers(
+            config
+                .allowed_headers
+                .iter()
+                .filter_map(|header| HeaderName::from_bytes(header.as_bytes()).ok())
+                .collect::<Vec<_>>(),
+        )
+        .expose_headers(
+            config
+                .expose_headers
+                .iter()
+                .filter_map(|header| HeaderName::from_bytes(header.as_bytes()).ok())
+                .collect::<Vec<_>>(),
+        )
+        .max_age(Duration::from_secs(config.max_age_secs));
+
+    // browsers reject credentials with a wildcard origin, so `*` never allows them
+    if config.allows_any_origin() {
+        return layer.allow_origin(Any).allow_credentials(false);
+    }
+
+    let origins = config
+        .allowed_origins
+        .iter()
+        .filter_map(|origin| HeaderValue::from_str(origin).ok())
+        .collect::<Vec<_>>();
+    // validated on startup
+    let patterns = config.origin_patterns().unwrap_or_default();
+
+    let allow_origin = if patterns.is_empty() {
+        AllowOrigin::list(origins)
+    } else {
+        AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+            origins.contains(origin)
+                || origin
+                    .to_str()
+                    .is_ok_and(|origin| patterns.iter().any(|pattern| pattern.is_match(origin)))
+        })
+    };
+
+    layer
+        .allow_origin(allow_origin)
+        .allow_credentials(config.allow_credentials)
+}
@@ -0,0 +1,104 @@
+use std::time::Instant;
+
+use axum::{
+    body::Body,
+    http::{HeaderName, HeaderValue, Request},
+    middleware::Next,
+    response::IntoResponse,
+};
+use tracing::{field, Instrument};
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+use crate::config::{LogConfig, LogFormat};
+
+pub static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+// longer or non-printable ids sent by clients are replaced to keep the logs clean
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+// the id of the request being handled, if called from within `request_context_middleware`
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.to_owned()).ok()
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+// `RUST_LOG` takes precedence over `log.filter`
+pub fn init(config: &LogConfig) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.filter));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    let result = match config.format {
+        LogFormat::Json => builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .try_init(),
+        LogFormat::Pretty => builder.pretty().try_init(),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Failed to initialise logging: {e}");
+    }
+}
+
+fn request_id_from(req: &Request<Body>) -> String {
+    req.headers()
+        .get(&X_REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.bytes().all(|b| b.is_ascii_graphic())
+        })
+        .map(|id| id.to_owned())
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+// accepts or generates an `X-Request-Id`, echoes it in the response and runs the rest of the
+// stack inside a span carrying it; `route` and `user_id` are filled in further down the stack
+pub async fn request_context_middleware(mut req: Request<Body>, next: Next) -> impl IntoResponse {
+    let start = Instant::now();
+    let request_id = request_id_from(&req);
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.uri().path(),
+        route = field::Empty,
+        user_id = field::Empty,
+        status = field::Empty,
+        latency_ms = field::Empty,
+    );
+
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+    let mut response = REQUEST_ID
+        .scope(request_id.clone(), next.run(req).instrument(span.clone()))
+        .await;
+
+    let status = response.status();
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    span.record("status", status.as_u16());
+    span.record("latency_ms", latency_ms);
+    span.in_scope(|| {
+        if status.is_server_error() {
+            tracing::error!("request failed");
+        } else {
+            tracing::info!("request completed");
+        }
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(X_REQUEST_ID.clone(), value);
+    }
+    response
+}
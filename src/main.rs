@@ -1,60 +1,47 @@
-mod guard;
-mod handlers;
-mod model;
-mod route;
-mod schema;
-
-use axum::{
-    http::{
-        header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
-        Method,
-    },
-    routing::get,
-    Router,
-};
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
+use shuttle_runtime::CustomError;
 use sqlx::PgPool;
+use tokio::net::TcpListener;
 
-use tower_http::cors::{Any, CorsLayer};
-
-use route::api_routes;
-
-pub struct Env {
-    jwt_secret: String,
-}
+use blogrs::{
+    config::Config,
+    fixtures, logging,
+    repository::{postgres::MIGRATOR, Backend, PgRepository},
+    server, AppState,
+};
 
-pub struct AppState {
-    db: PgPool,
-    env: Env,
+// serves the app through `server::serve` so shuttle deployments get the same graceful shutdown
+// as the standalone binary
+pub struct BlogrsService(Arc<AppState>);
+
+#[shuttle_runtime::async_trait]
+impl shuttle_runtime::Service for BlogrsService {
+    async fn bind(mut self, addr: SocketAddr) -> Result<(), shuttle_runtime::Error> {
+        let listener = TcpListener::bind(addr).await.map_err(CustomError::new)?;
+        server::serve(listener, self.0)
+            .await
+            .map_err(CustomError::new)?;
+        Ok(())
+    }
 }
 
 #[shuttle_runtime::main]
 pub async fn axum(
     #[shuttle_shared_db::Postgres] pool: PgPool,
     #[shuttle_secrets::Secrets] secrets: shuttle_secrets::SecretStore,
-) -> shuttle_axum::ShuttleAxum {
-    sqlx::migrate!()
-        .run(&pool)
+) -> Result<BlogrsService, shuttle_runtime::Error> {
+    // secrets (e.g. JWT_SECRET) take precedence over the config file and the environment
+    let config =
+        Config::load_with(secrets).map_err(|e| shuttle_runtime::Error::Custom(e.into()))?;
+    logging::init(&config.log);
+
+    MIGRATOR.run(&pool).await.expect("Migrations failed :(");
+
+    let app_state = Arc::new(Backend::Postgres(PgRepository::new(pool)).into_app_state(config));
+    fixtures::load_paths(&app_state, &app_state.config.fixtures.paths)
         .await
-        .expect("Migrations failed :(");
-
-    let jwt_secret = secrets.get("JWT_SECRET").expect("JWT_SECRET must be set");
-
-    let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
-        .allow_origin(Any)
-        .allow_credentials(false)
-        .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE]);
-
-    let app_state = Arc::new(AppState {
-        db: pool.clone(),
-        env: Env { jwt_secret },
-    });
-    let app = Router::new()
-        .route("/", get(|| async { "Welcome to blogrs API!" }))
-        .nest("/api", api_routes(app_state))
-        .layer(cors);
-
-    Ok(app.into())
+        .map_err(CustomError::new)?;
+
+    Ok(BlogrsService(app_state))
 }
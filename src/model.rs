@@ -1,13 +1,14 @@
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{types::Json, FromRow};
+use utoipa::ToSchema;
 
-#[derive(FromRow, Serialize, Deserialize)]
+#[derive(Debug, FromRow, Serialize, Deserialize, Clone, ToSchema)]
 pub struct CategoryModel {
     pub id: i32,
     pub name: String,
 }
 
-#[derive(Debug, FromRow, Deserialize, Serialize)]
+#[derive(Debug, FromRow, Deserialize, Serialize, Clone, ToSchema)]
 pub struct PostModel {
     pub id: i32,
     pub title: String,
@@ -18,6 +19,46 @@ pub struct PostModel {
     pub category_id: Option<i32>,
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    // backups from before posts had a status only hold published posts
+    #[serde(default = "PostStatus::published")]
+    pub status: PostStatus,
+    // the first time the post was published, kept when it goes back to a draft
+    #[serde(default)]
+    pub published_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+// only published posts are public; drafts and archived posts are seen by their author alone.
+// Stored as text in `post.status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum PostStatus {
+    Draft,
+    Published,
+    Archived,
+}
+
+impl PostStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Draft => "draft",
+            Self::Published => "published",
+            Self::Archived => "archived",
+        }
+    }
+
+    // what moving a post to this status records in the audit log
+    pub fn audit_action(self) -> AuditAction {
+        match self {
+            Self::Draft => AuditAction::PostUnpublish,
+            Self::Published => AuditAction::PostPublish,
+            Self::Archived => AuditAction::PostArchive,
+        }
+    }
+
+    fn published() -> Self {
+        Self::Published
+    }
 }
 
 #[derive(FromRow, Serialize, Deserialize, Debug, Clone)]
@@ -27,6 +68,186 @@ pub struct UserModel {
     pub username: String,
     pub email: String,
     pub password: String,
+    pub is_admin: bool,
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    // waiting for `run_at`, including retries
+    Queued,
+    // claimed by a worker until `locked_until`
+    Running,
+    // out of attempts, or not runnable at all; kept for inspection
+    Dead,
+}
+
+impl JobState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Dead => "dead",
+        }
+    }
+}
+
+// a row of `jobs`; completed jobs are deleted
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct JobModel {
+    pub id: i64,
+    pub kind: String,
+    pub payload: Json<serde_json::Value>,
+    // one of `JobState`
+    pub state: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub unique_key: Option<String>,
+    pub last_error: Option<String>,
+    pub run_at: chrono::DateTime<chrono::Utc>,
+    pub locked_until: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+// a row of `idempotency_key`, see `idempotency`
+#[derive(Debug, FromRow, Clone)]
+pub struct IdempotencyKeyModel {
+    pub user_id: i32,
+    pub key: String,
+    pub fingerprint: String,
+    // the response columns are `None` while the first request is running
+    pub response_status: Option<i32>,
+    pub response_headers: Option<Json<Vec<(String, String)>>>,
+    pub response_body: Option<Vec<u8>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+// a row of `csp_report`, a policy violation reported by a browser to `/csp-report`
+#[derive(Debug, FromRow, Serialize, Clone)]
+pub struct CspReportModel {
+    pub id: i64,
+    pub document_uri: String,
+    pub violated_directive: String,
+    pub blocked_uri: Option<String>,
+    pub source_file: Option<String>,
+    pub line_number: Option<i64>,
+    // `enforce` or `report`
+    pub disposition: String,
+    pub user_agent: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+// what an entry of `audit_log` records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    PostCreate,
+    PostUpdate,
+    PostDelete,
+    PostPublish,
+    PostUnpublish,
+    PostArchive,
+    PostReassign,
+    UserRegister,
+    UserLogin,
+    UserLogout,
+    UserPasswordReset,
+    CategoryCreate,
+    FeatureFlagUpsert,
+    FeatureFlagDelete,
+    FeatureFlagOverrideSet,
+    FeatureFlagOverrideDelete,
+}
+
+impl AuditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PostCreate => "post.create",
+            Self::PostUpdate => "post.update",
+            Self::PostDelete => "post.delete",
+            Self::PostPublish => "post.publish",
+            Self::PostUnpublish => "post.unpublish",
+            Self::PostArchive => "post.archive",
+            Self::PostReassign => "post.reassign",
+            Self::UserRegister => "user.register",
+            Self::UserLogin => "user.login",
+            Self::UserLogout => "user.logout",
+            Self::UserPasswordReset => "user.password_reset",
+            Self::CategoryCreate => "category.create",
+            Self::FeatureFlagUpsert => "feature_flag.upsert",
+            Self::FeatureFlagDelete => "feature_flag.delete",
+            Self::FeatureFlagOverrideSet => "feature_flag.override_set",
+            Self::FeatureFlagOverrideDelete => "feature_flag.override_delete",
+        }
+    }
+
+    // the kind of `target_id`
+    pub fn target_type(self) -> &'static str {
+        match self {
+            Self::PostCreate
+            | Self::PostUpdate
+            | Self::PostDelete
+            | Self::PostPublish
+            | Self::PostUnpublish
+            | Self::PostArchive
+            | Self::PostReassign => "post",
+            Self::UserRegister | Self::UserLogin | Self::UserLogout | Self::UserPasswordReset => {
+                "user"
+            }
+            Self::CategoryCreate => "category",
+            // the name of the flag, also for the changes of its overrides
+            Self::FeatureFlagUpsert
+            | Self::FeatureFlagDelete
+            | Self::FeatureFlagOverrideSet
+            | Self::FeatureFlagOverrideDelete => "feature_flag",
+        }
+    }
+}
+
+// a row of `audit_log`; entries are never updated or deleted by the api
+#[derive(Debug, FromRow, Serialize, Clone, ToSchema)]
+pub struct AuditLogModel {
+    pub id: i64,
+    // `None` for anonymous requests and `blogrs-admin`
+    pub actor_id: Option<i32>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    // one of `AuditAction`
+    #[schema(example = "post.delete")]
+    pub action: String,
+    #[schema(example = "post")]
+    pub target_type: String,
+    pub target_id: String,
+    // the target before and after the action, without secrets like the password hash
+    #[schema(value_type = Option<Object>)]
+    pub before: Option<Json<serde_json::Value>>,
+    #[schema(value_type = Option<Object>)]
+    pub after: Option<Json<serde_json::Value>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+// a row of `feature_flags`, see `feature_flags` for how a flag is evaluated
+#[derive(Debug, FromRow, Serialize, Clone, ToSchema)]
+pub struct FeatureFlagModel {
+    #[schema(example = "comments")]
+    pub name: String,
+    pub description: String,
+    pub enabled: bool,
+    // 0 to 100
+    #[schema(example = 25)]
+    pub rollout_percentage: i32,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+// a row of `feature_flag_overrides`, which wins over the flag's own settings for one user
+#[derive(Debug, FromRow, Serialize, Clone, ToSchema)]
+pub struct FeatureFlagOverrideModel {
+    pub flag_name: String,
+    pub user_id: i32,
+    pub enabled: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
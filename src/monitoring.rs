@@ -0,0 +1,125 @@
+use std::{
+    sync::{Arc, OnceLock},
+    time::Instant,
+};
+
+use axum::{
+    body::Body,
+    extract::{MatchedPath, State},
+    http::{header, Request},
+    middleware::Next,
+    response::IntoResponse,
+};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+use crate::AppState;
+
+const HTTP_REQUESTS_TOTAL: &str = "http_requests_total";
+const HTTP_REQUEST_DURATION_SECONDS: &str = "http_request_duration_seconds";
+const DB_POOL_CONNECTIONS: &str = "db_pool_connections";
+const DB_POOL_MAX_CONNECTIONS: &str = "db_pool_max_connections";
+const LOGINS_TOTAL: &str = "blogrs_logins_total";
+const FAILED_LOGINS_TOTAL: &str = "blogrs_failed_logins_total";
+const REGISTRATIONS_TOTAL: &str = "blogrs_registrations_total";
+const POSTS_CREATED_TOTAL: &str = "blogrs_posts_created_total";
+const RATE_LIMITED_TOTAL: &str = "blogrs_rate_limited_total";
+const JOBS_TOTAL: &str = "blogrs_jobs_total";
+const POST_CACHE_REQUESTS_TOTAL: &str = "blogrs_post_cache_requests_total";
+const POST_CACHE_ENTRIES: &str = "blogrs_post_cache_entries";
+
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+// the recorder is process-global, so it is installed once no matter how many routers are built
+pub fn prometheus_handle() -> &'static PrometheusHandle {
+    HANDLE.get_or_init(|| {
+        PrometheusBuilder::new()
+            .set_buckets_for_metric(
+                Matcher::Full(HTTP_REQUEST_DURATION_SECONDS.to_string()),
+                LATENCY_BUCKETS,
+            )
+            .expect("latency buckets are not empty")
+            .install_recorder()
+            .expect("Failed to install the prometheus recorder")
+    })
+}
+
+// must be added with `route_layer` so the matched route is known; labelling by the route template
+// instead of the raw path keeps the number of series bounded
+pub async fn track_metrics(req: Request<Body>, next: Next) -> impl IntoResponse {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    // the request span is opened before routing, so the route is only known here
+    tracing::Span::current().record("route", route.as_str());
+
+    let response = next.run(req).await;
+
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    counter!(HTTP_REQUESTS_TOTAL, &labels).increment(1);
+    histogram!(HTTP_REQUEST_DURATION_SECONDS, &labels).record(start.elapsed().as_secs_f64());
+
+    response
+}
+
+pub async fn metrics_handler(State(data): State<Arc<AppState>>) -> impl IntoResponse {
+    // pool gauges are sampled on scrape rather than on every request
+    if let Some(pool) = data.database.pool_status() {
+        let active = pool.size.saturating_sub(pool.idle);
+        gauge!(DB_POOL_CONNECTIONS, "state" => "idle").set(pool.idle as f64);
+        gauge!(DB_POOL_CONNECTIONS, "state" => "active").set(active as f64);
+        gauge!(DB_POOL_CONNECTIONS, "state" => "size").set(pool.size as f64);
+        gauge!(DB_POOL_MAX_CONNECTIONS).set(pool.max as f64);
+    }
+    gauge!(POST_CACHE_ENTRIES).set(data.post_cache.stats().entries as f64);
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        prometheus_handle().render(),
+    )
+}
+
+// domain events
+
+pub fn record_login() {
+    counter!(LOGINS_TOTAL).increment(1);
+}
+
+pub fn record_failed_login() {
+    counter!(FAILED_LOGINS_TOTAL).increment(1);
+}
+
+pub fn record_registration() {
+    counter!(REGISTRATIONS_TOTAL).increment(1);
+}
+
+pub fn record_post_created() {
+    counter!(POSTS_CREATED_TOTAL).increment(1);
+}
+
+pub fn record_rate_limited(group: &'static str) {
+    counter!(RATE_LIMITED_TOTAL, "group" => group).increment(1);
+}
+
+// `outcome` is `completed`, `retried`, `dead` or `lease_lost`
+pub fn record_job(kind: &str, outcome: &'static str) {
+    counter!(JOBS_TOTAL, "kind" => kind.to_string(), "outcome" => outcome).increment(1);
+}
+
+// `kind` is `list` or `detail`, `outcome` is `hit` or `miss`
+pub fn record_post_cache(kind: &'static str, outcome: &'static str) {
+    counter!(POST_CACHE_REQUESTS_TOTAL, "kind" => kind, "outcome" => outcome).increment(1);
+}
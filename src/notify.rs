@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use sqlx::{postgres::PgListener, PgPool};
+use tokio_util::sync::CancellationToken;
+
+use crate::{error::AppError, feature_flags, post_cache, tasks::WorkerError, AppState};
+
+// the replicas of a postgres deployment tell each other about writes through `NOTIFY`; every cache
+// with a bus (`PgPostCacheBus`, `PgFeatureFlagBus`) has a channel of its own, and one connection
+// per replica listens on all of them
+
+// postgres rejects larger payloads
+pub const MAX_PAYLOAD_BYTES: usize = 7999;
+
+pub async fn notify(pool: &PgPool, channel: &str, payload: &str) -> Result<(), AppError> {
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(channel)
+        .bind(payload)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// a channel and what to do with its payloads. The handler is called with `None` when whatever was
+// published may have been missed: once the listener is up, as nothing was heard before, and after
+// the connection was lost. It then has to drop everything it keeps.
+pub struct Subscription<'a> {
+    channel: &'static str,
+    handler: Handler<'a>,
+}
+
+type Handler<'a> = Box<dyn Fn(Option<&str>) + Send + Sync + 'a>;
+
+impl<'a> Subscription<'a> {
+    pub fn new(channel: &'static str, handler: impl Fn(Option<&str>) + Send + Sync + 'a) -> Self {
+        Self {
+            channel,
+            handler: Box::new(handler),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PgNotifyListener {
+    pool: PgPool,
+}
+
+impl PgNotifyListener {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // listens on the channels of `subscriptions` on a connection of its own until `shutdown`
+    pub async fn listen(
+        &self,
+        subscriptions: &[Subscription<'_>],
+        shutdown: CancellationToken,
+    ) -> Result<(), WorkerError> {
+        if subscriptions.is_empty() {
+            return Ok(());
+        }
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener
+            .listen_all(
+                subscriptions
+                    .iter()
+                    .map(|subscription| subscription.channel),
+            )
+            .await?;
+        missed(subscriptions);
+
+        loop {
+            let notification = tokio::select! {
+                notification = listener.try_recv() => notification?,
+                _ = shutdown.cancelled() => return Ok(()),
+            };
+
+            match notification {
+                Some(notification) => {
+                    let channel = notification.channel();
+                    for subscription in subscriptions.iter().filter(|s| s.channel == channel) {
+                        (subscription.handler)(Some(notification.payload()));
+                    }
+                }
+                // `try_recv` reconnects on the next call
+                None => {
+                    tracing::warn!("The listener lost its connection, notifications were missed");
+                    missed(subscriptions);
+                }
+            }
+        }
+    }
+}
+
+fn missed(subscriptions: &[Subscription<'_>]) {
+    for subscription in subscriptions {
+        (subscription.handler)(None);
+    }
+}
+
+// runs under the `TaskSupervisor` when the state has a listener; hears the other replicas for
+// every cache that has a bus
+pub async fn listen_worker(
+    data: Arc<AppState>,
+    shutdown: CancellationToken,
+) -> Result<(), WorkerError> {
+    let Some(listener) = &data.notify_listener else {
+        return Ok(());
+    };
+
+    let mut subscriptions = Vec::new();
+    if data.config.post_cache.enabled && data.post_cache.bus().is_some() {
+        subscriptions.push(Subscription::new(
+            post_cache::postgres::CHANNEL,
+            |payload| post_cache::postgres::receive(&data.post_cache, payload),
+        ));
+    }
+    if data.feature_flags.bus().is_some() {
+        subscriptions.push(Subscription::new(
+            feature_flags::postgres::CHANNEL,
+            |name| feature_flags::postgres::receive(&data.feature_flags, name),
+        ));
+    }
+
+    listener.listen(&subscriptions, shutdown).await
+}
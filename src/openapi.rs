@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi, ToSchema,
+};
+
+use crate::{
+    error::ErrorResponse,
+    handlers::{
+        admin, auth, csp, feature_flag,
+        health::{
+            self, CheckResult, CheckStatus, LivenessResponse, MigrationsCheck, PoolCheck,
+            ReadinessChecks, ReadinessResponse, WorkersCheck,
+        },
+        post,
+    },
+    model::{AuditLogModel, FeatureFlagModel, FeatureFlagOverrideModel, PostModel, PostStatus},
+    schema::{
+        CreatePostSchema, FeatureFlagOverrideSchema, FeatureFlagSchema, FetchAllPostSchema,
+        LoginUserSchema, RegisterUserSchema, SetFeatureFlagOverrideSchema, UpdatePostSchema,
+        UpsertFeatureFlagSchema, UserDataSchema,
+    },
+    tasks::{WorkerState, WorkerStatus},
+};
+
+// the response envelopes below only describe the json built by the handlers; they are never
+// constructed
+
+#[derive(Serialize, ToSchema)]
+pub struct PostListResponse {
+    #[schema(example = "success")]
+    pub status: String,
+    pub data: Vec<FetchAllPostSchema>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PostDetailResponse {
+    #[schema(example = "success")]
+    pub status: String,
+    pub data: PostModel,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PostPayload {
+    pub post: PostModel,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PostResponse {
+    #[schema(example = "success")]
+    pub status: String,
+    pub data: PostPayload,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UserPayload {
+    pub user: UserDataSchema,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UserResponse {
+    #[schema(example = "success")]
+    pub status: String,
+    pub data: UserPayload,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LoginResponse {
+    #[schema(example = "success")]
+    pub status: String,
+    pub token: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuditLogResponse {
+    #[schema(example = "success")]
+    pub status: String,
+    pub data: Vec<AuditLogModel>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FeatureFlagListResponse {
+    #[schema(example = "success")]
+    pub status: String,
+    pub data: Vec<FeatureFlagSchema>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FeatureFlagResponse {
+    #[schema(example = "success")]
+    pub status: String,
+    pub data: FeatureFlagModel,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FeatureFlagOverrideResponse {
+    #[schema(example = "success")]
+    pub status: String,
+    pub data: FeatureFlagOverrideModel,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FeatureFlagValuesResponse {
+    #[schema(example = "success")]
+    pub status: String,
+    // by flag name
+    #[schema(example = json!({"comments": true, "search": false}))]
+    pub data: BTreeMap<String, bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StatusResponse {
+    #[schema(example = "success")]
+    pub status: String,
+}
+
+// names of the security schemes referenced by the `security(...)` of the protected routes
+const BEARER_AUTH: &str = "bearer_auth";
+const COOKIE_AUTH: &str = "cookie_auth";
+
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            BEARER_AUTH,
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some("Token returned by `/api/v1/auth/login`"))
+                    .build(),
+            ),
+        );
+        // checked before the bearer token by `auth_guard_middleware`
+        components.add_security_scheme(
+            COOKIE_AUTH,
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::with_description(
+                "token",
+                "httpOnly cookie set by `/api/v1/auth/login`",
+            ))),
+        );
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "blogrs", description = "Blog API written in Rust using axum"),
+    paths(
+        post::fetch_post_handler,
+        post::fetch_post_detail_handler,
+        post::create_post_handler,
+        post::create_post_v2_handler,
+        post::update_post_handler,
+        post::delete_post_handler,
+        post::publish_post_handler,
+        post::unpublish_post_handler,
+        post::archive_post_handler,
+        post::fetch_own_posts_handler,
+        auth::register_user_handler,
+        auth::login_user_handler,
+        auth::logout_user_handler,
+        auth::current_user_handler,
+        health::liveness_handler,
+        health::readiness_handler,
+        csp::csp_report_handler,
+        admin::fetch_audit_log_handler,
+        admin::fetch_feature_flags_handler,
+        admin::upsert_feature_flag_handler,
+        admin::delete_feature_flag_handler,
+        admin::set_feature_flag_override_handler,
+        admin::delete_feature_flag_override_handler,
+        feature_flag::fetch_feature_flag_values_handler,
+    ),
+    components(schemas(
+        CreatePostSchema,
+        UpdatePostSchema,
+        FetchAllPostSchema,
+        PostModel,
+        PostStatus,
+        RegisterUserSchema,
+        LoginUserSchema,
+        UserDataSchema,
+        ErrorResponse,
+        PostListResponse,
+        PostDetailResponse,
+        PostPayload,
+        PostResponse,
+        UserPayload,
+        UserResponse,
+        LoginResponse,
+        StatusResponse,
+        AuditLogModel,
+        AuditLogResponse,
+        FeatureFlagModel,
+        FeatureFlagOverrideModel,
+        FeatureFlagSchema,
+        FeatureFlagOverrideSchema,
+        UpsertFeatureFlagSchema,
+        SetFeatureFlagOverrideSchema,
+        FeatureFlagListResponse,
+        FeatureFlagResponse,
+        FeatureFlagOverrideResponse,
+        FeatureFlagValuesResponse,
+        LivenessResponse,
+        ReadinessResponse,
+        ReadinessChecks,
+        CheckStatus,
+        CheckResult,
+        MigrationsCheck,
+        PoolCheck,
+        WorkersCheck,
+        WorkerStatus,
+        WorkerState,
+    )),
+    modifiers(&SecurityAddon),
+    tags(
+        (name = "post", description = "Blog posts"),
+        (name = "auth", description = "Registration and authentication"),
+        (name = "health", description = "Liveness and readiness probes"),
+        (name = "security", description = "Content Security Policy violation reports"),
+        (name = "feature_flags", description = "Runtime feature flags"),
+        (name = "admin", description = "Administration, for admin accounts only"),
+    )
+)]
+pub struct ApiDoc;
@@ -0,0 +1,256 @@
+pub mod postgres;
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    config::PostCacheConfig, error::AppError, model::PostModel, monitoring,
+    schema::FetchAllPostSchema,
+};
+
+pub use postgres::PgPostCacheBus;
+
+// carries invalidations to the other replicas; without one the cache only sees the writes of its
+// own process. The other replicas hear them through `notify::listen_worker`.
+#[async_trait]
+pub trait PostCacheBus: Send + Sync {
+    // tells the other replicas that the posts with `slugs` changed
+    async fn publish(&self, slugs: &[String]) -> Result<(), AppError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    List { limit: i64, offset: i64 },
+    Detail(String),
+}
+
+impl Key {
+    fn kind(&self) -> &'static str {
+        match self {
+            Key::List { .. } => "list",
+            Key::Detail(_) => "detail",
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Value {
+    List(Arc<Vec<FetchAllPostSchema>>),
+    Detail(Arc<PostModel>),
+}
+
+struct Entry {
+    value: Value,
+    expires_at: Instant,
+}
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<Key, Entry>,
+    // bumped by every invalidation; a load that started before one is not stored, since it may
+    // have read the post before the write
+    generation: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PostCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+// the post list pages and details, kept for `post_cache.ttl_secs`. Only found posts are cached, so
+// a create never has to find a stale "not found". Every write clears all list pages, since any of
+// them can show the post.
+pub struct PostCache {
+    enabled: bool,
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    bus: Option<Arc<dyn PostCacheBus>>,
+}
+
+impl PostCache {
+    pub fn new(config: &PostCacheConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            ttl: Duration::from_secs(config.ttl_secs),
+            max_entries: config.max_entries,
+            entries: Mutex::default(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            bus: None,
+        }
+    }
+
+    pub fn with_bus(mut self, bus: Arc<dyn PostCacheBus>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    pub fn bus(&self) -> Option<&Arc<dyn PostCacheBus>> {
+        self.bus.as_ref()
+    }
+
+    fn entries(&self) -> MutexGuard<'_, Entries> {
+        // the map is valid after any panic while holding the lock
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub async fn list<F, Fut>(
+        &self,
+        limit: i64,
+        offset: i64,
+        load: F,
+    ) -> Result<Arc<Vec<FetchAllPostSchema>>, AppError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Vec<FetchAllPostSchema>, AppError>>,
+    {
+        let key = Key::List { limit, offset };
+        let value = self
+            .get_or_load(key, || async {
+                Ok(Some(Value::List(Arc::new(load().await?))))
+            })
+            .await?;
+
+        match value {
+            Some(Value::List(posts)) => Ok(posts),
+            _ => unreachable!("list keys only hold list values"),
+        }
+    }
+
+    pub async fn detail<F, Fut>(
+        &self,
+        slug: &str,
+        load: F,
+    ) -> Result<Option<Arc<PostModel>>, AppError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Option<PostModel>, AppError>>,
+    {
+        let key = Key::Detail(slug.to_string());
+        let value = self
+            .get_or_load(key, || async {
+                Ok(load().await?.map(|post| Value::Detail(Arc::new(post))))
+            })
+            .await?;
+
+        match value {
+            Some(Value::Detail(post)) => Ok(Some(post)),
+            None => Ok(None),
+            _ => unreachable!("detail keys only hold detail values"),
+        }
+    }
+
+    async fn get_or_load<F, Fut>(&self, key: Key, load: F) -> Result<Option<Value>, AppError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Option<Value>, AppError>>,
+    {
+        if !self.enabled {
+            return load().await;
+        }
+
+        let generation = {
+            let entries = self.entries();
+            if let Some(entry) = entries.map.get(&key) {
+                if entry.expires_at > Instant::now() {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    monitoring::record_post_cache(key.kind(), "hit");
+                    return Ok(Some(entry.value.clone()));
+                }
+            }
+            entries.generation
+        };
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        monitoring::record_post_cache(key.kind(), "miss");
+
+        let value = load().await?;
+        if let Some(value) = &value {
+            let mut entries = self.entries();
+            if entries.generation == generation {
+                self.insert(&mut entries, key, value.clone());
+            }
+        }
+        Ok(value)
+    }
+
+    fn insert(&self, entries: &mut Entries, key: Key, value: Value) {
+        let now = Instant::now();
+        if entries.map.len() >= self.max_entries && !entries.map.contains_key(&key) {
+            entries.map.retain(|_, entry| entry.expires_at > now);
+        }
+        // every entry lives for the same ttl, so the one expiring first is the oldest
+        while entries.map.len() >= self.max_entries && !entries.map.contains_key(&key) {
+            let oldest = entries
+                .map
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires_at)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(oldest) => entries.map.remove(&oldest),
+                None => break,
+            };
+        }
+
+        entries.map.insert(
+            key,
+            Entry {
+                value,
+                expires_at: now + self.ttl,
+            },
+        );
+    }
+
+    // called after a write to the posts with `slugs` committed, with both slugs of a rename.
+    // The other replicas are told through the bus; if that fails they catch up within the ttl.
+    pub async fn invalidate(&self, slugs: &[&str]) {
+        let slugs = slugs
+            .iter()
+            .map(|slug| slug.to_string())
+            .collect::<Vec<_>>();
+        self.invalidate_local(&slugs);
+
+        if let Some(bus) = &self.bus {
+            if let Err(e) = bus.publish(&slugs).await {
+                tracing::warn!(?slugs, "Publishing a post cache invalidation failed: {e:?}");
+            }
+        }
+    }
+
+    // drops the details of `slugs` and every list page
+    pub fn invalidate_local(&self, slugs: &[String]) {
+        let mut entries = self.entries();
+        entries.generation += 1;
+        entries.map.retain(|key, _| match key {
+            Key::List { .. } => false,
+            Key::Detail(slug) => !slugs.contains(slug),
+        });
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries();
+        entries.generation += 1;
+        entries.map.clear();
+    }
+
+    pub fn stats(&self) -> PostCacheStats {
+        PostCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries().map.len(),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+use crate::{error::AppError, notify};
+
+use super::{PostCache, PostCacheBus};
+
+pub const CHANNEL: &str = "blogrs_post_cache";
+
+// a longer list of slugs than fits in a notification is sent as `*` instead
+const CLEAR_ALL: &str = "*";
+
+// invalidations through `NOTIFY` on `CHANNEL`, a json array of slugs. Every replica listens on its
+// own connection, including the one that published, which then drops the same entries twice.
+#[derive(Clone)]
+pub struct PgPostCacheBus {
+    pool: PgPool,
+}
+
+impl PgPostCacheBus {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl PostCacheBus for PgPostCacheBus {
+    async fn publish(&self, slugs: &[String]) -> Result<(), AppError> {
+        let payload = serde_json::to_string(slugs)
+            .map_err(|e| AppError::Internal(format!("failed to serialize the slugs: {e}")))?;
+        let payload = if payload.len() > notify::MAX_PAYLOAD_BYTES {
+            CLEAR_ALL
+        } else {
+            &payload
+        };
+
+        notify::notify(&self.pool, CHANNEL, payload).await
+    }
+}
+
+// applies a payload of `CHANNEL` to `cache`, see `notify::listen_worker`
+pub fn receive(cache: &PostCache, payload: Option<&str>) {
+    match payload.and_then(|payload| serde_json::from_str::<Vec<String>>(payload).ok()) {
+        Some(slugs) => cache.invalidate_local(&slugs),
+        // missed notifications, `*`, or a payload from another version
+        None => cache.clear(),
+    }
+}
@@ -0,0 +1,48 @@
+use std::{collections::HashMap, sync::Mutex, time::Instant};
+
+use async_trait::async_trait;
+
+use crate::error::AppError;
+
+use super::{take_token, Bucket, Decision, RateLimitStore, IDLE_BUCKET_TTL};
+
+// per-process buckets; every replica enforces the limits on its own
+#[derive(Default)]
+pub struct MemoryRateLimitStore {
+    buckets: Mutex<HashMap<String, (f64, Instant)>>,
+}
+
+impl MemoryRateLimitStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn take(&self, key: &str, bucket: Bucket) -> Result<Decision, AppError> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let (tokens, updated_at) = buckets
+            .entry(key.to_string())
+            .or_insert((bucket.capacity, now));
+
+        let elapsed = now.saturating_duration_since(*updated_at);
+        let (left, decision) = take_token(*tokens, elapsed.as_secs_f64(), bucket);
+        *tokens = left;
+        *updated_at = now;
+
+        Ok(decision)
+    }
+
+    async fn prune(&self) -> Result<(), AppError> {
+        let now = Instant::now();
+        self.buckets
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|_, (_, updated_at)| {
+                now.saturating_duration_since(*updated_at) < IDLE_BUCKET_TTL
+            });
+        Ok(())
+    }
+}
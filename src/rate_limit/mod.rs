@@ -0,0 +1,290 @@
+pub mod memory;
+pub mod postgres;
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, Request},
+    middleware::Next,
+    response::IntoResponse,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::{Config, RateLimitKey, RateLimitRule},
+    error::AppError,
+    model::UserModel,
+    monitoring,
+    tasks::WorkerError,
+    AppState,
+};
+
+pub use memory::MemoryRateLimitStore;
+pub use postgres::PgRateLimitStore;
+
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+// buckets untouched for this long are full again under any sensible rule and can be forgotten
+pub const IDLE_BUCKET_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Bucket {
+    pub capacity: f64,
+    pub refill_per_sec: f64,
+}
+
+impl From<&RateLimitRule> for Bucket {
+    fn from(rule: &RateLimitRule) -> Self {
+        Self {
+            capacity: f64::from(rule.burst),
+            refill_per_sec: f64::from(rule.per_minute) / 60.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Allowed,
+    Limited { retry_after_secs: u64 },
+}
+
+// refills `tokens` for the `elapsed_secs` since the last request and takes one; returns the
+// tokens left for the next request. Shared by the stores so they count the same way.
+pub fn take_token(tokens: f64, elapsed_secs: f64, bucket: Bucket) -> (f64, Decision) {
+    let tokens = (tokens + elapsed_secs.max(0.0) * bucket.refill_per_sec).min(bucket.capacity);
+    if tokens >= 1.0 {
+        return (tokens - 1.0, Decision::Allowed);
+    }
+
+    let retry_after_secs = ((1.0 - tokens) / bucket.refill_per_sec).ceil() as u64;
+    (
+        tokens,
+        Decision::Limited {
+            retry_after_secs: retry_after_secs.max(1),
+        },
+    )
+}
+
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    async fn take(&self, key: &str, bucket: Bucket) -> Result<Decision, AppError>;
+
+    // forgets the buckets idle for longer than `IDLE_BUCKET_TTL`
+    async fn prune(&self) -> Result<(), AppError>;
+}
+
+// the route groups limited by `rate_limit_middleware`, each with its own rule in `[rate_limit]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitGroup {
+    Auth,
+    Write,
+    CspReport,
+}
+
+impl RateLimitGroup {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Auth => "auth",
+            Self::Write => "write",
+            Self::CspReport => "csp_report",
+        }
+    }
+
+    fn rule(self, config: &Config) -> &RateLimitRule {
+        match self {
+            Self::Auth => &config.rate_limit.auth,
+            Self::Write => &config.rate_limit.write,
+            Self::CspReport => &config.rate_limit.csp_report,
+        }
+    }
+}
+
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    trusted_proxies: Vec<IpRange>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &Config, store: Arc<dyn RateLimitStore>) -> Self {
+        Self {
+            store,
+            // validated on startup
+            trusted_proxies: config
+                .rate_limit
+                .trusted_proxies
+                .iter()
+                .filter_map(|proxy| proxy.parse().ok())
+                .collect(),
+        }
+    }
+
+    pub fn store(&self) -> &Arc<dyn RateLimitStore> {
+        &self.store
+    }
+
+    // the peer address, or the address it forwarded for if the peer is a trusted proxy. The
+    // `X-Forwarded-For` hops are read from the right, skipping the trusted proxies, since anything
+    // left of the last untrusted hop can be forged by the client.
+    pub fn client_ip(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+        let peer = peer?;
+        if !self.is_trusted(peer) {
+            return Some(peer);
+        }
+
+        let hops = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect::<Vec<_>>();
+
+        let mut client = peer;
+        for hop in hops.into_iter().rev() {
+            let Ok(ip) = hop.parse::<IpAddr>() else {
+                break;
+            };
+            client = ip;
+            if !self.is_trusted(ip) {
+                break;
+            }
+        }
+        Some(client)
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|range| range.contains(ip))
+    }
+}
+
+// an address or a CIDR range, e.g. `10.0.0.0/8`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpRange {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match (self.network, ip) {
+            (IpAddr::V4(_), IpAddr::V6(v6)) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            _ => ip,
+        };
+
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                prefix_matches(u32::from(network), u32::from(ip), self.prefix, 32)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                prefix_matches(u128::from(network), u128::from(ip), self.prefix, 128)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_matches<T>(network: T, ip: T, prefix: u8, bits: u8) -> bool
+where
+    T: Copy + std::ops::BitXor<Output = T> + std::ops::Shr<u32, Output = T> + PartialEq + From<u8>,
+{
+    if prefix == 0 {
+        return true;
+    }
+    ((network ^ ip) >> u32::from(bits - prefix)) == T::from(0)
+}
+
+impl FromStr for IpRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = match s.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (s, None),
+        };
+        let network = address
+            .trim()
+            .parse::<IpAddr>()
+            .map_err(|e| e.to_string())?;
+        let bits = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse::<u8>().map_err(|e| e.to_string())?,
+            None => bits,
+        };
+        if prefix > bits {
+            return Err(format!("prefix /{prefix} is longer than {bits} bits"));
+        }
+
+        Ok(Self { network, prefix })
+    }
+}
+
+// limits the requests of a route group; `RateLimitKey::User` relies on `auth_guard_middleware`
+// running first and falls back to the client address without a user
+pub async fn rate_limit_middleware(
+    State((data, group)): State<(Arc<AppState>, RateLimitGroup)>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<impl IntoResponse, AppError> {
+    if !data.config.rate_limit.enabled {
+        return Ok(next.run(req).await);
+    }
+
+    let rule = group.rule(&data.config);
+    let user_id = req
+        .extensions()
+        .get::<UserModel>()
+        .map(|user| user.id)
+        .filter(|_| rule.key == RateLimitKey::User);
+    let key = match user_id {
+        Some(user_id) => format!("{}:user:{user_id}", group.name()),
+        None => {
+            let peer = req
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip());
+            match data.rate_limiter.client_ip(peer, req.headers()) {
+                Some(ip) => format!("{}:ip:{ip}", group.name()),
+                None => format!("{}:ip:unknown", group.name()),
+            }
+        }
+    };
+
+    // a broken store must not take the api down with it
+    match data.rate_limiter.store.take(&key, rule.into()).await {
+        Ok(Decision::Allowed) => {}
+        Ok(Decision::Limited { retry_after_secs }) => {
+            monitoring::record_rate_limited(group.name());
+            return Err(AppError::RateLimited { retry_after_secs });
+        }
+        Err(e) => tracing::warn!(key, "Rate limit store failed, allowing the request: {e:?}"),
+    }
+
+    Ok(next.run(req).await)
+}
+
+// runs under the `TaskSupervisor`; an error restarts it with backoff
+pub async fn prune_worker(
+    data: Arc<AppState>,
+    shutdown: CancellationToken,
+) -> Result<(), WorkerError> {
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(PRUNE_INTERVAL) => {}
+            _ = shutdown.cancelled() => return Ok(()),
+        }
+
+        data.rate_limiter
+            .store
+            .prune()
+            .await
+            .map_err(|e| format!("pruning the rate limit buckets failed: {e:?}"))?;
+    }
+}
@@ -0,0 +1,73 @@
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+use crate::error::AppError;
+
+use super::{take_token, Bucket, Decision, RateLimitStore, IDLE_BUCKET_TTL};
+
+// buckets in the `rate_limit_bucket` table, shared by every replica using the database. The row
+// is locked while a request takes its token and the elapsed time is measured by the database
+// clock, so replicas with skewed clocks still agree.
+#[derive(Clone)]
+pub struct PgRateLimitStore {
+    pool: PgPool,
+}
+
+impl PgRateLimitStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for PgRateLimitStore {
+    async fn take(&self, key: &str, bucket: Bucket) -> Result<Decision, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO rate_limit_bucket (key, tokens, updated_at)
+            VALUES ($1, $2, clock_timestamp())
+            ON CONFLICT (key) DO NOTHING
+            "#,
+        )
+        .bind(key)
+        .bind(bucket.capacity)
+        .execute(&mut *tx)
+        .await?;
+
+        let (tokens, elapsed): (f64, f64) = sqlx::query_as(
+            r#"
+            SELECT tokens, EXTRACT(EPOCH FROM clock_timestamp() - updated_at)::float8
+            FROM rate_limit_bucket
+            WHERE key = $1
+            FOR UPDATE
+            "#,
+        )
+        .bind(key)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let (left, decision) = take_token(tokens, elapsed, bucket);
+        sqlx::query(
+            "UPDATE rate_limit_bucket SET tokens = $2, updated_at = clock_timestamp() WHERE key = $1",
+        )
+        .bind(key)
+        .bind(left)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(decision)
+    }
+
+    async fn prune(&self) -> Result<(), AppError> {
+        sqlx::query(
+            "DELETE FROM rate_limit_bucket WHERE updated_at < NOW() - make_interval(secs => $1)",
+        )
+        .bind(IDLE_BUCKET_TTL.as_secs_f64())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use std::{str::FromStr, sync::Arc};
+
+use sqlx::{
+    migrate::MigrateError,
+    postgres::PgPoolOptions,
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+};
+
+use crate::{
+    config::{Config, DatabaseConfig, DatabaseKind, RateLimitStoreKind},
+    feature_flags::PgFeatureFlagBus,
+    notify::PgNotifyListener,
+    post_cache::PgPostCacheBus,
+    rate_limit::PgRateLimitStore,
+    AppState,
+};
+
+use super::{postgres, sqlite, PgRepository, SqliteRepository};
+
+// the sql backend selected by `database.url` at startup
+pub enum Backend {
+    Postgres(PgRepository),
+    Sqlite(SqliteRepository),
+}
+
+impl Backend {
+    pub async fn connect(config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+        let url = config
+            .url
+            .as_deref()
+            .ok_or_else(|| sqlx::Error::Configuration("database.url is not set".into()))?;
+
+        match DatabaseKind::from_url(url) {
+            Some(DatabaseKind::Postgres) => {
+                let pool = PgPoolOptions::new()
+                    .max_connections(config.max_connections)
+                    .connect(url)
+                    .await?;
+                Ok(Self::Postgres(PgRepository::new(pool)))
+            }
+            Some(DatabaseKind::Sqlite) => {
+                let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+                let pool = SqlitePoolOptions::new()
+                    .max_connections(config.max_connections)
+                    .connect_with(options)
+                    .await?;
+                Ok(Self::Sqlite(SqliteRepository::new(pool)))
+            }
+            None => Err(sqlx::Error::Configuration(
+                format!("unsupported database url `{url}`").into(),
+            )),
+        }
+    }
+
+    pub fn kind(&self) -> DatabaseKind {
+        match self {
+            Self::Postgres(_) => DatabaseKind::Postgres,
+            Self::Sqlite(_) => DatabaseKind::Sqlite,
+        }
+    }
+
+    // runs the migration set of the backend
+    pub async fn migrate(&self) -> Result<(), MigrateError> {
+        match self {
+            Self::Postgres(repository) => postgres::MIGRATOR.run(repository.pool()).await,
+            Self::Sqlite(repository) => sqlite::MIGRATOR.run(repository.pool()).await,
+        }
+    }
+
+    pub fn into_app_state(self, config: Config) -> AppState {
+        match self {
+            Self::Postgres(repository) => {
+                let store = (config.rate_limit.store == RateLimitStoreKind::Postgres)
+                    .then(|| PgRateLimitStore::new(repository.pool().clone()));
+                let post_cache_bus = PgPostCacheBus::new(repository.pool().clone());
+                let feature_flag_bus = PgFeatureFlagBus::new(repository.pool().clone());
+                let listener = PgNotifyListener::new(repository.pool().clone());
+                let state = AppState::new(config, repository)
+                    .with_post_cache_bus(Arc::new(post_cache_bus))
+                    .with_feature_flag_bus(Arc::new(feature_flag_bus))
+                    .with_notify_listener(listener);
+                match store {
+                    Some(store) => state.with_rate_limit_store(Arc::new(store)),
+                    None => state,
+                }
+            }
+            // `Config::validate` rejects the postgres rate limit store with a sqlite database
+            Self::Sqlite(repository) => AppState::new(config, repository),
+        }
+    }
+}
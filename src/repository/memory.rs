@@ -0,0 +1,1053 @@
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+
+use crate::{
+    error::AppError,
+    model::{
+        AuditAction, AuditLogModel, CategoryModel, CspReportModel, FeatureFlagModel,
+        FeatureFlagOverrideModel, IdempotencyKeyModel, JobModel, JobState, PostModel, PostStatus,
+        UserModel,
+    },
+    schema::FetchAllPostSchema,
+};
+
+use super::{
+    Actor, AuditFilter, AuditRepository, BackupExport, BackupImport, BackupRepository,
+    CategoryRepository, CspReportRepository, DatabaseHealth, FeatureFlagData,
+    FeatureFlagRepository, IdempotencyRepository, JobRepository, NewAuditEntry, NewCspReport,
+    NewJob, NewUser, PoolStatus, PostData, PostFilter, PostRepository, StoredResponse,
+    UserRepository,
+};
+
+// in-memory backend used by the tests; it enforces the same unique and foreign key rules as
+// the postgres schema so the handlers behave identically on both
+#[derive(Clone, Default)]
+pub struct MemoryRepository {
+    data: Arc<Mutex<Data>>,
+}
+
+#[derive(Default)]
+struct Data {
+    users: Vec<UserModel>,
+    categories: Vec<CategoryModel>,
+    posts: Vec<PostModel>,
+    jobs: Vec<JobModel>,
+    idempotency_keys: Vec<IdempotencyKeyModel>,
+    csp_reports: Vec<CspReportModel>,
+    audit_log: Vec<AuditLogModel>,
+    // kept sorted by name, and the overrides by flag name and user id
+    feature_flags: Vec<FeatureFlagModel>,
+    feature_flag_overrides: Vec<FeatureFlagOverrideModel>,
+    next_user_id: i32,
+    next_category_id: i32,
+    next_post_id: i32,
+    next_job_id: i64,
+    next_csp_report_id: i64,
+    next_audit_id: i64,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn data(&self) -> MutexGuard<'_, Data> {
+        // a panic while holding the lock cannot leave the vectors half-written
+        self.data.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn next_id(counter: &mut i32) -> i32 {
+    *counter += 1;
+    *counter
+}
+
+impl Data {
+    fn check_post(&self, post: &PostData, ignore_id: Option<i32>) -> Result<(), AppError> {
+        if self
+            .posts
+            .iter()
+            .any(|p| p.slug == post.slug && Some(p.id) != ignore_id)
+        {
+            return Err(AppError::PostSlugTaken);
+        }
+        if let Some(category_id) = post.category_id {
+            if !self.categories.iter().any(|c| c.id == category_id) {
+                return Err(AppError::CategoryNotFound);
+            }
+        }
+        Ok(())
+    }
+
+    // mirrors the partial unique index on `jobs.unique_key`
+    fn insert_job(&mut self, job: NewJob) -> Option<JobModel> {
+        let taken = job.unique_key.is_some()
+            && self
+                .jobs
+                .iter()
+                .any(|j| j.unique_key == job.unique_key && j.state != JobState::Dead.as_str());
+        if taken {
+            return None;
+        }
+
+        self.next_job_id += 1;
+        let now = Utc::now();
+        let job = JobModel {
+            id: self.next_job_id,
+            kind: job.kind,
+            payload: Json(job.payload),
+            state: JobState::Queued.as_str().to_string(),
+            attempts: 0,
+            max_attempts: job.max_attempts,
+            unique_key: job.unique_key,
+            last_error: None,
+            run_at: job.run_at.unwrap_or(now),
+            locked_until: None,
+            created_at: now,
+            updated_at: now,
+        };
+        self.jobs.push(job.clone());
+        Some(job)
+    }
+
+    // the writes hold the lock while recording, which makes the entry part of the same change
+    fn insert_audit_entry(&mut self, entry: NewAuditEntry) -> AuditLogModel {
+        self.next_audit_id += 1;
+        let entry = AuditLogModel {
+            id: self.next_audit_id,
+            actor_id: entry.actor.user_id,
+            ip: entry.actor.ip,
+            user_agent: entry.actor.user_agent,
+            action: entry.action.as_str().to_string(),
+            target_type: entry.action.target_type().to_string(),
+            target_id: entry.target_id,
+            before: entry.before.map(Json),
+            after: entry.after.map(Json),
+            created_at: Utc::now(),
+        };
+        self.audit_log.push(entry.clone());
+        entry
+    }
+
+    fn job_mut(&mut self, id: i64) -> Option<&mut JobModel> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    // the job, if the lease taken with `attempt` is still held
+    fn leased_job_mut(&mut self, id: i64, attempt: i32) -> Option<&mut JobModel> {
+        self.job_mut(id)
+            .filter(|job| job.state == JobState::Running.as_str() && job.attempts == attempt)
+    }
+}
+
+#[async_trait]
+impl PostRepository for MemoryRepository {
+    async fn list(
+        &self,
+        filter: &PostFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<FetchAllPostSchema>, AppError> {
+        let data = self.data();
+        let mut posts = data
+            .posts
+            .iter()
+            .filter(|post| filter.user_id.is_none_or(|id| post.user_id == id))
+            .filter(|post| filter.status.is_none_or(|status| post.status == status))
+            .collect::<Vec<_>>();
+        // never published posts first, as with `NULLS FIRST`; ids break ties between posts
+        // created within the same instant
+        posts.sort_by_key(|post| {
+            std::cmp::Reverse((
+                post.published_at.is_none(),
+                post.published_at,
+                post.created_at,
+                post.id,
+            ))
+        });
+
+        Ok(posts
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .map(|post| FetchAllPostSchema {
+                id: post.id,
+                title: post.title.to_owned(),
+                slug: post.slug.to_owned(),
+                user_id: post.user_id,
+                excerpt: post.excerpt.to_owned(),
+                category_id: post.category_id,
+                created_at: post.created_at,
+                updated_at: post.updated_at,
+                status: post.status,
+                published_at: post.published_at,
+            })
+            .collect())
+    }
+
+    async fn find_by_slug(&self, slug: &str) -> Result<Option<PostModel>, AppError> {
+        Ok(self.data().posts.iter().find(|p| p.slug == slug).cloned())
+    }
+
+    async fn create(
+        &self,
+        user_id: i32,
+        post: PostData,
+        status: PostStatus,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<PostModel, AppError> {
+        let mut data = self.data();
+        data.check_post(&post, None)?;
+        if !data.users.iter().any(|u| u.id == user_id) {
+            return Err(AppError::TokenUserNotFound);
+        }
+
+        let now = chrono::Utc::now();
+        let post = PostModel {
+            id: next_id(&mut data.next_post_id),
+            title: post.title,
+            slug: post.slug,
+            user_id,
+            excerpt: post.excerpt,
+            content: post.content,
+            category_id: post.category_id,
+            created_at: Some(now),
+            updated_at: Some(now),
+            status,
+            published_at: (status == PostStatus::Published).then_some(now),
+        };
+        data.posts.push(post.clone());
+        for job in jobs {
+            data.insert_job(job);
+        }
+        data.insert_audit_entry(NewAuditEntry::post(
+            actor,
+            AuditAction::PostCreate,
+            None,
+            Some(&post),
+        ));
+
+        Ok(post)
+    }
+
+    async fn update(
+        &self,
+        slug: &str,
+        post: PostData,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError> {
+        let mut data = self.data();
+        let Some(index) = data.posts.iter().position(|p| p.slug == slug) else {
+            return Ok(None);
+        };
+        data.check_post(&post, Some(data.posts[index].id))?;
+
+        let before = data.posts[index].clone();
+        let existing = &mut data.posts[index];
+        existing.title = post.title;
+        existing.slug = post.slug;
+        existing.excerpt = post.excerpt;
+        existing.content = post.content;
+        existing.category_id = post.category_id;
+        existing.updated_at = Some(chrono::Utc::now());
+        let updated = existing.clone();
+        for job in jobs {
+            data.insert_job(job);
+        }
+        data.insert_audit_entry(NewAuditEntry::post(
+            actor,
+            AuditAction::PostUpdate,
+            Some(&before),
+            Some(&updated),
+        ));
+
+        Ok(Some(updated))
+    }
+
+    async fn set_status(
+        &self,
+        slug: &str,
+        status: PostStatus,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError> {
+        let mut data = self.data();
+        let Some(index) = data.posts.iter().position(|p| p.slug == slug) else {
+            return Ok(None);
+        };
+
+        let before = data.posts[index].clone();
+        let existing = &mut data.posts[index];
+        let now = chrono::Utc::now();
+        existing.status = status;
+        if status == PostStatus::Published {
+            existing.published_at = existing.published_at.or(Some(now));
+        }
+        existing.updated_at = Some(now);
+        let updated = existing.clone();
+        for job in jobs {
+            data.insert_job(job);
+        }
+        data.insert_audit_entry(NewAuditEntry::post(
+            actor,
+            status.audit_action(),
+            Some(&before),
+            Some(&updated),
+        ));
+
+        Ok(Some(updated))
+    }
+
+    async fn reassign(
+        &self,
+        slug: &str,
+        user_id: i32,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError> {
+        let mut data = self.data();
+        let Some(index) = data.posts.iter().position(|p| p.slug == slug) else {
+            return Ok(None);
+        };
+        if !data.users.iter().any(|u| u.id == user_id) {
+            return Err(AppError::TokenUserNotFound);
+        }
+
+        let before = data.posts[index].clone();
+        let existing = &mut data.posts[index];
+        existing.user_id = user_id;
+        existing.updated_at = Some(chrono::Utc::now());
+        let updated = existing.clone();
+        data.insert_audit_entry(NewAuditEntry::post(
+            actor,
+            AuditAction::PostReassign,
+            Some(&before),
+            Some(&updated),
+        ));
+
+        Ok(Some(updated))
+    }
+
+    async fn delete(
+        &self,
+        slug: &str,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError> {
+        let mut data = self.data();
+        let Some(index) = data.posts.iter().position(|p| p.slug == slug) else {
+            return Ok(None);
+        };
+
+        let post = data.posts.remove(index);
+        for job in jobs {
+            data.insert_job(job);
+        }
+        data.insert_audit_entry(NewAuditEntry::post(
+            actor,
+            AuditAction::PostDelete,
+            Some(&post),
+            None,
+        ));
+
+        Ok(Some(post))
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryRepository {
+    async fn find_by_email(&self, email: &str) -> Result<Option<UserModel>, AppError> {
+        Ok(self.data().users.iter().find(|u| u.email == email).cloned())
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<UserModel>, AppError> {
+        Ok(self
+            .data()
+            .users
+            .iter()
+            .find(|u| u.username == username)
+            .cloned())
+    }
+
+    async fn exists(&self, email: &str, username: &str) -> Result<bool, AppError> {
+        Ok(self
+            .data()
+            .users
+            .iter()
+            .any(|u| u.email == email || u.username == username))
+    }
+
+    async fn create(&self, user: NewUser, actor: &Actor) -> Result<UserModel, AppError> {
+        let mut data = self.data();
+        if data
+            .users
+            .iter()
+            .any(|u| u.email == user.email || u.username == user.username)
+        {
+            return Err(AppError::UserAlreadyExists);
+        }
+
+        let now = chrono::Utc::now();
+        let user = UserModel {
+            id: next_id(&mut data.next_user_id),
+            name: None,
+            username: user.username,
+            email: user.email,
+            password: user.password,
+            is_admin: user.is_admin,
+            created_at: Some(now),
+            updated_at: Some(now),
+        };
+        data.users.push(user.clone());
+        data.insert_audit_entry(NewAuditEntry::user(actor, AuditAction::UserRegister, &user));
+
+        Ok(user)
+    }
+
+    async fn set_password(
+        &self,
+        email: &str,
+        password: &str,
+        actor: &Actor,
+    ) -> Result<Option<UserModel>, AppError> {
+        let mut data = self.data();
+        let Some(user) = data.users.iter_mut().find(|u| u.email == email) else {
+            return Ok(None);
+        };
+
+        user.password = password.to_string();
+        user.updated_at = Some(chrono::Utc::now());
+        let user = user.clone();
+        data.insert_audit_entry(NewAuditEntry::user(
+            actor,
+            AuditAction::UserPasswordReset,
+            &user,
+        ));
+
+        Ok(Some(user))
+    }
+}
+
+#[async_trait]
+impl CategoryRepository for MemoryRepository {
+    async fn list(&self) -> Result<Vec<CategoryModel>, AppError> {
+        Ok(self.data().categories.clone())
+    }
+
+    async fn find_by_id(&self, id: i32) -> Result<Option<CategoryModel>, AppError> {
+        Ok(self.data().categories.iter().find(|c| c.id == id).cloned())
+    }
+
+    async fn create(&self, name: &str, actor: &Actor) -> Result<CategoryModel, AppError> {
+        let mut data = self.data();
+        if data.categories.iter().any(|c| c.name == name) {
+            return Err(AppError::CategoryAlreadyExists);
+        }
+
+        let category = CategoryModel {
+            id: next_id(&mut data.next_category_id),
+            name: name.to_string(),
+        };
+        data.categories.push(category.clone());
+        data.insert_audit_entry(NewAuditEntry::snapshot(
+            actor,
+            AuditAction::CategoryCreate,
+            category.id,
+            None,
+            Some(&category),
+        ));
+
+        Ok(category)
+    }
+}
+
+#[async_trait]
+impl BackupRepository for MemoryRepository {
+    async fn begin_export(&self) -> Result<Box<dyn BackupExport>, AppError> {
+        let data = self.data();
+        Ok(Box::new(MemoryBackupExport {
+            users: data.users.clone(),
+            categories: data.categories.clone(),
+            posts: data.posts.clone(),
+        }))
+    }
+
+    async fn begin_import(&self) -> Result<Box<dyn BackupImport>, AppError> {
+        let data = self.data();
+        let staged = Data {
+            users: data.users.clone(),
+            categories: data.categories.clone(),
+            posts: data.posts.clone(),
+            next_user_id: data.next_user_id,
+            next_category_id: data.next_category_id,
+            next_post_id: data.next_post_id,
+            ..Data::default()
+        };
+        Ok(Box::new(MemoryBackupImport {
+            repository: self.clone(),
+            staged,
+        }))
+    }
+}
+
+// a copy of the tables taken when the export began
+struct MemoryBackupExport {
+    users: Vec<UserModel>,
+    categories: Vec<CategoryModel>,
+    posts: Vec<PostModel>,
+}
+
+fn page<T: Clone>(rows: &[T], id: impl Fn(&T) -> i32, after_id: i32, limit: i64) -> Vec<T> {
+    let mut page = rows
+        .iter()
+        .filter(|row| id(row) > after_id)
+        .cloned()
+        .collect::<Vec<_>>();
+    page.sort_by_key(&id);
+    page.truncate(limit.max(0) as usize);
+    page
+}
+
+#[async_trait]
+impl BackupExport for MemoryBackupExport {
+    async fn users(&mut self, after_id: i32, limit: i64) -> Result<Vec<UserModel>, AppError> {
+        Ok(page(&self.users, |user| user.id, after_id, limit))
+    }
+
+    async fn categories(&mut self) -> Result<Vec<CategoryModel>, AppError> {
+        Ok(self.categories.clone())
+    }
+
+    async fn posts(&mut self, after_id: i32, limit: i64) -> Result<Vec<PostModel>, AppError> {
+        Ok(page(&self.posts, |post| post.id, after_id, limit))
+    }
+}
+
+// writes into a copy of the tables that replaces them on commit
+struct MemoryBackupImport {
+    repository: MemoryRepository,
+    staged: Data,
+}
+
+#[async_trait]
+impl BackupImport for MemoryBackupImport {
+    async fn is_empty(&mut self) -> Result<bool, AppError> {
+        let data = &self.staged;
+        Ok(data.users.is_empty() && data.categories.is_empty() && data.posts.is_empty())
+    }
+
+    async fn user(&mut self, user: &UserModel) -> Result<UserModel, AppError> {
+        let data = &mut self.staged;
+        if data
+            .users
+            .iter()
+            .any(|u| u.email == user.email || u.username == user.username)
+        {
+            return Err(AppError::UserAlreadyExists);
+        }
+
+        let user = UserModel {
+            id: next_id(&mut data.next_user_id),
+            ..user.clone()
+        };
+        data.users.push(user.clone());
+
+        Ok(user)
+    }
+
+    async fn category(&mut self, name: &str) -> Result<CategoryModel, AppError> {
+        let data = &mut self.staged;
+        if data.categories.iter().any(|c| c.name == name) {
+            return Err(AppError::CategoryAlreadyExists);
+        }
+
+        let category = CategoryModel {
+            id: next_id(&mut data.next_category_id),
+            name: name.to_string(),
+        };
+        data.categories.push(category.clone());
+
+        Ok(category)
+    }
+
+    async fn post(&mut self, post: &PostModel) -> Result<PostModel, AppError> {
+        let data = &mut self.staged;
+        let fields = PostData {
+            title: post.title.to_owned(),
+            slug: post.slug.to_owned(),
+            excerpt: post.excerpt.to_owned(),
+            content: post.content.to_owned(),
+            category_id: post.category_id,
+        };
+        data.check_post(&fields, None)?;
+        if !data.users.iter().any(|u| u.id == post.user_id) {
+            return Err(AppError::TokenUserNotFound);
+        }
+
+        let post = PostModel {
+            id: next_id(&mut data.next_post_id),
+            ..post.clone()
+        };
+        data.posts.push(post.clone());
+
+        Ok(post)
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), AppError> {
+        let staged = self.staged;
+        let mut data = self.repository.data();
+        data.users = staged.users;
+        data.categories = staged.categories;
+        data.posts = staged.posts;
+        data.next_user_id = staged.next_user_id;
+        data.next_category_id = staged.next_category_id;
+        data.next_post_id = staged.next_post_id;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl JobRepository for MemoryRepository {
+    async fn enqueue(&self, job: NewJob) -> Result<Option<JobModel>, AppError> {
+        Ok(self.data().insert_job(job))
+    }
+
+    async fn claim(&self, limit: i64, lease: Duration) -> Result<Vec<JobModel>, AppError> {
+        let mut data = self.data();
+        let now = Utc::now();
+        let is_due = |job: &JobModel| match job.state.as_str() {
+            "queued" => job.run_at <= now,
+            "running" => job.locked_until.is_some_and(|until| until < now),
+            _ => false,
+        };
+
+        let mut due = data
+            .jobs
+            .iter()
+            .filter(|job| is_due(job))
+            .map(|job| (job.run_at, job.id))
+            .collect::<Vec<_>>();
+        due.sort();
+        due.truncate(limit.max(0) as usize);
+
+        let locked_until = now + chrono::Duration::milliseconds(lease.as_millis() as i64);
+        Ok(due
+            .into_iter()
+            .filter_map(|(_, id)| {
+                let job = data.job_mut(id)?;
+                job.state = JobState::Running.as_str().to_string();
+                job.attempts += 1;
+                job.locked_until = Some(locked_until);
+                job.updated_at = now;
+                Some(job.clone())
+            })
+            .collect())
+    }
+
+    async fn complete(&self, id: i64, attempt: i32) -> Result<bool, AppError> {
+        let mut data = self.data();
+        if data.leased_job_mut(id, attempt).is_none() {
+            return Ok(false);
+        }
+        data.jobs.retain(|job| job.id != id);
+        Ok(true)
+    }
+
+    async fn retry(
+        &self,
+        id: i64,
+        attempt: i32,
+        error: &str,
+        run_at: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let mut data = self.data();
+        let Some(job) = data.leased_job_mut(id, attempt) else {
+            return Ok(false);
+        };
+        job.state = JobState::Queued.as_str().to_string();
+        job.last_error = Some(error.to_string());
+        job.run_at = run_at;
+        job.locked_until = None;
+        job.updated_at = Utc::now();
+        Ok(true)
+    }
+
+    async fn dead_letter(&self, id: i64, attempt: i32, error: &str) -> Result<bool, AppError> {
+        let mut data = self.data();
+        let Some(job) = data.leased_job_mut(id, attempt) else {
+            return Ok(false);
+        };
+        job.state = JobState::Dead.as_str().to_string();
+        job.last_error = Some(error.to_string());
+        job.locked_until = None;
+        job.updated_at = Utc::now();
+        Ok(true)
+    }
+
+    async fn list(&self, state: JobState, limit: i64) -> Result<Vec<JobModel>, AppError> {
+        let data = self.data();
+        let mut jobs = data
+            .jobs
+            .iter()
+            .filter(|job| job.state == state.as_str())
+            .cloned()
+            .collect::<Vec<_>>();
+        jobs.sort_by_key(|job| (job.run_at, job.id));
+        jobs.truncate(limit.max(0) as usize);
+
+        Ok(jobs)
+    }
+
+    async fn requeue(&self, id: i64) -> Result<Option<JobModel>, AppError> {
+        let mut data = self.data();
+        let Some(unique_key) = data
+            .job_mut(id)
+            .filter(|job| job.state == JobState::Dead.as_str())
+            .map(|job| job.unique_key.clone())
+        else {
+            return Ok(None);
+        };
+        // mirrors the partial unique index on `jobs.unique_key`
+        let taken = unique_key.is_some()
+            && data.jobs.iter().any(|j| {
+                j.id != id && j.unique_key == unique_key && j.state != JobState::Dead.as_str()
+            });
+        if taken {
+            return Err(AppError::JobAlreadyQueued);
+        }
+
+        let job = data.job_mut(id).expect("found above");
+
+        let now = Utc::now();
+        job.state = JobState::Queued.as_str().to_string();
+        job.attempts = 0;
+        job.run_at = now;
+        job.updated_at = now;
+        Ok(Some(job.clone()))
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepository for MemoryRepository {
+    async fn begin(
+        &self,
+        user_id: i32,
+        key: &str,
+        fingerprint: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyKeyModel>, AppError> {
+        let mut data = self.data();
+        let now = Utc::now();
+        data.idempotency_keys.retain(|record| {
+            !(record.user_id == user_id && record.key == key && record.expires_at <= now)
+        });
+
+        if let Some(existing) = data
+            .idempotency_keys
+            .iter()
+            .find(|record| record.user_id == user_id && record.key == key)
+        {
+            return Ok(Some(existing.clone()));
+        }
+
+        data.idempotency_keys.push(IdempotencyKeyModel {
+            user_id,
+            key: key.to_string(),
+            fingerprint: fingerprint.to_string(),
+            response_status: None,
+            response_headers: None,
+            response_body: None,
+            created_at: now,
+            expires_at,
+        });
+        Ok(None)
+    }
+
+    async fn finish(
+        &self,
+        user_id: i32,
+        key: &str,
+        response: &StoredResponse,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let mut data = self.data();
+        if let Some(record) = data.idempotency_keys.iter_mut().find(|record| {
+            record.user_id == user_id && record.key == key && record.response_status.is_none()
+        }) {
+            record.response_status = Some(i32::from(response.status));
+            record.response_headers = Some(Json(response.headers.clone()));
+            record.response_body = Some(response.body.clone());
+            record.expires_at = expires_at;
+        }
+        Ok(())
+    }
+
+    async fn release(&self, user_id: i32, key: &str) -> Result<(), AppError> {
+        self.data().idempotency_keys.retain(|record| {
+            !(record.user_id == user_id && record.key == key && record.response_status.is_none())
+        });
+        Ok(())
+    }
+
+    async fn prune(&self) -> Result<(), AppError> {
+        let now = Utc::now();
+        self.data()
+            .idempotency_keys
+            .retain(|record| record.expires_at > now);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CspReportRepository for MemoryRepository {
+    async fn create(&self, report: NewCspReport) -> Result<CspReportModel, AppError> {
+        let mut data = self.data();
+        data.next_csp_report_id += 1;
+        let report = CspReportModel {
+            id: data.next_csp_report_id,
+            document_uri: report.document_uri,
+            violated_directive: report.violated_directive,
+            blocked_uri: report.blocked_uri,
+            source_file: report.source_file,
+            line_number: report.line_number,
+            disposition: report.disposition,
+            user_agent: report.user_agent,
+            created_at: Utc::now(),
+        };
+        data.csp_reports.push(report.clone());
+        Ok(report)
+    }
+
+    async fn list(&self, limit: i64) -> Result<Vec<CspReportModel>, AppError> {
+        Ok(self
+            .data()
+            .csp_reports
+            .iter()
+            .rev()
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+// the `WHERE` clause of the sql backends
+fn matches(filter: &AuditFilter, entry: &AuditLogModel) -> bool {
+    filter.actor_id.is_none_or(|id| entry.actor_id == Some(id))
+        && filter.action.as_ref().is_none_or(|a| *a == entry.action)
+        && filter
+            .target_type
+            .as_ref()
+            .is_none_or(|t| *t == entry.target_type)
+        && filter
+            .target_id
+            .as_ref()
+            .is_none_or(|t| *t == entry.target_id)
+        && filter.since.is_none_or(|since| entry.created_at >= since)
+        && filter.until.is_none_or(|until| entry.created_at < until)
+}
+
+#[async_trait]
+impl AuditRepository for MemoryRepository {
+    async fn record(&self, entry: NewAuditEntry) -> Result<AuditLogModel, AppError> {
+        Ok(self.data().insert_audit_entry(entry))
+    }
+
+    async fn list(
+        &self,
+        filter: &AuditFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AuditLogModel>, AppError> {
+        Ok(self
+            .data()
+            .audit_log
+            .iter()
+            .rev()
+            .filter(|entry| matches(filter, entry))
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl FeatureFlagRepository for MemoryRepository {
+    async fn list(&self) -> Result<Vec<FeatureFlagModel>, AppError> {
+        Ok(self.data().feature_flags.clone())
+    }
+
+    async fn list_overrides(&self) -> Result<Vec<FeatureFlagOverrideModel>, AppError> {
+        Ok(self.data().feature_flag_overrides.clone())
+    }
+
+    async fn upsert(
+        &self,
+        name: &str,
+        flag: FeatureFlagData,
+        actor: &Actor,
+    ) -> Result<FeatureFlagModel, AppError> {
+        let mut data = self.data();
+        let now = Utc::now();
+        let (index, before) = match data
+            .feature_flags
+            .binary_search_by(|f| f.name.as_str().cmp(name))
+        {
+            Ok(index) => (index, Some(data.feature_flags[index].clone())),
+            Err(index) => {
+                data.feature_flags.insert(
+                    index,
+                    FeatureFlagModel {
+                        name: name.to_string(),
+                        description: String::new(),
+                        enabled: false,
+                        rollout_percentage: 100,
+                        created_at: now,
+                        updated_at: now,
+                    },
+                );
+                (index, None)
+            }
+        };
+
+        let existing = &mut data.feature_flags[index];
+        if let Some(description) = flag.description {
+            existing.description = description;
+        }
+        if let Some(enabled) = flag.enabled {
+            existing.enabled = enabled;
+        }
+        if let Some(rollout_percentage) = flag.rollout_percentage {
+            existing.rollout_percentage = rollout_percentage;
+        }
+        existing.updated_at = now;
+        let flag = existing.clone();
+
+        data.insert_audit_entry(NewAuditEntry::snapshot(
+            actor,
+            AuditAction::FeatureFlagUpsert,
+            name,
+            before.as_ref(),
+            Some(&flag),
+        ));
+        Ok(flag)
+    }
+
+    async fn delete(
+        &self,
+        name: &str,
+        actor: &Actor,
+    ) -> Result<Option<FeatureFlagModel>, AppError> {
+        let mut data = self.data();
+        let Some(index) = data.feature_flags.iter().position(|f| f.name == name) else {
+            return Ok(None);
+        };
+
+        data.feature_flag_overrides.retain(|o| o.flag_name != name);
+        let flag = data.feature_flags.remove(index);
+        data.insert_audit_entry(NewAuditEntry::snapshot(
+            actor,
+            AuditAction::FeatureFlagDelete,
+            name,
+            Some(&flag),
+            None,
+        ));
+        Ok(Some(flag))
+    }
+
+    async fn set_override(
+        &self,
+        name: &str,
+        user_id: i32,
+        enabled: bool,
+        actor: &Actor,
+    ) -> Result<FeatureFlagOverrideModel, AppError> {
+        let mut data = self.data();
+        if !data.feature_flags.iter().any(|f| f.name == name) {
+            return Err(AppError::FeatureFlagNotFound(name.to_string()));
+        }
+        if !data.users.iter().any(|u| u.id == user_id) {
+            return Err(AppError::FeatureFlagUserNotFound);
+        }
+
+        let position = data
+            .feature_flag_overrides
+            .binary_search_by(|o| (o.flag_name.as_str(), o.user_id).cmp(&(name, user_id)));
+        let (before, flag_override) = match position {
+            Ok(index) => {
+                let existing = &mut data.feature_flag_overrides[index];
+                let before = existing.clone();
+                existing.enabled = enabled;
+                (Some(before), existing.clone())
+            }
+            Err(index) => {
+                let flag_override = FeatureFlagOverrideModel {
+                    flag_name: name.to_string(),
+                    user_id,
+                    enabled,
+                    created_at: Utc::now(),
+                };
+                data.feature_flag_overrides
+                    .insert(index, flag_override.clone());
+                (None, flag_override)
+            }
+        };
+
+        data.insert_audit_entry(NewAuditEntry::snapshot(
+            actor,
+            AuditAction::FeatureFlagOverrideSet,
+            name,
+            before.as_ref(),
+            Some(&flag_override),
+        ));
+        Ok(flag_override)
+    }
+
+    async fn delete_override(
+        &self,
+        name: &str,
+        user_id: i32,
+        actor: &Actor,
+    ) -> Result<Option<FeatureFlagOverrideModel>, AppError> {
+        let mut data = self.data();
+        let Some(index) = data
+            .feature_flag_overrides
+            .iter()
+            .position(|o| o.flag_name == name && o.user_id == user_id)
+        else {
+            return Ok(None);
+        };
+
+        let flag_override = data.feature_flag_overrides.remove(index);
+        data.insert_audit_entry(NewAuditEntry::snapshot(
+            actor,
+            AuditAction::FeatureFlagOverrideDelete,
+            name,
+            Some(&flag_override),
+            None,
+        ));
+        Ok(Some(flag_override))
+    }
+}
+
+#[async_trait]
+impl DatabaseHealth for MemoryRepository {
+    async fn ping(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    async fn pending_migrations(&self) -> Result<Vec<String>, AppError> {
+        Ok(Vec::new())
+    }
+
+    fn pool_status(&self) -> Option<PoolStatus> {
+        None
+    }
+}
@@ -0,0 +1,492 @@
+pub mod backend;
+pub mod memory;
+pub mod postgres;
+pub mod sqlite;
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    error::AppError,
+    jobs::Job,
+    model::{
+        AuditAction, AuditLogModel, CategoryModel, CspReportModel, FeatureFlagModel,
+        FeatureFlagOverrideModel, IdempotencyKeyModel, JobModel, JobState, PostModel, PostStatus,
+        UserModel,
+    },
+    schema::FetchAllPostSchema,
+};
+
+pub use backend::Backend;
+pub use memory::MemoryRepository;
+pub use postgres::PgRepository;
+pub use sqlite::SqliteRepository;
+
+// the writable fields of a post, used for both create and update
+#[derive(Debug, Clone)]
+pub struct PostData {
+    pub title: String,
+    pub slug: String,
+    pub excerpt: String,
+    pub content: String,
+    pub category_id: Option<i32>,
+}
+
+// the filters of `PostRepository::list`; `None` matches everything
+#[derive(Debug, Clone, Default)]
+pub struct PostFilter {
+    pub user_id: Option<i32>,
+    pub status: Option<PostStatus>,
+}
+
+impl PostFilter {
+    // what anyone may see
+    pub fn published() -> Self {
+        Self {
+            status: Some(PostStatus::Published),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewUser {
+    pub username: String,
+    pub email: String,
+    // already hashed
+    pub password: String,
+    pub is_admin: bool,
+}
+
+// a job to enqueue, built from a typed `Job` with `NewJob::new`
+#[derive(Debug, Clone)]
+pub struct NewJob {
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub max_attempts: i32,
+    // now when `None`
+    pub run_at: Option<DateTime<Utc>>,
+    // at most one queued or running job per key
+    pub unique_key: Option<String>,
+}
+
+impl NewJob {
+    pub fn new<J: Job>(job: &J) -> Result<Self, AppError> {
+        let payload = serde_json::to_value(job).map_err(|e| {
+            AppError::Internal(format!("failed to serialize a {} job: {e}", J::KIND))
+        })?;
+
+        Ok(Self {
+            kind: J::KIND.to_string(),
+            payload,
+            max_attempts: J::MAX_ATTEMPTS,
+            run_at: None,
+            unique_key: None,
+        })
+    }
+
+    pub fn run_at(mut self, run_at: DateTime<Utc>) -> Self {
+        self.run_at = Some(run_at);
+        self
+    }
+
+    pub fn unique_key(mut self, unique_key: impl Into<String>) -> Self {
+        self.unique_key = Some(unique_key.into());
+        self
+    }
+}
+
+// who performed an action, recorded with each entry of the audit log; built from the request by
+// its `FromRequestParts` impl in `audit`. Local tools name themselves with `Actor::tool`;
+// `Actor::default()` is an unknown caller, like the seed data loaded at startup.
+#[derive(Debug, Clone, Default)]
+pub struct Actor {
+    pub user_id: Option<i32>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl Actor {
+    pub fn tool(name: &str) -> Self {
+        Self {
+            user_agent: Some(name.to_string()),
+            ..Self::default()
+        }
+    }
+}
+
+// an entry of the audit log; the backends build these inside the transaction of the write so the
+// snapshots are exactly what was written
+#[derive(Debug, Clone)]
+pub struct NewAuditEntry {
+    pub actor: Actor,
+    pub action: AuditAction,
+    pub target_id: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+impl NewAuditEntry {
+    pub fn new(actor: &Actor, action: AuditAction, target_id: impl ToString) -> Self {
+        Self {
+            actor: actor.clone(),
+            action,
+            target_id: target_id.to_string(),
+            before: None,
+            after: None,
+        }
+    }
+
+    pub fn post(
+        actor: &Actor,
+        action: AuditAction,
+        before: Option<&PostModel>,
+        after: Option<&PostModel>,
+    ) -> Self {
+        let target_id = before.or(after).map(|post| post.id).unwrap_or_default();
+        Self::snapshot(actor, action, target_id, before, after)
+    }
+
+    pub fn snapshot<T: Serialize>(
+        actor: &Actor,
+        action: AuditAction,
+        target_id: impl ToString,
+        before: Option<&T>,
+        after: Option<&T>,
+    ) -> Self {
+        Self {
+            before: before.and_then(|target| serde_json::to_value(target).ok()),
+            after: after.and_then(|target| serde_json::to_value(target).ok()),
+            ..Self::new(actor, action, target_id)
+        }
+    }
+
+    // the password hash is left out of the snapshot
+    pub fn user(actor: &Actor, action: AuditAction, user: &UserModel) -> Self {
+        let mut after = serde_json::to_value(user).ok();
+        if let Some(serde_json::Value::Object(fields)) = &mut after {
+            fields.remove("password");
+        }
+        Self {
+            after,
+            ..Self::new(actor, action, user.id)
+        }
+    }
+}
+
+// the filters of `AuditRepository::list`; `None` matches everything
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub actor_id: Option<i32>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    // inclusive
+    pub since: Option<DateTime<Utc>>,
+    // exclusive
+    pub until: Option<DateTime<Utc>>,
+}
+
+// unique and foreign key violations are reported as the matching `AppError` variant
+// (e.g. `PostSlugTaken`) by every implementation, so handlers don't need to know the backend
+#[async_trait]
+pub trait PostRepository: Send + Sync {
+    // posts that were never published first, newest first, then the rest by `published_at`, most
+    // recent first; the public list only holds published posts, so there it's just the latter
+    async fn list(
+        &self,
+        filter: &PostFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<FetchAllPostSchema>, AppError>;
+    async fn find_by_slug(&self, slug: &str) -> Result<Option<PostModel>, AppError>;
+    // the writes enqueue `jobs` in their transaction, so the jobs exist if and only if the change
+    // was made; a post that isn't found enqueues nothing. `create`, `update` and `delete` also
+    // write their audit log entry in that transaction.
+    async fn create(
+        &self,
+        user_id: i32,
+        post: PostData,
+        status: PostStatus,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<PostModel, AppError>;
+    async fn update(
+        &self,
+        slug: &str,
+        post: PostData,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError>;
+    // `published_at` is set the first time the post is published. Writes a `post.publish`,
+    // `post.unpublish` or `post.archive` audit log entry in the same transaction.
+    async fn set_status(
+        &self,
+        slug: &str,
+        status: PostStatus,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError>;
+    // moves the post to another author; writes a `post.reassign` audit log entry in the same
+    // transaction
+    async fn reassign(
+        &self,
+        slug: &str,
+        user_id: i32,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError>;
+    async fn delete(
+        &self,
+        slug: &str,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError>;
+}
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn find_by_email(&self, email: &str) -> Result<Option<UserModel>, AppError>;
+    async fn find_by_username(&self, username: &str) -> Result<Option<UserModel>, AppError>;
+    async fn exists(&self, email: &str, username: &str) -> Result<bool, AppError>;
+    // writes a `user.register` audit log entry in the same transaction
+    async fn create(&self, user: NewUser, actor: &Actor) -> Result<UserModel, AppError>;
+    // writes a `user.password_reset` audit log entry in the same transaction
+    async fn set_password(
+        &self,
+        email: &str,
+        password: &str,
+        actor: &Actor,
+    ) -> Result<Option<UserModel>, AppError>;
+}
+
+#[async_trait]
+pub trait CategoryRepository: Send + Sync {
+    async fn list(&self) -> Result<Vec<CategoryModel>, AppError>;
+    async fn find_by_id(&self, id: i32) -> Result<Option<CategoryModel>, AppError>;
+    // writes a `category.create` audit log entry in the same transaction
+    async fn create(&self, name: &str, actor: &Actor) -> Result<CategoryModel, AppError>;
+}
+
+// the durable queue consumed by the workers in `jobs`
+#[async_trait]
+pub trait JobRepository: Send + Sync {
+    // `None` if a queued or running job has the same `unique_key`
+    async fn enqueue(&self, job: NewJob) -> Result<Option<JobModel>, AppError>;
+    // locks up to `limit` due jobs for `lease` and counts an attempt; jobs whose lease ran out
+    // (their worker died) are due again. Concurrent callers never get the same job.
+    async fn claim(&self, limit: i64, lease: Duration) -> Result<Vec<JobModel>, AppError>;
+    // `complete`, `retry` and `dead_letter` take the `attempts` of the claimed job, which names
+    // its lease: they change nothing and return `false` once the lease is lost, i.e. the job ran
+    // past it and was claimed again
+    //
+    // deletes the job
+    async fn complete(&self, id: i64, attempt: i32) -> Result<bool, AppError>;
+    // queues the job again until `run_at`
+    async fn retry(
+        &self,
+        id: i64,
+        attempt: i32,
+        error: &str,
+        run_at: DateTime<Utc>,
+    ) -> Result<bool, AppError>;
+    async fn dead_letter(&self, id: i64, attempt: i32, error: &str) -> Result<bool, AppError>;
+    // oldest first
+    async fn list(&self, state: JobState, limit: i64) -> Result<Vec<JobModel>, AppError>;
+    // queues a dead job again with fresh attempts; `None` if there is no dead job with the id,
+    // `JobAlreadyQueued` if a queued or running job has the same `unique_key`
+    async fn requeue(&self, id: i64) -> Result<Option<JobModel>, AppError>;
+}
+
+// the response to a request sent with an `Idempotency-Key`, replayed on retries
+#[derive(Debug, Clone)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+// the keys of `idempotency`, scoped to a user
+#[async_trait]
+pub trait IdempotencyRepository: Send + Sync {
+    // reserves the key for a request with `fingerprint` until `expires_at`, replacing an expired
+    // record; returns the record holding the key instead if it is taken
+    async fn begin(
+        &self,
+        user_id: i32,
+        key: &str,
+        fingerprint: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyKeyModel>, AppError>;
+    // stores the response of the request that reserved the key and keeps it until `expires_at`
+    async fn finish(
+        &self,
+        user_id: i32,
+        key: &str,
+        response: &StoredResponse,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError>;
+    // frees a key that has no response yet, e.g. after a server error, so a retry runs the
+    // request again
+    async fn release(&self, user_id: i32, key: &str) -> Result<(), AppError>;
+    // deletes the expired keys
+    async fn prune(&self) -> Result<(), AppError>;
+}
+
+// a violation reported to `/csp-report`, see `handlers::csp`
+#[derive(Debug, Clone)]
+pub struct NewCspReport {
+    pub document_uri: String,
+    pub violated_directive: String,
+    pub blocked_uri: Option<String>,
+    pub source_file: Option<String>,
+    pub line_number: Option<i64>,
+    pub disposition: String,
+    pub user_agent: Option<String>,
+}
+
+#[async_trait]
+pub trait CspReportRepository: Send + Sync {
+    async fn create(&self, report: NewCspReport) -> Result<CspReportModel, AppError>;
+    // newest first
+    async fn list(&self, limit: i64) -> Result<Vec<CspReportModel>, AppError>;
+}
+
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    // for actions that write nothing else, like logins; the writes of the other repositories
+    // record their own entries
+    async fn record(&self, entry: NewAuditEntry) -> Result<AuditLogModel, AppError>;
+    // newest first
+    async fn list(
+        &self,
+        filter: &AuditFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AuditLogModel>, AppError>;
+}
+
+// the settings of a flag to write; `None` keeps the current value, or the column default when
+// the flag is created
+#[derive(Debug, Clone, Default)]
+pub struct FeatureFlagData {
+    pub description: Option<String>,
+    pub enabled: Option<bool>,
+    pub rollout_percentage: Option<i32>,
+}
+
+// read in full by `feature_flags::FeatureFlagCache`; the writes don't touch the cache. Each write
+// records its `feature_flag.*` audit log entry in the same transaction, nothing if nothing changed.
+#[async_trait]
+pub trait FeatureFlagRepository: Send + Sync {
+    // by name
+    async fn list(&self) -> Result<Vec<FeatureFlagModel>, AppError>;
+    // by flag name and user id
+    async fn list_overrides(&self) -> Result<Vec<FeatureFlagOverrideModel>, AppError>;
+    // creates the flag or changes the given settings
+    async fn upsert(
+        &self,
+        name: &str,
+        flag: FeatureFlagData,
+        actor: &Actor,
+    ) -> Result<FeatureFlagModel, AppError>;
+    // deletes the overrides with the flag
+    async fn delete(&self, name: &str, actor: &Actor)
+        -> Result<Option<FeatureFlagModel>, AppError>;
+    // `FeatureFlagNotFound` or `FeatureFlagUserNotFound` if either doesn't exist
+    async fn set_override(
+        &self,
+        name: &str,
+        user_id: i32,
+        enabled: bool,
+        actor: &Actor,
+    ) -> Result<FeatureFlagOverrideModel, AppError>;
+    async fn delete_override(
+        &self,
+        name: &str,
+        user_id: i32,
+        actor: &Actor,
+    ) -> Result<Option<FeatureFlagOverrideModel>, AppError>;
+}
+
+// used by `backup`: the export reads every table from one snapshot, the import writes them all in
+// one transaction
+#[async_trait]
+pub trait BackupRepository: Send + Sync {
+    async fn begin_export(&self) -> Result<Box<dyn BackupExport>, AppError>;
+    async fn begin_import(&self) -> Result<Box<dyn BackupImport>, AppError>;
+}
+
+// full rows by id; every call reads the same snapshot, taken by the first one at the latest, so
+// rows written in the meantime are not seen
+#[async_trait]
+pub trait BackupExport: Send {
+    // users with an id greater than `after_id`
+    async fn users(&mut self, after_id: i32, limit: i64) -> Result<Vec<UserModel>, AppError>;
+    async fn categories(&mut self) -> Result<Vec<CategoryModel>, AppError>;
+    // posts with an id greater than `after_id`
+    async fn posts(&mut self, after_id: i32, limit: i64) -> Result<Vec<PostModel>, AppError>;
+}
+
+// rows are inserted under new ids, keeping their timestamps, password hashes and post statuses.
+// Nothing is visible before `commit`; dropping the import rolls every row back.
+#[async_trait]
+pub trait BackupImport: Send {
+    // no users, categories or posts
+    async fn is_empty(&mut self) -> Result<bool, AppError>;
+    async fn user(&mut self, user: &UserModel) -> Result<UserModel, AppError>;
+    async fn category(&mut self, name: &str) -> Result<CategoryModel, AppError>;
+    async fn post(&mut self, post: &PostModel) -> Result<PostModel, AppError>;
+    async fn commit(self: Box<Self>) -> Result<(), AppError>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PoolStatus {
+    pub size: u32,
+    pub idle: u32,
+    pub max: u32,
+}
+
+// used by the readiness probe
+#[async_trait]
+pub trait DatabaseHealth: Send + Sync {
+    // a real round-trip to the database
+    async fn ping(&self) -> Result<(), AppError>;
+    // descriptions of the embedded migrations that have not been applied successfully
+    async fn pending_migrations(&self) -> Result<Vec<String>, AppError>;
+    // `None` for backends without a connection pool
+    fn pool_status(&self) -> Option<PoolStatus>;
+}
+
+// convenience bound for backends that implement every repository
+pub trait Repository:
+    PostRepository
+    + UserRepository
+    + CategoryRepository
+    + JobRepository
+    + IdempotencyRepository
+    + CspReportRepository
+    + AuditRepository
+    + FeatureFlagRepository
+    + BackupRepository
+    + DatabaseHealth
+    + 'static
+{
+}
+
+impl<T> Repository for T where
+    T: PostRepository
+        + UserRepository
+        + CategoryRepository
+        + JobRepository
+        + IdempotencyRepository
+        + CspReportRepository
+        + AuditRepository
+        + FeatureFlagRepository
+        + BackupRepository
+        + DatabaseHealth
+        + 'static
+{
+}
@@ -0,0 +1,1113 @@
+use std::{collections::HashSet, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{migrate::Migrator, types::Json, PgExecutor, PgPool, Postgres, Transaction};
+
+use crate::{
+    error::AppError,
+    model::{
+        AuditAction, AuditLogModel, CategoryModel, CspReportModel, FeatureFlagModel,
+        FeatureFlagOverrideModel, IdempotencyKeyModel, JobModel, JobState, PostModel, PostStatus,
+        UserModel,
+    },
+    schema::FetchAllPostSchema,
+};
+
+use super::{
+    Actor, AuditFilter, AuditRepository, BackupExport, BackupImport, BackupRepository,
+    CategoryRepository, CspReportRepository, DatabaseHealth, FeatureFlagData,
+    FeatureFlagRepository, IdempotencyRepository, JobRepository, NewAuditEntry, NewCspReport,
+    NewJob, NewUser, PoolStatus, PostData, PostFilter, PostRepository, StoredResponse,
+    UserRepository,
+};
+
+// the migrations in `./migrations`, embedded at compile time
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+#[derive(Clone)]
+pub struct PgRepository {
+    pool: PgPool,
+}
+
+impl PgRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl PostRepository for PgRepository {
+    async fn list(
+        &self,
+        filter: &PostFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<FetchAllPostSchema>, AppError> {
+        let posts = sqlx::query_as!(
+            FetchAllPostSchema,
+            r#"
+            SELECT id, title, slug, user_id, excerpt, category_id, created_at, updated_at,
+                status AS "status: PostStatus", published_at
+            FROM post
+            WHERE ($1::INT IS NULL OR user_id = $1) AND ($2::TEXT IS NULL OR status = $2)
+            ORDER BY published_at DESC NULLS FIRST, created_at DESC, id DESC
+            LIMIT $3 OFFSET $4
+            "#,
+            filter.user_id,
+            filter.status.map(PostStatus::as_str),
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(posts)
+    }
+
+    async fn find_by_slug(&self, slug: &str) -> Result<Option<PostModel>, AppError> {
+        let post = sqlx::query_as!(
+            PostModel,
+            r#"
+            SELECT id, title, slug, user_id, excerpt, content, category_id, created_at, updated_at,
+                status AS "status: PostStatus", published_at
+            FROM post
+            WHERE slug = $1
+            "#,
+            slug
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(post)
+    }
+
+    async fn create(
+        &self,
+        user_id: i32,
+        post: PostData,
+        status: PostStatus,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<PostModel, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let post = sqlx::query_as!(
+            PostModel,
+            r#"
+            INSERT INTO post
+                (title, slug, excerpt, content, category_id, user_id, status, published_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7::VARCHAR, CASE WHEN $7 = 'published' THEN NOW() END)
+            RETURNING id, title, slug, user_id, excerpt, content, category_id, created_at,
+                updated_at, status AS "status: PostStatus", published_at
+            "#,
+            post.title,
+            post.slug,
+            post.excerpt,
+            post.content,
+            post.category_id,
+            user_id,
+            status.as_str()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        for job in &jobs {
+            insert_job(&mut *tx, job).await?;
+        }
+        let entry = NewAuditEntry::post(actor, AuditAction::PostCreate, None, Some(&post));
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(post)
+    }
+
+    async fn update(
+        &self,
+        slug: &str,
+        post: PostData,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let before = sqlx::query_as!(
+            PostModel,
+            r#"
+            SELECT id, title, slug, user_id, excerpt, content, category_id, created_at, updated_at,
+                status AS "status: PostStatus", published_at
+            FROM post WHERE slug = $1 FOR UPDATE
+            "#,
+            slug
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(before) = before else {
+            return Ok(None);
+        };
+
+        let post = sqlx::query_as!(
+            PostModel,
+            r#"
+            UPDATE post
+            SET title = $1, slug = $2, excerpt = $3, content = $4, category_id = $5,
+                updated_at = NOW()
+            WHERE id = $6
+            RETURNING id, title, slug, user_id, excerpt, content, category_id, created_at,
+                updated_at, status AS "status: PostStatus", published_at
+            "#,
+            post.title,
+            post.slug,
+            post.excerpt,
+            post.content,
+            post.category_id,
+            before.id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        for job in &jobs {
+            insert_job(&mut *tx, job).await?;
+        }
+        let entry = NewAuditEntry::post(actor, AuditAction::PostUpdate, Some(&before), Some(&post));
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(Some(post))
+    }
+
+    async fn set_status(
+        &self,
+        slug: &str,
+        status: PostStatus,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let before = sqlx::query_as!(
+            PostModel,
+            r#"
+            SELECT id, title, slug, user_id, excerpt, content, category_id, created_at, updated_at,
+                status AS "status: PostStatus", published_at
+            FROM post WHERE slug = $1 FOR UPDATE
+            "#,
+            slug
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(before) = before else {
+            return Ok(None);
+        };
+
+        let post = sqlx::query_as!(
+            PostModel,
+            r#"
+            UPDATE post
+            SET status = $1::VARCHAR,
+                published_at = CASE WHEN $1 = 'published' THEN COALESCE(published_at, NOW())
+                    ELSE published_at END,
+                updated_at = NOW()
+            WHERE id = $2
+            RETURNING id, title, slug, user_id, excerpt, content, category_id, created_at,
+                updated_at, status AS "status: PostStatus", published_at
+            "#,
+            status.as_str(),
+            before.id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        for job in &jobs {
+            insert_job(&mut *tx, job).await?;
+        }
+        let entry = NewAuditEntry::post(actor, status.audit_action(), Some(&before), Some(&post));
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(Some(post))
+    }
+
+    async fn reassign(
+        &self,
+        slug: &str,
+        user_id: i32,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let before = sqlx::query_as!(
+            PostModel,
+            r#"
+            SELECT id, title, slug, user_id, excerpt, content, category_id, created_at, updated_at,
+                status AS "status: PostStatus", published_at
+            FROM post WHERE slug = $1 FOR UPDATE
+            "#,
+            slug
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(before) = before else {
+            return Ok(None);
+        };
+
+        let post = sqlx::query_as!(
+            PostModel,
+            r#"
+            UPDATE post
+            SET user_id = $1, updated_at = NOW()
+            WHERE id = $2
+            RETURNING id, title, slug, user_id, excerpt, content, category_id, created_at,
+                updated_at, status AS "status: PostStatus", published_at
+            "#,
+            user_id,
+            before.id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let entry =
+            NewAuditEntry::post(actor, AuditAction::PostReassign, Some(&before), Some(&post));
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(Some(post))
+    }
+
+    async fn delete(
+        &self,
+        slug: &str,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let post = sqlx::query_as!(
+            PostModel,
+            r#"
+            DELETE FROM post
+            WHERE slug = $1
+            RETURNING id, title, slug, user_id, excerpt, content, category_id, created_at,
+                updated_at, status AS "status: PostStatus", published_at
+            "#,
+            slug
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(post) = &post {
+            for job in &jobs {
+                insert_job(&mut *tx, job).await?;
+            }
+            let entry = NewAuditEntry::post(actor, AuditAction::PostDelete, Some(post), None);
+            insert_audit_entry(&mut *tx, &entry).await?;
+        }
+        tx.commit().await?;
+
+        Ok(post)
+    }
+}
+
+#[async_trait]
+impl UserRepository for PgRepository {
+    async fn find_by_email(&self, email: &str) -> Result<Option<UserModel>, AppError> {
+        let user = sqlx::query_as!(UserModel, "SELECT * FROM users WHERE email = $1", email)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(user)
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<UserModel>, AppError> {
+        let user = sqlx::query_as!(
+            UserModel,
+            "SELECT * FROM users WHERE username = $1",
+            username
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn exists(&self, email: &str, username: &str) -> Result<bool, AppError> {
+        let exists: Option<bool> = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM users WHERE email = $1 OR username = $2)",
+        )
+        .bind(email)
+        .bind(username)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(exists.unwrap_or(false))
+    }
+
+    async fn create(&self, user: NewUser, actor: &Actor) -> Result<UserModel, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let user = sqlx::query_as!(
+            UserModel,
+            "INSERT INTO users (username,email,password,is_admin) VALUES ($1, $2, $3, $4) RETURNING *",
+            user.username,
+            user.email,
+            user.password,
+            user.is_admin
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let entry = NewAuditEntry::user(actor, AuditAction::UserRegister, &user);
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn set_password(
+        &self,
+        email: &str,
+        password: &str,
+        actor: &Actor,
+    ) -> Result<Option<UserModel>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let user = sqlx::query_as!(
+            UserModel,
+            "UPDATE users SET password = $1, updated_at = NOW() WHERE email = $2 RETURNING *",
+            password,
+            email
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(user) = &user {
+            let entry = NewAuditEntry::user(actor, AuditAction::UserPasswordReset, user);
+            insert_audit_entry(&mut *tx, &entry).await?;
+        }
+        tx.commit().await?;
+
+        Ok(user)
+    }
+}
+
+#[async_trait]
+impl CategoryRepository for PgRepository {
+    async fn list(&self) -> Result<Vec<CategoryModel>, AppError> {
+        let categories = sqlx::query_as!(CategoryModel, "SELECT * FROM category ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(categories)
+    }
+
+    async fn find_by_id(&self, id: i32) -> Result<Option<CategoryModel>, AppError> {
+        let category = sqlx::query_as!(CategoryModel, "SELECT * FROM category WHERE id = $1", id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(category)
+    }
+
+    async fn create(&self, name: &str, actor: &Actor) -> Result<CategoryModel, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let category = sqlx::query_as!(
+            CategoryModel,
+            "INSERT INTO category (name) VALUES ($1) RETURNING *",
+            name
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let entry = NewAuditEntry::snapshot(
+            actor,
+            AuditAction::CategoryCreate,
+            category.id,
+            None,
+            Some(&category),
+        );
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(category)
+    }
+}
+
+#[async_trait]
+impl BackupRepository for PgRepository {
+    async fn begin_export(&self) -> Result<Box<dyn BackupExport>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        // one snapshot for every page of every table
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+            .execute(&mut *tx)
+            .await?;
+        Ok(Box::new(PgBackup { tx }))
+    }
+
+    async fn begin_import(&self) -> Result<Box<dyn BackupImport>, AppError> {
+        let tx = self.pool.begin().await?;
+        Ok(Box::new(PgBackup { tx }))
+    }
+}
+
+// the export and the import both run in a single transaction
+struct PgBackup {
+    tx: Transaction<'static, Postgres>,
+}
+
+#[async_trait]
+impl BackupExport for PgBackup {
+    async fn users(&mut self, after_id: i32, limit: i64) -> Result<Vec<UserModel>, AppError> {
+        let users = sqlx::query_as!(
+            UserModel,
+            "SELECT * FROM users WHERE id > $1 ORDER BY id LIMIT $2",
+            after_id,
+            limit
+        )
+        .fetch_all(&mut *self.tx)
+        .await?;
+
+        Ok(users)
+    }
+
+    async fn categories(&mut self) -> Result<Vec<CategoryModel>, AppError> {
+        let categories = sqlx::query_as!(CategoryModel, "SELECT * FROM category ORDER BY id")
+            .fetch_all(&mut *self.tx)
+            .await?;
+
+        Ok(categories)
+    }
+
+    async fn posts(&mut self, after_id: i32, limit: i64) -> Result<Vec<PostModel>, AppError> {
+        let posts = sqlx::query_as!(
+            PostModel,
+            r#"
+            SELECT id, title, slug, user_id, excerpt, content, category_id, created_at, updated_at,
+                status AS "status: PostStatus", published_at
+            FROM post WHERE id > $1 ORDER BY id LIMIT $2
+            "#,
+            after_id,
+            limit
+        )
+        .fetch_all(&mut *self.tx)
+        .await?;
+
+        Ok(posts)
+    }
+}
+
+#[async_trait]
+impl BackupImport for PgBackup {
+    async fn is_empty(&mut self) -> Result<bool, AppError> {
+        let has_rows: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM users) OR EXISTS (SELECT 1 FROM category)
+                OR EXISTS (SELECT 1 FROM post)
+            "#,
+        )
+        .fetch_one(&mut *self.tx)
+        .await?;
+
+        Ok(!has_rows)
+    }
+
+    async fn user(&mut self, user: &UserModel) -> Result<UserModel, AppError> {
+        let user = sqlx::query_as!(
+            UserModel,
+            r#"
+            INSERT INTO users (name, username, email, password, is_admin, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+            user.name,
+            user.username,
+            user.email,
+            user.password,
+            user.is_admin,
+            user.created_at,
+            user.updated_at
+        )
+        .fetch_one(&mut *self.tx)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn category(&mut self, name: &str) -> Result<CategoryModel, AppError> {
+        let category = sqlx::query_as!(
+            CategoryModel,
+            "INSERT INTO category (name) VALUES ($1) RETURNING *",
+            name
+        )
+        .fetch_one(&mut *self.tx)
+        .await?;
+
+        Ok(category)
+    }
+
+    async fn post(&mut self, post: &PostModel) -> Result<PostModel, AppError> {
+        let post = sqlx::query_as!(
+            PostModel,
+            r#"
+            INSERT INTO post (
+                title, slug, excerpt, content, category_id, user_id, created_at, updated_at,
+                status, published_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            RETURNING id, title, slug, user_id, excerpt, content, category_id, created_at,
+                updated_at, status AS "status: PostStatus", published_at
+            "#,
+            post.title,
+            post.slug,
+            post.excerpt,
+            post.content,
+            post.category_id,
+            post.user_id,
+            post.created_at,
+            post.updated_at,
+            post.status.as_str(),
+            post.published_at
+        )
+        .fetch_one(&mut *self.tx)
+        .await?;
+
+        Ok(post)
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), AppError> {
+        self.tx.commit().await?;
+        Ok(())
+    }
+}
+
+// shared by `JobRepository::enqueue` and the writes that enqueue jobs in their transaction
+async fn insert_job<'c>(
+    executor: impl PgExecutor<'c>,
+    job: &NewJob,
+) -> Result<Option<JobModel>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        INSERT INTO jobs (kind, payload, max_attempts, run_at, unique_key)
+        VALUES ($1, $2, $3, COALESCE($4, NOW()), $5)
+        ON CONFLICT (unique_key) WHERE state IN ('queued', 'running') DO NOTHING
+        RETURNING *
+        "#,
+    )
+    .bind(&job.kind)
+    .bind(&job.payload)
+    .bind(job.max_attempts)
+    .bind(job.run_at)
+    .bind(&job.unique_key)
+    .fetch_optional(executor)
+    .await
+}
+
+// the job queries are checked at runtime: `payload` needs a type override that `RETURNING *`
+// can't express in the `query_as!` macros
+#[async_trait]
+impl JobRepository for PgRepository {
+    async fn enqueue(&self, job: NewJob) -> Result<Option<JobModel>, AppError> {
+        Ok(insert_job(&self.pool, &job).await?)
+    }
+
+    async fn claim(&self, limit: i64, lease: Duration) -> Result<Vec<JobModel>, AppError> {
+        let jobs = sqlx::query_as(
+            r#"
+            UPDATE jobs
+            SET state = 'running', attempts = attempts + 1,
+                locked_until = NOW() + make_interval(secs => $2), updated_at = NOW()
+            WHERE id IN (
+                SELECT id FROM jobs
+                WHERE (state = 'queued' AND run_at <= NOW())
+                    OR (state = 'running' AND locked_until < NOW())
+                ORDER BY run_at, id
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *
+            "#,
+        )
+        .bind(limit)
+        .bind(lease.as_secs_f64())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(jobs)
+    }
+
+    async fn complete(&self, id: i64, attempt: i32) -> Result<bool, AppError> {
+        let result =
+            sqlx::query("DELETE FROM jobs WHERE id = $1 AND state = 'running' AND attempts = $2")
+                .bind(id)
+                .bind(attempt)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn retry(
+        &self,
+        id: i64,
+        attempt: i32,
+        error: &str,
+        run_at: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE jobs
+            SET state = 'queued', last_error = $3, run_at = $4, locked_until = NULL,
+                updated_at = NOW()
+            WHERE id = $1 AND state = 'running' AND attempts = $2
+            "#,
+        )
+        .bind(id)
+        .bind(attempt)
+        .bind(error)
+        .bind(run_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn dead_letter(&self, id: i64, attempt: i32, error: &str) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE jobs
+            SET state = 'dead', last_error = $3, locked_until = NULL, updated_at = NOW()
+            WHERE id = $1 AND state = 'running' AND attempts = $2
+            "#,
+        )
+        .bind(id)
+        .bind(attempt)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list(&self, state: JobState, limit: i64) -> Result<Vec<JobModel>, AppError> {
+        let jobs =
+            sqlx::query_as("SELECT * FROM jobs WHERE state = $1 ORDER BY run_at, id LIMIT $2")
+                .bind(state.as_str())
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(jobs)
+    }
+
+    async fn requeue(&self, id: i64) -> Result<Option<JobModel>, AppError> {
+        let job = sqlx::query_as(
+            r#"
+            UPDATE jobs
+            SET state = 'queued', attempts = 0, run_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND state = 'dead'
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(job)
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepository for PgRepository {
+    async fn begin(
+        &self,
+        user_id: i32,
+        key: &str,
+        fingerprint: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyKeyModel>, AppError> {
+        // the record holding the key can expire or be released between the two statements
+        for _ in 0..3 {
+            let reserved = sqlx::query(
+                r#"
+                INSERT INTO idempotency_key (user_id, key, fingerprint, expires_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (user_id, key) DO UPDATE
+                SET fingerprint = EXCLUDED.fingerprint, response_status = NULL,
+                    response_headers = NULL, response_body = NULL, created_at = NOW(),
+                    expires_at = EXCLUDED.expires_at
+                WHERE idempotency_key.expires_at <= NOW()
+                "#,
+            )
+            .bind(user_id)
+            .bind(key)
+            .bind(fingerprint)
+            .bind(expires_at)
+            .execute(&self.pool)
+            .await?
+            .rows_affected()
+                > 0;
+            if reserved {
+                return Ok(None);
+            }
+
+            let existing = sqlx::query_as(
+                "SELECT * FROM idempotency_key WHERE user_id = $1 AND key = $2 AND expires_at > NOW()",
+            )
+            .bind(user_id)
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
+            if existing.is_some() {
+                return Ok(existing);
+            }
+        }
+
+        Err(AppError::Internal(format!(
+            "could not reserve the idempotency key `{key}`"
+        )))
+    }
+
+    async fn finish(
+        &self,
+        user_id: i32,
+        key: &str,
+        response: &StoredResponse,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE idempotency_key
+            SET response_status = $3, response_headers = $4, response_body = $5, expires_at = $6
+            WHERE user_id = $1 AND key = $2 AND response_status IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(key)
+        .bind(i32::from(response.status))
+        .bind(Json(&response.headers))
+        .bind(&response.body)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn release(&self, user_id: i32, key: &str) -> Result<(), AppError> {
+        sqlx::query(
+            "DELETE FROM idempotency_key WHERE user_id = $1 AND key = $2 AND response_status IS NULL",
+        )
+            .bind(user_id)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn prune(&self) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM idempotency_key WHERE expires_at <= NOW()")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CspReportRepository for PgRepository {
+    async fn create(&self, report: NewCspReport) -> Result<CspReportModel, AppError> {
+        let report = sqlx::query_as(
+            r#"
+            INSERT INTO csp_report (document_uri, violated_directive, blocked_uri, source_file,
+                line_number, disposition, user_agent)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+        )
+        .bind(report.document_uri)
+        .bind(report.violated_directive)
+        .bind(report.blocked_uri)
+        .bind(report.source_file)
+        .bind(report.line_number)
+        .bind(report.disposition)
+        .bind(report.user_agent)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(report)
+    }
+
+    async fn list(&self, limit: i64) -> Result<Vec<CspReportModel>, AppError> {
+        let reports = sqlx::query_as("SELECT * FROM csp_report ORDER BY id DESC LIMIT $1")
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(reports)
+    }
+}
+
+// shared by `AuditRepository::record` and the writes that record their entry in their transaction
+async fn insert_audit_entry<'c>(
+    executor: impl PgExecutor<'c>,
+    entry: &NewAuditEntry,
+) -> Result<AuditLogModel, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        INSERT INTO audit_log
+            (actor_id, ip, user_agent, action, target_type, target_id, before, after)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING *
+        "#,
+    )
+    .bind(entry.actor.user_id)
+    .bind(&entry.actor.ip)
+    .bind(&entry.actor.user_agent)
+    .bind(entry.action.as_str())
+    .bind(entry.action.target_type())
+    .bind(&entry.target_id)
+    .bind(entry.before.as_ref().map(Json))
+    .bind(entry.after.as_ref().map(Json))
+    .fetch_one(executor)
+    .await
+}
+
+#[async_trait]
+impl AuditRepository for PgRepository {
+    async fn record(&self, entry: NewAuditEntry) -> Result<AuditLogModel, AppError> {
+        Ok(insert_audit_entry(&self.pool, &entry).await?)
+    }
+
+    async fn list(
+        &self,
+        filter: &AuditFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AuditLogModel>, AppError> {
+        let entries = sqlx::query_as(
+            r#"
+            SELECT * FROM audit_log
+            WHERE ($1::INT IS NULL OR actor_id = $1)
+                AND ($2::TEXT IS NULL OR action = $2)
+                AND ($3::TEXT IS NULL OR target_type = $3)
+                AND ($4::TEXT IS NULL OR target_id = $4)
+                AND ($5::TIMESTAMPTZ IS NULL OR created_at >= $5)
+                AND ($6::TIMESTAMPTZ IS NULL OR created_at < $6)
+            ORDER BY id DESC
+            LIMIT $7 OFFSET $8
+            "#,
+        )
+        .bind(filter.actor_id)
+        .bind(&filter.action)
+        .bind(&filter.target_type)
+        .bind(&filter.target_id)
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(entries)
+    }
+}
+
+#[async_trait]
+impl FeatureFlagRepository for PgRepository {
+    async fn list(&self) -> Result<Vec<FeatureFlagModel>, AppError> {
+        let flags = sqlx::query_as("SELECT * FROM feature_flags ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(flags)
+    }
+
+    async fn list_overrides(&self) -> Result<Vec<FeatureFlagOverrideModel>, AppError> {
+        let overrides =
+            sqlx::query_as("SELECT * FROM feature_flag_overrides ORDER BY flag_name, user_id")
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(overrides)
+    }
+
+    async fn upsert(
+        &self,
+        name: &str,
+        flag: FeatureFlagData,
+        actor: &Actor,
+    ) -> Result<FeatureFlagModel, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let before: Option<FeatureFlagModel> =
+            sqlx::query_as("SELECT * FROM feature_flags WHERE name = $1 FOR UPDATE")
+                .bind(name)
+                .fetch_optional(&mut *tx)
+                .await?;
+        let flag: FeatureFlagModel = sqlx::query_as(
+            r#"
+            INSERT INTO feature_flags (name, description, enabled, rollout_percentage)
+            VALUES ($1, COALESCE($2, ''), COALESCE($3, FALSE), COALESCE($4, 100))
+            ON CONFLICT (name) DO UPDATE SET
+                description = COALESCE($2, feature_flags.description),
+                enabled = COALESCE($3, feature_flags.enabled),
+                rollout_percentage = COALESCE($4, feature_flags.rollout_percentage),
+                updated_at = NOW()
+            RETURNING *
+            "#,
+        )
+        .bind(name)
+        .bind(flag.description)
+        .bind(flag.enabled)
+        .bind(flag.rollout_percentage)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let entry = NewAuditEntry::snapshot(
+            actor,
+            AuditAction::FeatureFlagUpsert,
+            name,
+            before.as_ref(),
+            Some(&flag),
+        );
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(flag)
+    }
+
+    async fn delete(
+        &self,
+        name: &str,
+        actor: &Actor,
+    ) -> Result<Option<FeatureFlagModel>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let flag: Option<FeatureFlagModel> =
+            sqlx::query_as("DELETE FROM feature_flags WHERE name = $1 RETURNING *")
+                .bind(name)
+                .fetch_optional(&mut *tx)
+                .await?;
+        let Some(flag) = flag else {
+            return Ok(None);
+        };
+
+        let entry = NewAuditEntry::snapshot(
+            actor,
+            AuditAction::FeatureFlagDelete,
+            name,
+            Some(&flag),
+            None,
+        );
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(Some(flag))
+    }
+
+    async fn set_override(
+        &self,
+        name: &str,
+        user_id: i32,
+        enabled: bool,
+        actor: &Actor,
+    ) -> Result<FeatureFlagOverrideModel, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let before: Option<FeatureFlagOverrideModel> = sqlx::query_as(
+            "SELECT * FROM feature_flag_overrides WHERE flag_name = $1 AND user_id = $2 FOR UPDATE",
+        )
+        .bind(name)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let flag_override: Option<FeatureFlagOverrideModel> = sqlx::query_as(
+            r#"
+            INSERT INTO feature_flag_overrides (flag_name, user_id, enabled)
+            SELECT name, $2, $3 FROM feature_flags WHERE name = $1
+            ON CONFLICT (flag_name, user_id) DO UPDATE SET enabled = EXCLUDED.enabled
+            RETURNING *
+            "#,
+        )
+        .bind(name)
+        .bind(user_id)
+        .bind(enabled)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let flag_override =
+            flag_override.ok_or_else(|| AppError::FeatureFlagNotFound(name.to_string()))?;
+
+        let entry = NewAuditEntry::snapshot(
+            actor,
+            AuditAction::FeatureFlagOverrideSet,
+            name,
+            before.as_ref(),
+            Some(&flag_override),
+        );
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(flag_override)
+    }
+
+    async fn delete_override(
+        &self,
+        name: &str,
+        user_id: i32,
+        actor: &Actor,
+    ) -> Result<Option<FeatureFlagOverrideModel>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let flag_override: Option<FeatureFlagOverrideModel> = sqlx::query_as(
+            "DELETE FROM feature_flag_overrides WHERE flag_name = $1 AND user_id = $2 RETURNING *",
+        )
+        .bind(name)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(flag_override) = flag_override else {
+            return Ok(None);
+        };
+
+        let entry = NewAuditEntry::snapshot(
+            actor,
+            AuditAction::FeatureFlagOverrideDelete,
+            name,
+            Some(&flag_override),
+            None,
+        );
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(Some(flag_override))
+    }
+}
+
+#[async_trait]
+impl DatabaseHealth for PgRepository {
+    async fn ping(&self) -> Result<(), AppError> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn pending_migrations(&self) -> Result<Vec<String>, AppError> {
+        let applied: Vec<i64> =
+            sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+                .fetch_all(&self.pool)
+                .await?;
+        let applied = applied.into_iter().collect::<HashSet<_>>();
+
+        Ok(MIGRATOR
+            .iter()
+            .filter(|migration| !applied.contains(&migration.version))
+            .map(|migration| format!("{}/{}", migration.version, migration.description))
+            .collect())
+    }
+
+    fn pool_status(&self) -> Option<PoolStatus> {
+        Some(PoolStatus {
+            size: self.pool.size(),
+            idle: self.pool.num_idle() as u32,
+            max: self.pool.options().get_max_connections(),
+        })
+    }
+}
@@ -0,0 +1,1143 @@
+use std::{collections::HashSet, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::{
+    error::ErrorKind, migrate::Migrator, types::Json, Sqlite, SqliteExecutor, SqlitePool,
+    Transaction,
+};
+
+use crate::{
+    error::AppError,
+    model::{
+        AuditAction, AuditLogModel, CategoryModel, CspReportModel, FeatureFlagModel,
+        FeatureFlagOverrideModel, IdempotencyKeyModel, JobModel, JobState, PostModel, PostStatus,
+        UserModel,
+    },
+    schema::FetchAllPostSchema,
+};
+
+use super::{
+    Actor, AuditFilter, AuditRepository, BackupExport, BackupImport, BackupRepository,
+    CategoryRepository, CspReportRepository, DatabaseHealth, FeatureFlagData,
+    FeatureFlagRepository, IdempotencyRepository, JobRepository, NewAuditEntry, NewCspReport,
+    NewJob, NewUser, PoolStatus, PostData, PostFilter, PostRepository, StoredResponse,
+    UserRepository,
+};
+
+// the migrations in `./migrations/sqlite`, embedded at compile time
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+// the queries are checked at runtime only: the `query_as!` macros are verified against the
+// postgres database in `DATABASE_URL`
+#[derive(Clone)]
+pub struct SqliteRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    // sqlite doesn't say which foreign key failed; a post only has two, so check the category
+    async fn post_write_error(&self, e: sqlx::Error, category_id: Option<i32>) -> AppError {
+        let is_fk_violation = e
+            .as_database_error()
+            .is_some_and(|db_error| db_error.kind() == ErrorKind::ForeignKeyViolation);
+        if !is_fk_violation {
+            return e.into();
+        }
+
+        match category_id {
+            Some(id) if matches!(self.find_by_id(id).await, Ok(None)) => AppError::CategoryNotFound,
+            _ => AppError::TokenUserNotFound,
+        }
+    }
+}
+
+// `fetch_one` and `fetch_optional` stop stepping a `RETURNING` statement after the first row, which
+// leaves sqlite's implicit transaction open until the connection runs another query; writes are
+// therefore fetched in full and the row picked here
+fn first<T>(rows: Vec<T>) -> Result<T, sqlx::Error> {
+    rows.into_iter().next().ok_or(sqlx::Error::RowNotFound)
+}
+
+#[async_trait]
+impl PostRepository for SqliteRepository {
+    async fn list(
+        &self,
+        filter: &PostFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<FetchAllPostSchema>, AppError> {
+        let posts = sqlx::query_as(
+            r#"
+            SELECT id, title, slug, user_id, excerpt, category_id, created_at, updated_at, status,
+                published_at
+            FROM post
+            WHERE (?1 IS NULL OR user_id = ?1) AND (?2 IS NULL OR status = ?2)
+            ORDER BY published_at DESC NULLS FIRST, created_at DESC, id DESC
+            LIMIT ?3 OFFSET ?4
+            "#,
+        )
+        .bind(filter.user_id)
+        .bind(filter.status.map(PostStatus::as_str))
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(posts)
+    }
+
+    async fn find_by_slug(&self, slug: &str) -> Result<Option<PostModel>, AppError> {
+        let post = sqlx::query_as("SELECT * FROM post WHERE slug = ?")
+            .bind(slug)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(post)
+    }
+
+    async fn create(
+        &self,
+        user_id: i32,
+        post: PostData,
+        status: PostStatus,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<PostModel, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query_as(
+            r#"
+            INSERT INTO post
+                (title, slug, excerpt, content, category_id, user_id, status, published_at)
+            VALUES (
+                ?1, ?2, ?3, ?4, ?5, ?6, ?7,
+                CASE WHEN ?7 = 'published' THEN strftime('%Y-%m-%dT%H:%M:%fZ', 'now') END
+            )
+            RETURNING *
+            "#,
+        )
+        .bind(&post.title)
+        .bind(&post.slug)
+        .bind(&post.excerpt)
+        .bind(&post.content)
+        .bind(post.category_id)
+        .bind(user_id)
+        .bind(status.as_str())
+        .fetch_all(&mut *tx)
+        .await
+        .and_then(first);
+
+        let created = match result {
+            Ok(created) => created,
+            Err(e) => {
+                tx.rollback().await?;
+                return Err(self.post_write_error(e, post.category_id).await);
+            }
+        };
+        for job in &jobs {
+            insert_job(&mut *tx, job).await?;
+        }
+        let entry = NewAuditEntry::post(actor, AuditAction::PostCreate, None, Some(&created));
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(created)
+    }
+
+    async fn update(
+        &self,
+        slug: &str,
+        post: PostData,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let before: Option<PostModel> = sqlx::query_as("SELECT * FROM post WHERE slug = ?")
+            .bind(slug)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let Some(before) = before else {
+            return Ok(None);
+        };
+
+        let result = sqlx::query_as(
+            r#"
+            UPDATE post
+            SET title = ?, slug = ?, excerpt = ?, content = ?, category_id = ?,
+                updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+            WHERE id = ?
+            RETURNING *
+            "#,
+        )
+        .bind(&post.title)
+        .bind(&post.slug)
+        .bind(&post.excerpt)
+        .bind(&post.content)
+        .bind(post.category_id)
+        .bind(before.id)
+        .fetch_all(&mut *tx)
+        .await
+        .and_then(first);
+
+        let updated = match result {
+            Ok(updated) => updated,
+            Err(e) => {
+                tx.rollback().await?;
+                return Err(self.post_write_error(e, post.category_id).await);
+            }
+        };
+        for job in &jobs {
+            insert_job(&mut *tx, job).await?;
+        }
+        let entry = NewAuditEntry::post(
+            actor,
+            AuditAction::PostUpdate,
+            Some(&before),
+            Some(&updated),
+        );
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(Some(updated))
+    }
+
+    async fn set_status(
+        &self,
+        slug: &str,
+        status: PostStatus,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let before: Option<PostModel> = sqlx::query_as("SELECT * FROM post WHERE slug = ?")
+            .bind(slug)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let Some(before) = before else {
+            return Ok(None);
+        };
+
+        let posts = sqlx::query_as(
+            r#"
+            UPDATE post
+            SET status = ?1,
+                published_at = CASE WHEN ?1 = 'published'
+                    THEN COALESCE(published_at, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+                    ELSE published_at END,
+                updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+            WHERE id = ?2
+            RETURNING *
+            "#,
+        )
+        .bind(status.as_str())
+        .bind(before.id)
+        .fetch_all(&mut *tx)
+        .await?;
+        let updated = first(posts)?;
+
+        for job in &jobs {
+            insert_job(&mut *tx, job).await?;
+        }
+        let entry =
+            NewAuditEntry::post(actor, status.audit_action(), Some(&before), Some(&updated));
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(Some(updated))
+    }
+
+    async fn reassign(
+        &self,
+        slug: &str,
+        user_id: i32,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let before: Option<PostModel> = sqlx::query_as("SELECT * FROM post WHERE slug = ?")
+            .bind(slug)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let Some(before) = before else {
+            return Ok(None);
+        };
+
+        let result = sqlx::query_as(
+            r#"
+            UPDATE post
+            SET user_id = ?, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+            WHERE id = ?
+            RETURNING *
+            "#,
+        )
+        .bind(user_id)
+        .bind(before.id)
+        .fetch_all(&mut *tx)
+        .await
+        .and_then(first);
+
+        let updated = match result {
+            Ok(updated) => updated,
+            Err(e) => {
+                tx.rollback().await?;
+                return Err(self.post_write_error(e, None).await);
+            }
+        };
+        let entry = NewAuditEntry::post(
+            actor,
+            AuditAction::PostReassign,
+            Some(&before),
+            Some(&updated),
+        );
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(Some(updated))
+    }
+
+    async fn delete(
+        &self,
+        slug: &str,
+        jobs: Vec<NewJob>,
+        actor: &Actor,
+    ) -> Result<Option<PostModel>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let posts = sqlx::query_as("DELETE FROM post WHERE slug = ? RETURNING *")
+            .bind(slug)
+            .fetch_all(&mut *tx)
+            .await?;
+        let post = posts.into_iter().next();
+
+        if let Some(post) = &post {
+            for job in &jobs {
+                insert_job(&mut *tx, job).await?;
+            }
+            let entry = NewAuditEntry::post(actor, AuditAction::PostDelete, Some(post), None);
+            insert_audit_entry(&mut *tx, &entry).await?;
+        }
+        tx.commit().await?;
+
+        Ok(post)
+    }
+}
+
+#[async_trait]
+impl UserRepository for SqliteRepository {
+    async fn find_by_email(&self, email: &str) -> Result<Option<UserModel>, AppError> {
+        let user = sqlx::query_as("SELECT * FROM users WHERE email = ?")
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(user)
+    }
+
+    async fn find_by_username(&self, username: &str) -> Result<Option<UserModel>, AppError> {
+        let user = sqlx::query_as("SELECT * FROM users WHERE username = ?")
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(user)
+    }
+
+    async fn exists(&self, email: &str, username: &str) -> Result<bool, AppError> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM users WHERE email = ? OR username = ?)",
+        )
+        .bind(email)
+        .bind(username)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(exists)
+    }
+
+    async fn create(&self, user: NewUser, actor: &Actor) -> Result<UserModel, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let user = sqlx::query_as(
+            "INSERT INTO users (username, email, password, is_admin) VALUES (?, ?, ?, ?) RETURNING *",
+        )
+        .bind(&user.username)
+        .bind(&user.email)
+        .bind(&user.password)
+        .bind(user.is_admin)
+        .fetch_all(&mut *tx)
+        .await
+        .and_then(first)?;
+
+        let entry = NewAuditEntry::user(actor, AuditAction::UserRegister, &user);
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn set_password(
+        &self,
+        email: &str,
+        password: &str,
+        actor: &Actor,
+    ) -> Result<Option<UserModel>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let users = sqlx::query_as(
+            r#"
+            UPDATE users
+            SET password = ?, updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+            WHERE email = ?
+            RETURNING *
+            "#,
+        )
+        .bind(password)
+        .bind(email)
+        .fetch_all(&mut *tx)
+        .await?;
+        let user: Option<UserModel> = users.into_iter().next();
+
+        if let Some(user) = &user {
+            let entry = NewAuditEntry::user(actor, AuditAction::UserPasswordReset, user);
+            insert_audit_entry(&mut *tx, &entry).await?;
+        }
+        tx.commit().await?;
+
+        Ok(user)
+    }
+}
+
+#[async_trait]
+impl CategoryRepository for SqliteRepository {
+    async fn list(&self) -> Result<Vec<CategoryModel>, AppError> {
+        let categories = sqlx::query_as("SELECT * FROM category ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(categories)
+    }
+
+    async fn find_by_id(&self, id: i32) -> Result<Option<CategoryModel>, AppError> {
+        let category = sqlx::query_as("SELECT * FROM category WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(category)
+    }
+
+    async fn create(&self, name: &str, actor: &Actor) -> Result<CategoryModel, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let category: CategoryModel =
+            sqlx::query_as("INSERT INTO category (name) VALUES (?) RETURNING *")
+                .bind(name)
+                .fetch_all(&mut *tx)
+                .await
+                .and_then(first)?;
+
+        let entry = NewAuditEntry::snapshot(
+            actor,
+            AuditAction::CategoryCreate,
+            category.id,
+            None,
+            Some(&category),
+        );
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(category)
+    }
+}
+
+#[async_trait]
+impl BackupRepository for SqliteRepository {
+    async fn begin_export(&self) -> Result<Box<dyn BackupExport>, AppError> {
+        // sqlite reads from one snapshot for the whole transaction
+        let tx = self.pool.begin().await?;
+        Ok(Box::new(SqliteBackup { tx }))
+    }
+
+    async fn begin_import(&self) -> Result<Box<dyn BackupImport>, AppError> {
+        let tx = self.pool.begin().await?;
+        Ok(Box::new(SqliteBackup { tx }))
+    }
+}
+
+// the export and the import both run in a single transaction
+struct SqliteBackup {
+    tx: Transaction<'static, Sqlite>,
+}
+
+#[async_trait]
+impl BackupExport for SqliteBackup {
+    async fn users(&mut self, after_id: i32, limit: i64) -> Result<Vec<UserModel>, AppError> {
+        let users = sqlx::query_as("SELECT * FROM users WHERE id > ? ORDER BY id LIMIT ?")
+            .bind(after_id)
+            .bind(limit)
+            .fetch_all(&mut *self.tx)
+            .await?;
+
+        Ok(users)
+    }
+
+    async fn categories(&mut self) -> Result<Vec<CategoryModel>, AppError> {
+        let categories = sqlx::query_as("SELECT * FROM category ORDER BY id")
+            .fetch_all(&mut *self.tx)
+            .await?;
+
+        Ok(categories)
+    }
+
+    async fn posts(&mut self, after_id: i32, limit: i64) -> Result<Vec<PostModel>, AppError> {
+        let posts = sqlx::query_as("SELECT * FROM post WHERE id > ? ORDER BY id LIMIT ?")
+            .bind(after_id)
+            .bind(limit)
+            .fetch_all(&mut *self.tx)
+            .await?;
+
+        Ok(posts)
+    }
+}
+
+#[async_trait]
+impl BackupImport for SqliteBackup {
+    async fn is_empty(&mut self) -> Result<bool, AppError> {
+        let has_rows: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM users) OR EXISTS (SELECT 1 FROM category)
+                OR EXISTS (SELECT 1 FROM post)
+            "#,
+        )
+        .fetch_one(&mut *self.tx)
+        .await?;
+
+        Ok(!has_rows)
+    }
+
+    async fn user(&mut self, user: &UserModel) -> Result<UserModel, AppError> {
+        let user = sqlx::query_as(
+            r#"
+            INSERT INTO users (name, username, email, password, is_admin, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(&user.name)
+        .bind(&user.username)
+        .bind(&user.email)
+        .bind(&user.password)
+        .bind(user.is_admin)
+        .bind(user.created_at.map(timestamp))
+        .bind(user.updated_at.map(timestamp))
+        .fetch_all(&mut *self.tx)
+        .await
+        .and_then(first)?;
+
+        Ok(user)
+    }
+
+    async fn category(&mut self, name: &str) -> Result<CategoryModel, AppError> {
+        let category = sqlx::query_as("INSERT INTO category (name) VALUES (?) RETURNING *")
+            .bind(name)
+            .fetch_all(&mut *self.tx)
+            .await
+            .and_then(first)?;
+
+        Ok(category)
+    }
+
+    async fn post(&mut self, post: &PostModel) -> Result<PostModel, AppError> {
+        let post = sqlx::query_as(
+            r#"
+            INSERT INTO post (
+                title, slug, excerpt, content, category_id, user_id, created_at, updated_at,
+                status, published_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(&post.title)
+        .bind(&post.slug)
+        .bind(&post.excerpt)
+        .bind(&post.content)
+        .bind(post.category_id)
+        .bind(post.user_id)
+        .bind(post.created_at.map(timestamp))
+        .bind(post.updated_at.map(timestamp))
+        .bind(post.status)
+        .bind(post.published_at.map(timestamp))
+        .fetch_all(&mut *self.tx)
+        .await
+        .and_then(first)?;
+
+        Ok(post)
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), AppError> {
+        self.tx.commit().await?;
+        Ok(())
+    }
+}
+
+// the format of the `strftime` defaults in the migrations, so timestamps compare as text
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+const NOW: &str = "strftime('%Y-%m-%dT%H:%M:%fZ', 'now')";
+
+async fn insert_job<'c>(
+    executor: impl SqliteExecutor<'c>,
+    job: &NewJob,
+) -> Result<Option<JobModel>, sqlx::Error> {
+    let jobs = sqlx::query_as(&format!(
+        r#"
+        INSERT INTO jobs (kind, payload, max_attempts, run_at, unique_key)
+        VALUES (?, ?, ?, COALESCE(?, {NOW}), ?)
+        ON CONFLICT (unique_key) WHERE state IN ('queued', 'running') DO NOTHING
+        RETURNING *
+        "#
+    ))
+    .bind(&job.kind)
+    .bind(Json(&job.payload))
+    .bind(job.max_attempts)
+    .bind(job.run_at.map(timestamp))
+    .bind(&job.unique_key)
+    .fetch_all(executor)
+    .await?;
+
+    Ok(jobs.into_iter().next())
+}
+
+// sqlite has a single writer, so the claiming `UPDATE` needs no row locks
+#[async_trait]
+impl JobRepository for SqliteRepository {
+    async fn enqueue(&self, job: NewJob) -> Result<Option<JobModel>, AppError> {
+        Ok(insert_job(&self.pool, &job).await?)
+    }
+
+    async fn claim(&self, limit: i64, lease: Duration) -> Result<Vec<JobModel>, AppError> {
+        let jobs = sqlx::query_as(&format!(
+            r#"
+            UPDATE jobs
+            SET state = 'running', attempts = attempts + 1,
+                locked_until = strftime('%Y-%m-%dT%H:%M:%fZ', 'now', ?), updated_at = {NOW}
+            WHERE id IN (
+                SELECT id FROM jobs
+                WHERE (state = 'queued' AND run_at <= {NOW})
+                    OR (state = 'running' AND locked_until < {NOW})
+                ORDER BY run_at, id
+                LIMIT ?
+            )
+            RETURNING *
+            "#
+        ))
+        .bind(format!("+{} seconds", lease.as_secs_f64()))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(jobs)
+    }
+
+    async fn complete(&self, id: i64, attempt: i32) -> Result<bool, AppError> {
+        let result =
+            sqlx::query("DELETE FROM jobs WHERE id = ? AND state = 'running' AND attempts = ?")
+                .bind(id)
+                .bind(attempt)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn retry(
+        &self,
+        id: i64,
+        attempt: i32,
+        error: &str,
+        run_at: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query(&format!(
+            r#"
+            UPDATE jobs
+            SET state = 'queued', last_error = ?, run_at = ?, locked_until = NULL,
+                updated_at = {NOW}
+            WHERE id = ? AND state = 'running' AND attempts = ?
+            "#
+        ))
+        .bind(error)
+        .bind(timestamp(run_at))
+        .bind(id)
+        .bind(attempt)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn dead_letter(&self, id: i64, attempt: i32, error: &str) -> Result<bool, AppError> {
+        let result = sqlx::query(&format!(
+            r#"
+            UPDATE jobs
+            SET state = 'dead', last_error = ?, locked_until = NULL, updated_at = {NOW}
+            WHERE id = ? AND state = 'running' AND attempts = ?
+            "#
+        ))
+        .bind(error)
+        .bind(id)
+        .bind(attempt)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list(&self, state: JobState, limit: i64) -> Result<Vec<JobModel>, AppError> {
+        let jobs = sqlx::query_as("SELECT * FROM jobs WHERE state = ? ORDER BY run_at, id LIMIT ?")
+            .bind(state.as_str())
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(jobs)
+    }
+
+    async fn requeue(&self, id: i64) -> Result<Option<JobModel>, AppError> {
+        let jobs = sqlx::query_as(&format!(
+            r#"
+            UPDATE jobs
+            SET state = 'queued', attempts = 0, run_at = {NOW}, updated_at = {NOW}
+            WHERE id = ? AND state = 'dead'
+            RETURNING *
+            "#
+        ))
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(jobs.into_iter().next())
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepository for SqliteRepository {
+    async fn begin(
+        &self,
+        user_id: i32,
+        key: &str,
+        fingerprint: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyKeyModel>, AppError> {
+        // the record holding the key can expire or be released between the two statements
+        for _ in 0..3 {
+            let reserved = sqlx::query(&format!(
+                r#"
+                INSERT INTO idempotency_key (user_id, key, fingerprint, expires_at)
+                VALUES (?, ?, ?, ?)
+                ON CONFLICT (user_id, key) DO UPDATE
+                SET fingerprint = excluded.fingerprint, response_status = NULL,
+                    response_headers = NULL, response_body = NULL, created_at = {NOW},
+                    expires_at = excluded.expires_at
+                WHERE idempotency_key.expires_at <= {NOW}
+                "#
+            ))
+            .bind(user_id)
+            .bind(key)
+            .bind(fingerprint)
+            .bind(timestamp(expires_at))
+            .execute(&self.pool)
+            .await?
+            .rows_affected()
+                > 0;
+            if reserved {
+                return Ok(None);
+            }
+
+            let existing = sqlx::query_as(&format!(
+                "SELECT * FROM idempotency_key WHERE user_id = ? AND key = ? AND expires_at > {NOW}"
+            ))
+            .bind(user_id)
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
+            if existing.is_some() {
+                return Ok(existing);
+            }
+        }
+
+        Err(AppError::Internal(format!(
+            "could not reserve the idempotency key `{key}`"
+        )))
+    }
+
+    async fn finish(
+        &self,
+        user_id: i32,
+        key: &str,
+        response: &StoredResponse,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE idempotency_key
+            SET response_status = ?, response_headers = ?, response_body = ?, expires_at = ?
+            WHERE user_id = ? AND key = ? AND response_status IS NULL
+            "#,
+        )
+        .bind(i32::from(response.status))
+        .bind(Json(&response.headers))
+        .bind(&response.body)
+        .bind(timestamp(expires_at))
+        .bind(user_id)
+        .bind(key)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn release(&self, user_id: i32, key: &str) -> Result<(), AppError> {
+        sqlx::query(
+            "DELETE FROM idempotency_key WHERE user_id = ? AND key = ? AND response_status IS NULL",
+        )
+        .bind(user_id)
+        .bind(key)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn prune(&self) -> Result<(), AppError> {
+        sqlx::query(&format!(
+            "DELETE FROM idempotency_key WHERE expires_at <= {NOW}"
+        ))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CspReportRepository for SqliteRepository {
+    async fn create(&self, report: NewCspReport) -> Result<CspReportModel, AppError> {
+        let reports = sqlx::query_as(
+            r#"
+            INSERT INTO csp_report (document_uri, violated_directive, blocked_uri, source_file,
+                line_number, disposition, user_agent)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(report.document_uri)
+        .bind(report.violated_directive)
+        .bind(report.blocked_uri)
+        .bind(report.source_file)
+        .bind(report.line_number)
+        .bind(report.disposition)
+        .bind(report.user_agent)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(first(reports)?)
+    }
+
+    async fn list(&self, limit: i64) -> Result<Vec<CspReportModel>, AppError> {
+        let reports = sqlx::query_as("SELECT * FROM csp_report ORDER BY id DESC LIMIT ?")
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(reports)
+    }
+}
+
+async fn insert_audit_entry<'c>(
+    executor: impl SqliteExecutor<'c>,
+    entry: &NewAuditEntry,
+) -> Result<AuditLogModel, sqlx::Error> {
+    let entries = sqlx::query_as(
+        r#"
+        INSERT INTO audit_log
+            (actor_id, ip, user_agent, action, target_type, target_id, before, after)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING *
+        "#,
+    )
+    .bind(entry.actor.user_id)
+    .bind(&entry.actor.ip)
+    .bind(&entry.actor.user_agent)
+    .bind(entry.action.as_str())
+    .bind(entry.action.target_type())
+    .bind(&entry.target_id)
+    .bind(entry.before.as_ref().map(Json))
+    .bind(entry.after.as_ref().map(Json))
+    .fetch_all(executor)
+    .await?;
+
+    first(entries)
+}
+
+#[async_trait]
+impl AuditRepository for SqliteRepository {
+    async fn record(&self, entry: NewAuditEntry) -> Result<AuditLogModel, AppError> {
+        Ok(insert_audit_entry(&self.pool, &entry).await?)
+    }
+
+    async fn list(
+        &self,
+        filter: &AuditFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AuditLogModel>, AppError> {
+        let entries = sqlx::query_as(
+            r#"
+            SELECT * FROM audit_log
+            WHERE (?1 IS NULL OR actor_id = ?1)
+                AND (?2 IS NULL OR action = ?2)
+                AND (?3 IS NULL OR target_type = ?3)
+                AND (?4 IS NULL OR target_id = ?4)
+                AND (?5 IS NULL OR created_at >= ?5)
+                AND (?6 IS NULL OR created_at < ?6)
+            ORDER BY id DESC
+            LIMIT ?7 OFFSET ?8
+            "#,
+        )
+        .bind(filter.actor_id)
+        .bind(&filter.action)
+        .bind(&filter.target_type)
+        .bind(&filter.target_id)
+        .bind(filter.since.map(timestamp))
+        .bind(filter.until.map(timestamp))
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(entries)
+    }
+}
+
+#[async_trait]
+impl FeatureFlagRepository for SqliteRepository {
+    async fn list(&self) -> Result<Vec<FeatureFlagModel>, AppError> {
+        let flags = sqlx::query_as("SELECT * FROM feature_flags ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(flags)
+    }
+
+    async fn list_overrides(&self) -> Result<Vec<FeatureFlagOverrideModel>, AppError> {
+        let overrides =
+            sqlx::query_as("SELECT * FROM feature_flag_overrides ORDER BY flag_name, user_id")
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(overrides)
+    }
+
+    async fn upsert(
+        &self,
+        name: &str,
+        flag: FeatureFlagData,
+        actor: &Actor,
+    ) -> Result<FeatureFlagModel, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let before: Option<FeatureFlagModel> =
+            sqlx::query_as("SELECT * FROM feature_flags WHERE name = ?")
+                .bind(name)
+                .fetch_optional(&mut *tx)
+                .await?;
+        let flags = sqlx::query_as(&format!(
+            r#"
+            INSERT INTO feature_flags (name, description, enabled, rollout_percentage)
+            VALUES (?1, COALESCE(?2, ''), COALESCE(?3, FALSE), COALESCE(?4, 100))
+            ON CONFLICT (name) DO UPDATE SET
+                description = COALESCE(?2, feature_flags.description),
+                enabled = COALESCE(?3, feature_flags.enabled),
+                rollout_percentage = COALESCE(?4, feature_flags.rollout_percentage),
+                updated_at = {NOW}
+            RETURNING *
+            "#
+        ))
+        .bind(name)
+        .bind(flag.description)
+        .bind(flag.enabled)
+        .bind(flag.rollout_percentage)
+        .fetch_all(&mut *tx)
+        .await?;
+        let flag: FeatureFlagModel = first(flags)?;
+
+        let entry = NewAuditEntry::snapshot(
+            actor,
+            AuditAction::FeatureFlagUpsert,
+            name,
+            before.as_ref(),
+            Some(&flag),
+        );
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(flag)
+    }
+
+    async fn delete(
+        &self,
+        name: &str,
+        actor: &Actor,
+    ) -> Result<Option<FeatureFlagModel>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let flags: Vec<FeatureFlagModel> =
+            sqlx::query_as("DELETE FROM feature_flags WHERE name = ? RETURNING *")
+                .bind(name)
+                .fetch_all(&mut *tx)
+                .await?;
+        let Some(flag) = flags.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let entry = NewAuditEntry::snapshot(
+            actor,
+            AuditAction::FeatureFlagDelete,
+            name,
+            Some(&flag),
+            None,
+        );
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(Some(flag))
+    }
+
+    // the flag is checked by the `SELECT`, so a failing foreign key can only be the user's
+    async fn set_override(
+        &self,
+        name: &str,
+        user_id: i32,
+        enabled: bool,
+        actor: &Actor,
+    ) -> Result<FeatureFlagOverrideModel, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let before: Option<FeatureFlagOverrideModel> = sqlx::query_as(
+            "SELECT * FROM feature_flag_overrides WHERE flag_name = ? AND user_id = ?",
+        )
+        .bind(name)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let overrides: Vec<FeatureFlagOverrideModel> = sqlx::query_as(
+            r#"
+            INSERT INTO feature_flag_overrides (flag_name, user_id, enabled)
+            SELECT name, ?2, ?3 FROM feature_flags WHERE name = ?1
+            ON CONFLICT (flag_name, user_id) DO UPDATE SET enabled = excluded.enabled
+            RETURNING *
+            "#,
+        )
+        .bind(name)
+        .bind(user_id)
+        .bind(enabled)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| {
+            let is_fk_violation = e
+                .as_database_error()
+                .is_some_and(|db_error| db_error.kind() == ErrorKind::ForeignKeyViolation);
+            if is_fk_violation {
+                AppError::FeatureFlagUserNotFound
+            } else {
+                e.into()
+            }
+        })?;
+        let flag_override = overrides
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::FeatureFlagNotFound(name.to_string()))?;
+
+        let entry = NewAuditEntry::snapshot(
+            actor,
+            AuditAction::FeatureFlagOverrideSet,
+            name,
+            before.as_ref(),
+            Some(&flag_override),
+        );
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(flag_override)
+    }
+
+    async fn delete_override(
+        &self,
+        name: &str,
+        user_id: i32,
+        actor: &Actor,
+    ) -> Result<Option<FeatureFlagOverrideModel>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let overrides: Vec<FeatureFlagOverrideModel> = sqlx::query_as(
+            "DELETE FROM feature_flag_overrides WHERE flag_name = ? AND user_id = ? RETURNING *",
+        )
+        .bind(name)
+        .bind(user_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        let Some(flag_override) = overrides.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let entry = NewAuditEntry::snapshot(
+            actor,
+            AuditAction::FeatureFlagOverrideDelete,
+            name,
+            Some(&flag_override),
+            None,
+        );
+        insert_audit_entry(&mut *tx, &entry).await?;
+        tx.commit().await?;
+
+        Ok(Some(flag_override))
+    }
+}
+
+#[async_trait]
+impl DatabaseHealth for SqliteRepository {
+    async fn ping(&self) -> Result<(), AppError> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn pending_migrations(&self) -> Result<Vec<String>, AppError> {
+        let applied: Vec<i64> =
+            sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+                .fetch_all(&self.pool)
+                .await?;
+        let applied = applied.into_iter().collect::<HashSet<_>>();
+
+        Ok(MIGRATOR
+            .iter()
+            .filter(|migration| !applied.contains(&migration.version))
+            .map(|migration| format!("{}/{}", migration.version, migration.description))
+            .collect())
+    }
+
+    fn pool_status(&self) -> Option<PoolStatus> {
+        Some(PoolStatus {
+            size: self.pool.size(),
+            idle: self.pool.num_idle() as u32,
+            max: self.pool.options().get_max_connections(),
+        })
+    }
+}
@@ -2,64 +2,138 @@ use std::sync::Arc;
 
 use axum::{
     middleware,
-    routing::{delete, get, patch, post},
+    routing::{delete, get, patch, post, put},
     Router,
 };
 
+use chrono::{TimeZone, Utc};
+
 use crate::{
-    guard::auth_guard_middleware,
+    deprecation::{deprecated, Deprecation},
+    guard::{admin_guard_middleware, auth_guard_middleware},
     handlers::{
+        admin::{
+            delete_feature_flag_handler, delete_feature_flag_override_handler,
+            fetch_audit_log_handler, fetch_feature_flags_handler,
+            set_feature_flag_override_handler, upsert_feature_flag_handler,
+        },
         auth::{
             current_user_handler, login_user_handler, logout_user_handler, register_user_handler,
         },
+        feature_flag::fetch_feature_flag_values_handler,
         post::{
-            create_post_handler, delete_post_handler, fetch_post_detail_handler,
-            fetch_post_handler, update_post_handler,
+            archive_post_handler, create_post_handler, create_post_v2_handler, delete_post_handler,
+            fetch_own_posts_handler, fetch_post_detail_handler, fetch_post_handler,
+            publish_post_handler, unpublish_post_handler, update_post_handler,
         },
     },
+    idempotency::idempotency_middleware,
+    rate_limit::{rate_limit_middleware, RateLimitGroup},
     AppState,
 };
 
+// `/api/v1` is the stable surface and `/api/v2` holds routes with breaking changes; both share
+// `AppState`. The unversioned `/api` paths are an alias of v1 kept for existing clients.
 pub fn api_routes(app_state: Arc<AppState>) -> Router {
+    let v1 = v1_routes(app_state.clone());
+
     Router::new()
-        .route("/post", get(fetch_post_handler))
-        .route("/post/:slug", get(fetch_post_detail_handler))
-        .route(
-            "/post/create",
-            post(create_post_handler).route_layer(middleware::from_fn_with_state(
-                app_state.clone(),
-                auth_guard_middleware,
-            )),
-        )
+        .nest("/v1", v1.clone())
+        .nest("/v2", v2_routes(app_state))
+        .merge(v1)
+}
+
+// the layers of every route that writes posts; the guard is the outer layer so the limiter and
+// the idempotency keys can use the user
+fn write_routes(app_state: &Arc<AppState>, routes: Router<Arc<AppState>>) -> Router<Arc<AppState>> {
+    routes
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            idempotency_middleware,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            (app_state.clone(), RateLimitGroup::Write),
+            rate_limit_middleware,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            auth_guard_middleware,
+        ))
+}
+
+// v2 creates drafts unless the body sets a status, where v1 publishes
+fn create_post_deprecation() -> Deprecation {
+    Deprecation::new(Utc.with_ymd_and_hms(2026, 10, 18, 0, 0, 0).unwrap())
+        .sunset(Utc.with_ymd_and_hms(2027, 4, 1, 0, 0, 0).unwrap())
+        .successor("/api/v2/post/create")
+}
+
+// routes of v1 that are replaced in v2 should be wrapped with `deprecation::deprecated`
+pub fn v1_routes(app_state: Arc<AppState>) -> Router {
+    let auth_guard = || middleware::from_fn_with_state(app_state.clone(), auth_guard_middleware);
+    let rate_limit =
+        |group| middleware::from_fn_with_state((app_state.clone(), group), rate_limit_middleware);
+
+    let auth = Router::new()
+        .route("/auth/register", post(register_user_handler))
+        .route("/auth/login", post(login_user_handler))
+        .route_layer(rate_limit(RateLimitGroup::Auth));
+
+    let write = write_routes(
+        &app_state,
+        Router::new()
+            .route(
+                "/post/create",
+                deprecated(post(create_post_handler), create_post_deprecation()),
+            )
+            .route("/post/update/:slug", patch(update_post_handler))
+            .route("/post/delete/:slug", delete(delete_post_handler))
+            .route("/post/publish/:slug", post(publish_post_handler))
+            .route("/post/unpublish/:slug", post(unpublish_post_handler))
+            .route("/post/archive/:slug", post(archive_post_handler)),
+    );
+
+    let admin = Router::new()
+        .route("/admin/audit", get(fetch_audit_log_handler))
+        .route("/admin/feature_flags", get(fetch_feature_flags_handler))
         .route(
-            "/post/update/:slug",
-            patch(update_post_handler).route_layer(middleware::from_fn_with_state(
-                app_state.clone(),
-                auth_guard_middleware,
-            )),
+            "/admin/feature_flags/:name",
+            put(upsert_feature_flag_handler).delete(delete_feature_flag_handler),
         )
         .route(
-            "/post/delete/:slug",
-            delete(delete_post_handler).route_layer(middleware::from_fn_with_state(
-                app_state.clone(),
-                auth_guard_middleware,
-            )),
+            "/admin/feature_flags/:name/overrides/:user_id",
+            put(set_feature_flag_override_handler).delete(delete_feature_flag_override_handler),
         )
-        .route("/auth/register", post(register_user_handler))
-        .route("/auth/login", post(login_user_handler))
+        .route_layer(middleware::from_fn(admin_guard_middleware))
+        .route_layer(auth_guard());
+
+    Router::new()
+        .route("/post", get(fetch_post_handler))
+        .route("/post/:slug", get(fetch_post_detail_handler))
+        .route("/feature_flags", get(fetch_feature_flag_values_handler))
         .route(
             "/auth/logout",
-            post(logout_user_handler).route_layer(middleware::from_fn_with_state(
-                app_state.clone(),
-                auth_guard_middleware,
-            )),
+            post(logout_user_handler).route_layer(auth_guard()),
         )
         .route(
             "/auth/current_user",
-            get(current_user_handler).route_layer(middleware::from_fn_with_state(
-                app_state.clone(),
-                auth_guard_middleware,
-            )),
+            get(current_user_handler).route_layer(auth_guard()),
         )
+        .route(
+            "/auth/current_user/posts",
+            get(fetch_own_posts_handler).route_layer(auth_guard()),
+        )
+        .merge(auth)
+        .merge(write)
+        .merge(admin)
         .with_state(app_state)
 }
+
+// new posts are drafts unless they set a status
+pub fn v2_routes(app_state: Arc<AppState>) -> Router {
+    write_routes(
+        &app_state,
+        Router::new().route("/post/create", post(create_post_v2_handler)),
+    )
+    .with_state(app_state)
+}
@@ -1,10 +1,49 @@
 use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::model::PostStatus;
 
 // Post related schemas
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct FilterOptions {
+    /// Page number, starting at 1
+    pub page: Option<usize>,
+    /// Page size, capped by `pagination.max_page_size`
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OwnPostFilterOptions {
+    /// Page number, starting at 1
     pub page: Option<usize>,
+    /// Page size, capped by `pagination.max_page_size`
     pub limit: Option<usize>,
+    /// Only posts with this status
+    pub status: Option<PostStatus>,
+}
+
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditLogFilterOptions {
+    /// Page number, starting at 1
+    pub page: Option<usize>,
+    /// Page size, capped by `pagination.max_page_size`
+    pub limit: Option<usize>,
+    /// Only entries by this user
+    pub actor_id: Option<i32>,
+    /// Only this action, e.g. `post.delete`
+    pub action: Option<String>,
+    /// Only entries about a `post` or a `user`
+    pub target_type: Option<String>,
+    /// Only entries about the post or user with this id
+    pub target_id: Option<String>,
+    /// Only entries at or after this RFC 3339 time
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only entries before this RFC 3339 time
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -14,7 +53,7 @@ pub struct ParamOptions {
 }
 
 // this is the schema for the the post overview; it is used to fetch all posts so it doesn't need the content
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
 pub struct FetchAllPostSchema {
     pub id: i32,
     pub title: String,
@@ -24,9 +63,11 @@ pub struct FetchAllPostSchema {
     pub category_id: Option<i32>,
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub status: PostStatus,
+    pub published_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct CreatePostSchema {
     pub title: String,
     pub slug: String,
@@ -34,9 +75,12 @@ pub struct CreatePostSchema {
     pub content: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub category_id: Option<i32>,
+    // published unless given on v1, a draft on v2
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<PostStatus>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct UpdatePostSchema {
     pub title: Option<String>,
     pub slug: Option<String>,
@@ -47,12 +91,13 @@ pub struct UpdatePostSchema {
 
 // Auth related schemas
 // user data schema is for response data so it doesn't include password.
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 pub struct UserDataSchema {
     pub id: i32,
     pub name: Option<String>,
     pub username: String,
     pub email: String,
+    pub is_admin: bool,
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
 }
@@ -64,15 +109,49 @@ pub struct TokenClaims {
     pub email: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct RegisterUserSchema {
     pub username: String,
     pub email: String,
     pub password: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct LoginUserSchema {
     pub email: String,
     pub password: String,
 }
+
+// Feature flag related schemas
+// omitted fields keep their value, or get the default when the flag is created
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct UpsertFeatureFlagSchema {
+    /// Defaults to an empty string
+    pub description: Option<String>,
+    /// Defaults to `false`
+    pub enabled: Option<bool>,
+    /// 0 to 100, defaults to 100
+    pub rollout_percentage: Option<i32>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct SetFeatureFlagOverrideSchema {
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct FeatureFlagSchema {
+    pub name: String,
+    pub description: String,
+    pub enabled: bool,
+    pub rollout_percentage: i32,
+    pub overrides: Vec<FeatureFlagOverrideSchema>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct FeatureFlagOverrideSchema {
+    pub user_id: i32,
+    pub enabled: bool,
+}
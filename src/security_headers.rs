@@ -0,0 +1,212 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderName, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::config::{SecurityHeadersConfig, SecurityHeadersRoute};
+
+const CSP_REPORT_ONLY: HeaderName = HeaderName::from_static("content-security-policy-report-only");
+const PERMISSIONS_POLICY: HeaderName = HeaderName::from_static("permissions-policy");
+
+// the headers of a response, resolved from `SecurityHeadersConfig` once at startup. `None` leaves
+// a header out.
+#[derive(Debug, Clone)]
+struct Policy {
+    csp: Option<HeaderValue>,
+    referrer_policy: Option<HeaderValue>,
+    permissions_policy: Option<HeaderValue>,
+    frame_options: Option<HeaderValue>,
+}
+
+#[derive(Debug, Clone)]
+struct RoutePolicy {
+    path: String,
+    json: Policy,
+    html: Policy,
+}
+
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    enabled: bool,
+    csp_header: HeaderName,
+    hsts: Option<HeaderValue>,
+    json: Policy,
+    html: Policy,
+    // longest path first
+    routes: Vec<RoutePolicy>,
+}
+
+impl SecurityHeaders {
+    // the values are checked by `Config::validate` at startup; invalid ones are left out
+    pub fn new(config: &SecurityHeadersConfig) -> Self {
+        let csp = |policy: &str| {
+            let policy = policy.trim().trim_end_matches(';');
+            if policy.is_empty() {
+                return None;
+            }
+            match config.csp_report_uri.as_str() {
+                "" => header_value(policy),
+                uri => header_value(&format!("{policy}; report-uri {uri}")),
+            }
+        };
+
+        let json = Policy {
+            csp: csp(&config.content_security_policy),
+            referrer_policy: header_value(&config.referrer_policy),
+            permissions_policy: header_value(&config.permissions_policy),
+            frame_options: header_value(&config.frame_options.to_ascii_uppercase()),
+        };
+        let html = Policy {
+            csp: csp(&config.html_content_security_policy),
+            ..json.clone()
+        };
+
+        let mut routes = config
+            .routes
+            .iter()
+            .map(|route| RoutePolicy {
+                path: route.path.trim_end_matches('/').to_string(),
+                json: json.with_overrides(route, csp),
+                html: html.with_overrides(route, csp),
+            })
+            .collect::<Vec<_>>();
+        routes.sort_by_key(|route| std::cmp::Reverse(route.path.len()));
+
+        let hsts = (config.hsts_max_age_secs > 0).then(|| {
+            let mut value = format!("max-age={}", config.hsts_max_age_secs);
+            if config.hsts_include_subdomains {
+                value.push_str("; includeSubDomains");
+            }
+            if config.hsts_preload {
+                value.push_str("; preload");
+            }
+            value
+        });
+
+        Self {
+            enabled: config.enabled,
+            csp_header: if config.csp_report_only {
+                CSP_REPORT_ONLY
+            } else {
+                header::CONTENT_SECURITY_POLICY
+            },
+            hsts: hsts.as_deref().and_then(header_value),
+            json,
+            html,
+            routes,
+        }
+    }
+
+    fn policy(&self, path: &str, is_html: bool) -> &Policy {
+        let route = self
+            .routes
+            .iter()
+            .find(|route| matches_path(&route.path, path));
+        match (route, is_html) {
+            (Some(route), true) => &route.html,
+            (Some(route), false) => &route.json,
+            (None, true) => &self.html,
+            (None, false) => &self.json,
+        }
+    }
+}
+
+impl Policy {
+    // the headers of a route, falling back to `self` for the ones it doesn't set
+    fn with_overrides(
+        &self,
+        route: &SecurityHeadersRoute,
+        csp: impl Fn(&str) -> Option<HeaderValue>,
+    ) -> Self {
+        let or_inherited = |value: Option<String>, inherited: &Option<HeaderValue>| match value {
+            Some(value) => header_value(&value),
+            None => inherited.clone(),
+        };
+
+        Self {
+            csp: match &route.content_security_policy {
+                Some(policy) => csp(policy),
+                None => self.csp.clone(),
+            },
+            referrer_policy: or_inherited(route.referrer_policy.clone(), &self.referrer_policy),
+            permissions_policy: or_inherited(
+                route.permissions_policy.clone(),
+                &self.permissions_policy,
+            ),
+            frame_options: or_inherited(
+                route
+                    .frame_options
+                    .as_ref()
+                    .map(|value| value.to_ascii_uppercase()),
+                &self.frame_options,
+            ),
+        }
+    }
+}
+
+// `/api/docs` matches `/api/docs` and `/api/docs/...` but not `/api/docsearch`
+fn matches_path(prefix: &str, path: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+fn header_value(value: &str) -> Option<HeaderValue> {
+    if value.is_empty() {
+        return None;
+    }
+    HeaderValue::from_str(value).ok()
+}
+
+// adds the configured headers to every response; the ones a handler set itself are kept, so a
+// route can also override them in code. The policy is picked by the path, then by whether the
+// response is html.
+pub async fn security_headers_middleware(
+    State(security): State<Arc<SecurityHeaders>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    if !security.enabled {
+        return next.run(req).await;
+    }
+
+    let path = req.uri().path().to_string();
+    let mut response = next.run(req).await;
+
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    let policy = security.policy(&path, is_html);
+
+    let headers = response.headers_mut();
+    let values = [
+        (security.csp_header.clone(), &policy.csp),
+        (header::STRICT_TRANSPORT_SECURITY, &security.hsts),
+        (header::REFERRER_POLICY, &policy.referrer_policy),
+        (PERMISSIONS_POLICY, &policy.permissions_policy),
+        (header::X_FRAME_OPTIONS, &policy.frame_options),
+    ];
+    for (name, value) in values {
+        if let Some(value) = value {
+            if !headers.contains_key(&name) {
+                headers.insert(name, value.clone());
+            }
+        }
+    }
+    if !headers.contains_key(header::X_CONTENT_TYPE_OPTIONS) {
+        headers.insert(
+            header::X_CONTENT_TYPE_OPTIONS,
+            HeaderValue::from_static("nosniff"),
+        );
+    }
+
+    response
+}
@@ -0,0 +1,95 @@
+use std::{future::IntoFuture, net::SocketAddr, sync::Arc, time::Duration};
+
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    app, idempotency,
+    jobs::{self, JobRegistry},
+    notify, rate_limit, AppState,
+};
+
+// serves the app until SIGTERM or ctrl-c, then stops accepting connections, gives in-flight
+// requests `server.shutdown_timeout_secs` to finish and finally stops the background workers
+pub async fn serve(listener: TcpListener, app_state: Arc<AppState>) -> std::io::Result<()> {
+    let timeout = Duration::from_secs(app_state.config.server.shutdown_timeout_secs);
+    let stop_accepting = CancellationToken::new();
+
+    let state = app_state.clone();
+    app_state.tasks.spawn("rate_limit_prune", move |shutdown| {
+        rate_limit::prune_worker(state.clone(), shutdown)
+    });
+    let state = app_state.clone();
+    app_state.tasks.spawn("idempotency_prune", move |shutdown| {
+        idempotency::prune_worker(state.clone(), shutdown)
+    });
+    if app_state.notify_listener.is_some() {
+        let state = app_state.clone();
+        app_state.tasks.spawn("notify_listener", move |shutdown| {
+            notify::listen_worker(state.clone(), shutdown)
+        });
+    }
+    if app_state.config.jobs.enabled {
+        jobs::spawn_workers(&app_state, JobRegistry::builtin());
+    }
+
+    // the peer address is the rate limiting key of anonymous requests
+    let service = app(app_state.clone()).into_make_service_with_connect_info::<SocketAddr>();
+    let server = axum::serve(listener, service)
+        .with_graceful_shutdown(stop_accepting.clone().cancelled_owned());
+    let mut server = tokio::spawn(server.into_future());
+
+    let result = tokio::select! {
+        result = &mut server => Some(result),
+        _ = shutdown_signal() => None,
+    };
+
+    let result = match result {
+        // the server stopped on its own, e.g. the listener failed
+        Some(result) => result,
+        None => {
+            tracing::info!("Shutting down, draining in-flight requests");
+            stop_accepting.cancel();
+            match tokio::time::timeout(timeout, &mut server).await {
+                Ok(result) => result,
+                Err(_) => {
+                    tracing::warn!(
+                        "In-flight requests did not finish within {}s, dropping them",
+                        timeout.as_secs()
+                    );
+                    server.abort();
+                    Ok(Ok(()))
+                }
+            }
+        }
+    };
+
+    app_state.tasks.shutdown(timeout).await;
+    tracing::info!("Shutdown complete");
+
+    result.unwrap_or_else(|e| Err(std::io::Error::other(e)))
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install the ctrl-c handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install the SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
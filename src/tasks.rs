@@ -0,0 +1,204 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use utoipa::ToSchema;
+
+pub type WorkerError = Box<dyn std::error::Error + Send + Sync>;
+
+// restarts back off exponentially from `INITIAL_BACKOFF` up to `MAX_BACKOFF`; a worker that
+// stayed up for `MAX_BACKOFF` is considered healthy again and starts over from the minimum
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkerState {
+    Running,
+    // waiting for the backoff after a panic or an error
+    Restarting,
+    // returned `Ok` or was asked to shut down
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WorkerStatus {
+    pub state: WorkerState,
+    pub restarts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+type Statuses = Arc<Mutex<BTreeMap<String, WorkerStatus>>>;
+
+// owns the long-running background work of the process; each worker gets a `CancellationToken`
+// that is cancelled on shutdown and must return soon after
+#[derive(Default)]
+pub struct TaskSupervisor {
+    shutdown: CancellationToken,
+    statuses: Statuses,
+    handles: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl TaskSupervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `worker` is called again to restart the worker after it panics or returns an error
+    pub fn spawn<F, Fut>(&self, name: &str, worker: F)
+    where
+        F: Fn(CancellationToken) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), WorkerError>> + Send + 'static,
+    {
+        let name = name.to_string();
+        let shutdown = self.shutdown.clone();
+        let statuses = self.statuses.clone();
+        update(&statuses, &name, |status| {
+            status.state = WorkerState::Running
+        });
+
+        let handle = tokio::spawn(async move {
+            let mut backoff = INITIAL_BACKOFF;
+
+            loop {
+                let started = tokio::time::Instant::now();
+                let result = AbortOnDrop(tokio::spawn(worker(shutdown.clone()))).await;
+
+                let error = match result {
+                    Ok(Ok(())) => None,
+                    Ok(Err(e)) => Some(e.to_string()),
+                    Err(e) if e.is_panic() => Some(panic_message(e.into_panic())),
+                    Err(e) => Some(e.to_string()),
+                };
+
+                let Some(error) = error.filter(|_| !shutdown.is_cancelled()) else {
+                    tracing::info!(worker = %name, "Worker stopped");
+                    update(&statuses, &name, |status| {
+                        status.state = WorkerState::Stopped
+                    });
+                    return;
+                };
+
+                if started.elapsed() >= MAX_BACKOFF {
+                    backoff = INITIAL_BACKOFF;
+                }
+                tracing::error!(
+                    worker = %name,
+                    "Worker failed, restarting in {}s: {error}",
+                    backoff.as_secs()
+                );
+                update(&statuses, &name, |status| {
+                    status.state = WorkerState::Restarting;
+                    status.last_error = Some(error);
+                });
+
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = shutdown.cancelled() => {
+                        update(&statuses, &name, |status| status.state = WorkerState::Stopped);
+                        return;
+                    }
+                }
+
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                update(&statuses, &name, |status| {
+                    status.state = WorkerState::Running;
+                    status.restarts += 1;
+                });
+            }
+        });
+
+        self.handles
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(handle);
+    }
+
+    pub fn statuses(&self) -> BTreeMap<String, WorkerStatus> {
+        self.statuses
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    // signals every worker and waits up to `timeout` for them to return; stragglers are aborted
+    pub async fn shutdown(&self, timeout: Duration) {
+        self.shutdown.cancel();
+
+        let handles = std::mem::take(&mut *self.handles.lock().unwrap_or_else(|e| e.into_inner()));
+        let aborts = handles
+            .iter()
+            .map(|handle| handle.abort_handle())
+            .collect::<Vec<_>>();
+
+        if tokio::time::timeout(timeout, join_all(handles))
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                "Background workers did not stop within {}s, aborting them",
+                timeout.as_secs()
+            );
+            for abort in aborts {
+                abort.abort();
+            }
+        }
+    }
+}
+
+// the worker runs in its own task so a panic is caught as a `JoinError`; aborting the supervising
+// task drops this and takes the worker down with it
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Future for AbortOnDrop<T> {
+    type Output = <JoinHandle<T> as Future>::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+async fn join_all(handles: Vec<JoinHandle<()>>) {
+    for handle in handles {
+        let _ = handle.await;
+    }
+}
+
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .map(|message| format!("panicked: {message}"))
+        .unwrap_or_else(|| "panicked".to_string())
+}
+
+fn update(statuses: &Statuses, name: &str, f: impl FnOnce(&mut WorkerStatus)) {
+    let mut statuses = statuses.lock().unwrap_or_else(|e| e.into_inner());
+    f(statuses
+        .entry(name.to_string())
+        .or_insert_with(|| WorkerStatus {
+            state: WorkerState::Running,
+            restarts: 0,
+            last_error: None,
+        }));
+}
@@ -0,0 +1,317 @@
+// drives the `blogrs-admin` binary against a temporary sqlite database
+mod common;
+
+use std::{
+    io::Write,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+    sync::Arc,
+};
+
+use axum::http::StatusCode;
+use sqlx::SqlitePool;
+
+use blogrs::{repository::SqliteRepository, AppState};
+use common::{test_config, TestApp, PASSWORD};
+
+struct Database {
+    path: PathBuf,
+}
+
+impl Database {
+    fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("blogrs-admin-{}.db", uuid::Uuid::new_v4()));
+        Self { path }
+    }
+
+    fn url(&self) -> String {
+        format!("sqlite://{}", self.path.display())
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_blogrs-admin"));
+        command
+            .args(args)
+            .env("BLOGRS_CONFIG", "/dev/null")
+            .env("DATABASE_URL", self.url())
+            .env_remove("JWT_SECRET")
+            .env_remove("BLOGRS_ADMIN_PASSWORD");
+        command
+    }
+
+    fn admin(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    fn admin_with_password(&self, args: &[&str], password: &str) -> Output {
+        self.command(args)
+            .env("BLOGRS_ADMIN_PASSWORD", password)
+            .output()
+            .unwrap()
+    }
+
+    // runs the command and returns its stdout, failing the test if it exits with an error
+    fn ok(&self, args: &[&str]) -> String {
+        let output = self.admin(args);
+        assert!(
+            output.status.success(),
+            "{args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    async fn app(&self) -> TestApp {
+        let pool = SqlitePool::connect(&self.url()).await.unwrap();
+        // the cli writes from another process, which sqlite has no way to tell the cache about
+        let mut config = test_config();
+        config.post_cache.enabled = false;
+        let state = AppState::new(config, SqliteRepository::new(pool));
+        TestApp::from_state(Arc::new(state))
+    }
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[tokio::test]
+async fn migrate_check_reports_pending_migrations() {
+    let db = Database::new();
+    db.ok(&["migrate", "run"]);
+    assert!(db.ok(&["migrate", "check"]).contains("up to date"));
+
+    let pool = SqlitePool::connect(&db.url()).await.unwrap();
+    sqlx::query("DELETE FROM _sqlx_migrations WHERE version = 4")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let output = db.admin(&["migrate", "check"]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("4/add users is admin"));
+}
+
+#[tokio::test]
+async fn created_users_can_log_in() {
+    let db = Database::new();
+    db.ok(&["migrate", "run"]);
+
+    let output = db.admin_with_password(
+        &[
+            "user",
+            "create",
+            "--username",
+            "root",
+            "--email",
+            "Root@Example.com",
+            "--admin",
+        ],
+        PASSWORD,
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("created admin root (root@example.com)"));
+
+    let output = db.admin_with_password(
+        &[
+            "user",
+            "create",
+            "--username",
+            "root",
+            "--email",
+            "other@example.com",
+        ],
+        PASSWORD,
+    );
+    assert!(!output.status.success());
+
+    // the password is not accepted as an argument
+    let output = db.admin(&[
+        "user",
+        "create",
+        "--username",
+        "other",
+        "--email",
+        "other@example.com",
+        "--password",
+        PASSWORD,
+    ]);
+    assert!(!output.status.success());
+
+    let app = db.app().await;
+    let response = app.login("root").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let token = response.body["token"].as_str().unwrap();
+    let response = app.get("/api/auth/current_user", Some(token)).await;
+    assert_eq!(response.body["data"]["user"]["is_admin"], true);
+}
+
+#[tokio::test]
+async fn reset_password_replaces_the_old_one() {
+    let db = Database::new();
+    db.ok(&["migrate", "run"]);
+    let app = db.app().await;
+    app.signup("alice").await;
+
+    // read from stdin when it is not a terminal
+    let mut child = db
+        .command(&["user", "reset-password", "--email", "alice@example.com"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"new-password\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert_eq!(app.login("alice").await.status, StatusCode::BAD_REQUEST);
+    let response = app
+        .post(
+            "/api/auth/login",
+            None,
+            serde_json::json!({"email": "alice@example.com", "password": "new-password"}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let output = db.admin_with_password(
+        &["user", "reset-password", "--email", "nobody@example.com"],
+        "x",
+    );
+    assert!(!output.status.success());
+}
+
+#[tokio::test]
+async fn posts_can_be_listed_reassigned_and_deleted() {
+    let db = Database::new();
+    db.ok(&["migrate", "run"]);
+    let stdout = db.ok(&["category", "seed"]);
+    assert!(stdout.contains("created General with id 1"));
+    let stdout = db.ok(&["category", "seed", "General", "Rust"]);
+    assert!(stdout.contains("General already exists"));
+    assert!(stdout.contains("created Rust with id 2"));
+
+    let app = db.app().await;
+    let alice = app.signup("alice").await;
+    app.signup("bob").await;
+    app.create_post(&alice, "hello").await;
+
+    let stdout = db.ok(&["post", "list"]);
+    assert!(stdout
+        .lines()
+        .any(|line| line.starts_with("1\thello\t1\t1\t")));
+
+    db.ok(&["post", "reassign", "hello", "--to", "bob@example.com"]);
+    let response = app.get("/api/post/hello", None).await;
+    assert_eq!(response.body["data"]["user_id"], 2);
+
+    db.ok(&["post", "delete", "hello"]);
+    let response = app.get("/api/post/hello", None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let output = db.admin(&["post", "delete", "hello"]);
+    assert!(!output.status.success());
+}
+
+#[tokio::test]
+async fn writes_are_audited_as_blogrs_admin() {
+    let db = Database::new();
+    db.ok(&["migrate", "run"]);
+    db.ok(&["category", "seed"]);
+    let app = db.app().await;
+    let admin = app.signup_admin("admin").await;
+    let alice = app.signup("alice").await;
+    app.create_post(&alice, "hello").await;
+
+    db.ok(&["post", "reassign", "hello", "--to", "admin@example.com"]);
+    db.admin_with_password(
+        &["user", "reset-password", "--email", "alice@example.com"],
+        "new-password",
+    );
+    db.ok(&["post", "delete", "hello"]);
+
+    let response = app.get("/api/admin/audit", Some(&admin)).await;
+    let entries: Vec<_> = response.body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry["user_agent"] == "blogrs-admin")
+        .collect();
+    let actions: Vec<_> = entries
+        .iter()
+        .map(|entry| entry["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        actions,
+        [
+            "post.delete",
+            "user.password_reset",
+            "post.reassign",
+            "category.create"
+        ]
+    );
+    assert!(entries.iter().all(|entry| entry["actor_id"].is_null()));
+    assert_eq!(entries[2]["before"]["user_id"], 2);
+    assert_eq!(entries[2]["after"]["user_id"], 1);
+    assert_eq!(entries[1]["after"]["username"], "alice");
+    assert!(entries[1]["after"].get("password").is_none());
+    assert_eq!(entries[3]["after"]["name"], "General");
+}
+
+#[tokio::test]
+async fn fixtures_can_be_loaded_repeatedly() {
+    let db = Database::new();
+    db.ok(&["migrate", "run"]);
+
+    let stdout = db.ok(&["fixtures", "load", "seed/demo.yaml"]);
+    assert!(stdout.contains("created 2 users, 2 categories and 2 posts"));
+    let stdout = db.ok(&["fixtures", "load", "seed/demo.yaml"]);
+    assert!(stdout.contains("created 0 users, 0 categories and 0 posts"));
+
+    let output = db.admin(&["fixtures", "load", "seed/missing.yaml"]);
+    assert!(!output.status.success());
+
+    let app = db.app().await;
+    let response = app.get("/api/post/welcome", None).await;
+    assert_eq!(response.body["data"]["category_id"], 1);
+}
+
+#[tokio::test]
+async fn backups_can_be_restored_into_another_database() {
+    let source = Database::new();
+    source.ok(&["migrate", "run"]);
+    source.ok(&["fixtures", "load", "seed/demo.yaml"]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("backup.tar.gz");
+    let archive = archive.to_str().unwrap();
+    let stdout = source.ok(&["backup", "create", "--output", archive]);
+    assert!(stdout.contains("posts.ndjson: 2 records"));
+
+    let target = Database::new();
+    target.ok(&["migrate", "run"]);
+    let stdout = target.ok(&["backup", "restore", archive]);
+    assert!(stdout.contains("users.ndjson: 2 records"));
+    // only into an empty database
+    let output = target.admin(&["backup", "restore", archive]);
+    assert!(!output.status.success());
+
+    let app = target.app().await;
+    assert_eq!(app.login("alice").await.status, StatusCode::OK);
+    let response = app.get("/api/post/hello-rust", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+}
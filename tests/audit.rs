@@ -0,0 +1,236 @@
+mod common;
+
+use std::net::SocketAddr;
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, Method, Request, StatusCode},
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use blogrs::repository::{Actor, CategoryRepository, MemoryRepository};
+use common::{TestApp, PASSWORD};
+
+async fn audit_log(app: &TestApp, token: &str, query: &str) -> Vec<Value> {
+    let response = app
+        .get(&format!("/api/admin/audit{query}"), Some(token))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+    response.body["data"].as_array().unwrap().clone()
+}
+
+fn actions(entries: &[Value]) -> Vec<&str> {
+    entries
+        .iter()
+        .map(|entry| entry["action"].as_str().unwrap())
+        .collect()
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn every_mutating_action_is_recorded(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let admin = app.signup_admin("admin").await;
+
+    assert_eq!(app.register("alice").await.status, StatusCode::OK);
+    let login = Request::builder()
+        .method(Method::POST)
+        .uri("/api/auth/login")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::USER_AGENT, "curl/8.5.0")
+        .extension(ConnectInfo(SocketAddr::from(([203, 0, 113, 7], 4000))))
+        .body(Body::from(
+            json!({ "email": "alice@example.com", "password": PASSWORD }).to_string(),
+        ))
+        .unwrap();
+    let token = app.send(login).await.body["token"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let alice = app.state.users.find_by_username("alice").await.unwrap();
+    let alice = alice.unwrap().id;
+    let alice_id = alice.to_string();
+
+    app.create_post(&token, "hello").await;
+    app.patch(
+        "/api/post/update/hello",
+        Some(&token),
+        json!({ "title": "Renamed" }),
+    )
+    .await;
+    app.delete("/api/post/delete/hello", Some(&token)).await;
+    app.post("/api/auth/logout", Some(&token), json!({})).await;
+
+    let entries = audit_log(&app, &admin, &format!("?actor_id={alice}")).await;
+    assert_eq!(
+        actions(&entries),
+        [
+            "user.logout",
+            "post.delete",
+            "post.update",
+            "post.create",
+            "user.login"
+        ]
+    );
+
+    let login = &entries[4];
+    assert_eq!(login["ip"], "203.0.113.7");
+    assert_eq!(login["user_agent"], "curl/8.5.0");
+    assert_eq!(login["target_type"], "user");
+    assert_eq!(login["target_id"], alice_id.as_str());
+
+    let update = &entries[2];
+    assert_eq!(update["before"]["title"], "Title of hello");
+    assert_eq!(update["after"]["title"], "Renamed");
+
+    // what the deleted post contained
+    let delete = &entries[1];
+    assert_eq!(delete["target_type"], "post");
+    assert_eq!(delete["before"]["content"], "Some content");
+    assert_eq!(delete["after"], Value::Null);
+
+    // the registration was anonymous
+    let entries = audit_log(&app, &admin, "?action=user.register").await;
+    let register = entries
+        .iter()
+        .find(|entry| entry["target_id"] == alice_id.as_str())
+        .unwrap();
+    assert_eq!(register["actor_id"], Value::Null);
+    assert_eq!(register["after"]["username"], "alice");
+    assert!(register["after"].get("password").is_none());
+}
+
+// the writes only `blogrs-admin` performs
+#[sqlx::test(fixtures("categories"))]
+async fn operator_writes_are_recorded(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let admin = app.signup_admin("admin").await;
+    let token = app.signup("alice").await;
+    app.create_post(&token, "hello").await;
+    let actor = Actor::tool("blogrs-admin");
+
+    app.state.posts.reassign("hello", 1, &actor).await.unwrap();
+    app.state
+        .users
+        .set_password("alice@example.com", "hash", &actor)
+        .await
+        .unwrap();
+    app.state.categories.create("Go", &actor).await.unwrap();
+
+    let entries = audit_log(&app, &admin, "").await;
+    assert_eq!(
+        actions(&entries[..3]),
+        ["category.create", "user.password_reset", "post.reassign"]
+    );
+    assert!(entries[..3]
+        .iter()
+        .all(|entry| entry["user_agent"] == "blogrs-admin" && entry["actor_id"].is_null()));
+    assert_eq!(entries[0]["after"]["name"], "Go");
+    assert!(entries[1]["after"].get("password").is_none());
+    assert_eq!(entries[2]["before"]["user_id"], 2);
+    assert_eq!(entries[2]["after"]["user_id"], 1);
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn failed_writes_leave_no_entry(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let admin = app.signup_admin("admin").await;
+    let token = app.signup("alice").await;
+    app.create_post(&token, "hello").await;
+
+    let response = app.create_post(&token, "hello").await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    let response = app
+        .patch(
+            "/api/post/update/hello",
+            Some(&token),
+            json!({ "category_id": 999 }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let entries = audit_log(&app, &admin, "?target_type=post").await;
+    assert_eq!(actions(&entries), ["post.create"]);
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn the_log_is_filtered_and_paginated(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let admin = app.signup_admin("admin").await;
+    let token = app.signup("alice").await;
+    for slug in ["one", "two", "three"] {
+        app.create_post(&token, slug).await;
+    }
+
+    let entries = audit_log(&app, &admin, "?action=post.create&limit=2").await;
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["after"]["slug"], "three");
+    let entries = audit_log(&app, &admin, "?action=post.create&limit=2&page=2").await;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["after"]["slug"], "one");
+
+    let post_id = entries[0]["target_id"].as_str().unwrap().to_string();
+    let entries = audit_log(
+        &app,
+        &admin,
+        &format!("?target_type=post&target_id={post_id}"),
+    )
+    .await;
+    assert_eq!(entries.len(), 1);
+
+    let entries = audit_log(&app, &admin, "?since=2999-01-01T00:00:00Z").await;
+    assert!(entries.is_empty());
+    let entries = audit_log(&app, &admin, "?until=2999-01-01T00:00:00Z&limit=100").await;
+    // the registrations and logins of the admin and alice, and the three posts
+    assert_eq!(entries.len(), 7);
+
+    let response = app
+        .get("/api/admin/audit?since=yesterday", Some(&admin))
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn only_admins_can_read_the_log(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+
+    let response = app.get("/api/admin/audit", None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.get("/api/v1/admin/audit", Some(&token)).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert_eq!(response.code(), "AUTH_NOT_ADMIN");
+}
+
+#[tokio::test]
+async fn memory_and_sqlite_record_the_same() {
+    let repository = MemoryRepository::new();
+    repository
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+
+    for app in [TestApp::new(repository), TestApp::sqlite().await] {
+        let admin = app.signup_admin("admin").await;
+        let token = app.signup("alice").await;
+        app.create_post(&token, "hello").await;
+        app.patch(
+            "/api/post/update/hello",
+            Some(&token),
+            json!({ "title": "Renamed" }),
+        )
+        .await;
+        app.delete("/api/post/delete/hello", Some(&token)).await;
+
+        let entries = audit_log(&app, &admin, "?target_type=post").await;
+        assert_eq!(
+            actions(&entries),
+            ["post.delete", "post.update", "post.create"]
+        );
+        assert_eq!(entries[1]["before"]["title"], "Title of hello");
+        assert_eq!(entries[1]["after"]["title"], "Renamed");
+        assert_eq!(entries[0]["before"]["title"], "Renamed");
+    }
+}
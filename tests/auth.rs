@@ -0,0 +1,205 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use serde_json::json;
+use sqlx::PgPool;
+
+use common::TestApp;
+
+#[sqlx::test]
+async fn register_returns_user_without_password(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+
+    let response = app.register("alice").await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["status"], "success");
+    let user = &response.body["data"]["user"];
+    assert_eq!(user["username"], "alice");
+    assert_eq!(user["email"], "alice@example.com");
+    assert!(user.get("password").is_none());
+}
+
+#[sqlx::test]
+async fn register_lowercases_email(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+
+    let response = app
+        .post(
+            "/api/auth/register",
+            None,
+            json!({"username": "bob", "email": "Bob@Example.COM", "password": "pw"}),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["data"]["user"]["email"], "bob@example.com");
+}
+
+#[sqlx::test]
+async fn register_duplicate_user_conflicts(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    app.register("alice").await;
+
+    let response = app.register("alice").await;
+
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.body["status"], "fail");
+    assert_eq!(response.code(), "AUTH_USER_EXISTS");
+}
+
+#[sqlx::test]
+async fn login_returns_token_and_cookie(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    app.register("alice").await;
+
+    let response = app.login("alice").await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["status"], "success");
+    let token = response.body["token"].as_str().unwrap();
+    let cookie = response.headers[header::SET_COOKIE].to_str().unwrap();
+    assert!(cookie.starts_with(&format!("token={token}")));
+    assert!(cookie.contains("HttpOnly"));
+}
+
+#[sqlx::test]
+async fn login_with_wrong_password_fails(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    app.register("alice").await;
+
+    let response = app
+        .post(
+            "/api/auth/login",
+            None,
+            json!({"email": "alice@example.com", "password": "wrong"}),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.code(), "AUTH_INVALID_CREDENTIALS");
+}
+
+#[sqlx::test]
+async fn login_with_unknown_email_fails(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+
+    let response = app.login("nobody").await;
+
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.code(), "AUTH_INVALID_CREDENTIALS");
+}
+
+#[sqlx::test]
+async fn current_user_with_bearer_token(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+
+    let response = app.get("/api/auth/current_user", Some(&token)).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["data"]["user"]["username"], "alice");
+}
+
+#[sqlx::test]
+async fn current_user_with_cookie(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+
+    let request = Request::get("/api/auth/current_user")
+        .header(header::COOKIE, format!("token={token}"))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.send(request).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["data"]["user"]["username"], "alice");
+}
+
+#[sqlx::test]
+async fn cookie_takes_precedence_over_bearer_token(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+
+    let request = Request::get("/api/auth/current_user")
+        .header(header::COOKIE, "token=garbage")
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.send(request).await;
+
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "AUTH_INVALID_TOKEN");
+}
+
+#[sqlx::test]
+async fn guard_rejects_missing_token(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+
+    let response = app.get("/api/auth/current_user", None).await;
+
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.body["status"], "fail");
+    assert_eq!(response.code(), "AUTH_NOT_LOGGED_IN");
+}
+
+#[sqlx::test]
+async fn guard_rejects_invalid_token(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+
+    let response = app.get("/api/auth/current_user", Some("not-a-jwt")).await;
+
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "AUTH_INVALID_TOKEN");
+}
+
+#[sqlx::test]
+async fn guard_rejects_token_of_deleted_user(pool: PgPool) {
+    let app = TestApp::postgres(pool.clone());
+    let token = app.signup("alice").await;
+    sqlx::query("DELETE FROM users WHERE username = 'alice'")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let response = app.get("/api/auth/current_user", Some(&token)).await;
+
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "AUTH_USER_NOT_FOUND");
+}
+
+#[sqlx::test]
+async fn logout_clears_cookie(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+
+    let response = app
+        .request(
+            axum::http::Method::POST,
+            "/api/auth/logout",
+            Some(&token),
+            None,
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["status"], "success");
+    let cookie = response.headers[header::SET_COOKIE].to_str().unwrap();
+    assert!(cookie.starts_with("token=;"));
+    assert!(cookie.contains("Max-Age=-3600"));
+}
+
+#[sqlx::test]
+async fn logout_requires_authentication(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+
+    let response = app
+        .request(axum::http::Method::POST, "/api/auth/logout", None, None)
+        .await;
+
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "AUTH_NOT_LOGGED_IN");
+}
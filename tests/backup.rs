@@ -0,0 +1,320 @@
+// writing and restoring archives with `blogrs::backup`
+mod common;
+
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use axum::http::StatusCode;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use blogrs::{
+    backup::{self, BackupError},
+    repository::{Actor, CategoryRepository, MemoryRepository},
+};
+use common::TestApp;
+
+struct TempDir(tempfile::TempDir);
+
+impl TempDir {
+    fn new() -> Self {
+        Self(tempfile::tempdir().unwrap())
+    }
+
+    fn archive(&self) -> PathBuf {
+        self.0.path().join("backup.tar.gz")
+    }
+}
+
+// rewrites the archive at `path` entry by entry, letting `edit` change each entry's content
+fn rewrite(path: &Path, edit: impl Fn(&str, Vec<u8>) -> Vec<u8>) {
+    let mut entries = Vec::new();
+    let mut archive = tar::Archive::new(GzDecoder::new(std::fs::File::open(path).unwrap()));
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let name = entry.path().unwrap().to_string_lossy().into_owned();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content).unwrap();
+        entries.push((name, content));
+    }
+
+    let file = std::fs::File::create(path).unwrap();
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    for (name, content) in entries {
+        let content = edit(&name, content);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, &name, content.as_slice())
+            .unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap();
+}
+
+// like `rewrite` for a single entry, updating its checksum and size in the manifest
+fn rewrite_entry(path: &Path, entry: &str, edit: impl Fn(Vec<u8>) -> Vec<u8>) {
+    let edited = std::cell::RefCell::new(Vec::new());
+    rewrite(path, |name, content| {
+        if name != entry {
+            return content;
+        }
+        edited.replace(edit(content));
+        edited.borrow().clone()
+    });
+    rewrite(path, |name, content| {
+        if name != "manifest.json" {
+            return content;
+        }
+        let edited = edited.borrow();
+        let mut manifest: Value = serde_json::from_slice(&content).unwrap();
+        for manifest_entry in manifest["entries"].as_array_mut().unwrap() {
+            if manifest_entry["name"] == entry {
+                let digest = Sha256::digest(edited.as_slice());
+                manifest_entry["sha256"] = digest
+                    .iter()
+                    .map(|byte| format!("{byte:02x}"))
+                    .collect::<String>()
+                    .into();
+                manifest_entry["bytes"] = edited.len().into();
+            }
+        }
+        serde_json::to_vec(&manifest).unwrap()
+    });
+}
+
+async fn source(pool: &PgPool) -> TestApp {
+    let app = TestApp::postgres(pool.clone());
+    let token = app.signup("alice").await;
+    app.create_post(&token, "hello").await;
+    app
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn backups_restore_with_remapped_ids(pool: PgPool) {
+    // leave gaps in the ids so the restored rows get different ones
+    let app = TestApp::postgres(pool.clone());
+    app.signup("ghost").await;
+    sqlx::query("DELETE FROM users WHERE username = 'ghost'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let token = app.signup("alice").await;
+    app.create_post(&token, "hello").await;
+    sqlx::query("UPDATE post SET category_id = 2")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("DELETE FROM category WHERE id = 1")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let original = app.get("/api/post/hello", None).await.body["data"].clone();
+
+    let dir = TempDir::new();
+    let manifest = backup::create(&app.state, &dir.archive()).await.unwrap();
+    assert_eq!(manifest.version, backup::VERSION);
+    let records = manifest
+        .entries
+        .iter()
+        .map(|entry| (entry.name.as_str(), entry.records))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        records,
+        [
+            ("users.ndjson", 1),
+            ("categories.ndjson", 1),
+            ("posts.ndjson", 1)
+        ]
+    );
+
+    let restored = TestApp::memory();
+    backup::restore(&restored.state, &dir.archive())
+        .await
+        .unwrap();
+
+    // the password hash came along
+    assert_eq!(restored.login("alice").await.status, StatusCode::OK);
+    let post = restored.get("/api/post/hello", None).await.body["data"].clone();
+    assert_eq!(post["user_id"], 1);
+    assert_eq!(post["category_id"], 1);
+    assert_eq!(post["title"], original["title"]);
+    assert_eq!(post["created_at"], original["created_at"]);
+    assert_eq!(post["updated_at"], original["updated_at"]);
+    assert_eq!(post["published_at"], original["published_at"]);
+    let categories = restored.state.categories.list().await.unwrap();
+    assert_eq!(categories[0].name, "Rust");
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn restore_refuses_a_database_with_data(pool: PgPool) {
+    let app = source(&pool).await;
+    let dir = TempDir::new();
+    backup::create(&app.state, &dir.archive()).await.unwrap();
+
+    let repository = MemoryRepository::new();
+    repository
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+    let restored = TestApp::new(repository);
+    let result = backup::restore(&restored.state, &dir.archive()).await;
+    assert!(matches!(result, Err(BackupError::NotEmpty)));
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn tampered_archives_are_rejected_before_writing(pool: PgPool) {
+    let app = source(&pool).await;
+    let dir = TempDir::new();
+    backup::create(&app.state, &dir.archive()).await.unwrap();
+
+    rewrite(&dir.archive(), |name, content| {
+        if name != "posts.ndjson" {
+            return content;
+        }
+        String::from_utf8(content)
+            .unwrap()
+            .replace("Some content", "Other content")
+            .into_bytes()
+    });
+
+    let restored = TestApp::memory();
+    let error = backup::restore(&restored.state, &dir.archive())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid backup: posts.ndjson does not match its checksum"
+    );
+    // users come first in the archive but were not restored either
+    assert!(restored
+        .state
+        .users
+        .find_by_username("alice")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn newer_versions_are_rejected(pool: PgPool) {
+    let app = source(&pool).await;
+    let dir = TempDir::new();
+    backup::create(&app.state, &dir.archive()).await.unwrap();
+
+    rewrite(&dir.archive(), |name, content| {
+        if name != "manifest.json" {
+            return content;
+        }
+        let mut manifest: serde_json::Value = serde_json::from_slice(&content).unwrap();
+        manifest["version"] = (backup::VERSION + 1).into();
+        serde_json::to_vec(&manifest).unwrap()
+    });
+
+    let result = backup::restore(&TestApp::memory().state, &dir.archive()).await;
+    assert!(matches!(result, Err(BackupError::Invalid(_))));
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn posts_from_before_the_status_restore_as_published(pool: PgPool) {
+    let app = source(&pool).await;
+    let dir = TempDir::new();
+    backup::create(&app.state, &dir.archive()).await.unwrap();
+
+    // what version 1 wrote: the same posts without a status
+    rewrite_entry(&dir.archive(), "posts.ndjson", |content| {
+        let mut post: Value = serde_json::from_slice(&content).unwrap();
+        let fields = post.as_object_mut().unwrap();
+        fields.remove("status");
+        fields.remove("published_at");
+        let mut content = serde_json::to_vec(&post).unwrap();
+        content.push(b'\n');
+        content
+    });
+    rewrite(&dir.archive(), |name, content| {
+        if name != "manifest.json" {
+            return content;
+        }
+        let mut manifest: Value = serde_json::from_slice(&content).unwrap();
+        manifest["version"] = 1.into();
+        serde_json::to_vec(&manifest).unwrap()
+    });
+
+    let restored = TestApp::memory();
+    backup::restore(&restored.state, &dir.archive())
+        .await
+        .unwrap();
+
+    let post = restored.get("/api/post/hello", None).await.body["data"].clone();
+    assert_eq!(post["status"], "published");
+    assert_eq!(post["published_at"], post["created_at"]);
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn exports_read_one_snapshot(pool: PgPool) {
+    let app = source(&pool).await;
+
+    let mut export = app.state.backups.begin_export().await.unwrap();
+    assert_eq!(export.users(0, 10).await.unwrap().len(), 1);
+
+    // written while the export runs
+    let token = app.signup("bob").await;
+    app.create_post(&token, "late").await;
+
+    assert_eq!(export.users(0, 10).await.unwrap().len(), 1);
+    let posts = export.posts(0, 10).await.unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].slug, "hello");
+}
+
+#[sqlx::test]
+async fn failed_restores_leave_the_database_empty(pool: PgPool) {
+    let repository = MemoryRepository::new();
+    repository
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+    let app = TestApp::new(repository);
+    let token = app.signup("alice").await;
+    app.create_post(&token, "hello").await;
+
+    let dir = TempDir::new();
+    backup::create(&app.state, &dir.archive()).await.unwrap();
+    let valid = dir.0.path().join("valid.tar.gz");
+    std::fs::copy(dir.archive(), &valid).unwrap();
+
+    // passes the checksums, but fails after the users and categories were written
+    rewrite_entry(&dir.archive(), "posts.ndjson", |content| {
+        let mut post: Value = serde_json::from_slice(&content).unwrap();
+        post["user_id"] = 999.into();
+        let mut content = serde_json::to_vec(&post).unwrap();
+        content.push(b'\n');
+        content
+    });
+
+    let restored = TestApp::postgres(pool.clone());
+    let error = backup::restore(&restored.state, &dir.archive())
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("missing user 999"), "{error}");
+    for table in ["users", "category", "post"] {
+        let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {table}"))
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 0, "{table}");
+    }
+
+    // so it can simply be run again
+    backup::restore(&restored.state, &valid).await.unwrap();
+    assert_eq!(restored.login("alice").await.status, StatusCode::OK);
+    assert_eq!(
+        restored.get("/api/post/hello", None).await.status,
+        StatusCode::OK
+    );
+}
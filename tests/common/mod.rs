@@ -0,0 +1,230 @@
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    Router,
+};
+use serde_json::Value;
+use sqlx::{sqlite::SqlitePoolOptions, PgPool, SqlitePool};
+use tower::ServiceExt;
+
+use blogrs::{
+    app,
+    config::Config,
+    password::hash_password,
+    repository::{
+        sqlite, Actor, MemoryRepository, NewUser, PgRepository, Repository, SqliteRepository,
+    },
+    AppState,
+};
+
+pub const PASSWORD: &str = "password123";
+
+pub fn test_config() -> Config {
+    let mut config = Config::default();
+    config.auth.jwt_secret = "test-secret".to_string();
+    // requests without a peer address would all share one bucket; see tests/rate_limit.rs
+    config.rate_limit.enabled = false;
+    config
+}
+
+// a fresh, migrated in-memory sqlite database
+pub async fn sqlite_pool() -> SqlitePool {
+    // every connection to `sqlite::memory:` opens its own database, so keep exactly one
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    sqlite::MIGRATOR.run(&pool).await.unwrap();
+    pool
+}
+
+pub struct TestApp {
+    pub state: Arc<AppState>,
+    router: Router,
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Value,
+}
+
+impl TestApp {
+    pub fn new<R: Repository>(repository: R) -> Self {
+        Self::with_config(test_config(), repository)
+    }
+
+    pub fn with_config<R: Repository>(config: Config, repository: R) -> Self {
+        Self::from_state(Arc::new(AppState::new(config, repository)))
+    }
+
+    pub fn from_state(app_state: Arc<AppState>) -> Self {
+        Self {
+            router: app(app_state.clone()),
+            state: app_state,
+        }
+    }
+
+    pub fn postgres(pool: PgPool) -> Self {
+        Self::new(PgRepository::new(pool))
+    }
+
+    pub fn memory() -> Self {
+        Self::new(MemoryRepository::new())
+    }
+
+    // a fresh in-memory sqlite database with the same categories as `fixtures/categories.sql`
+    pub async fn sqlite() -> Self {
+        let pool = sqlite_pool().await;
+        sqlx::query("INSERT INTO category (id, name) VALUES (1, 'General'), (2, 'Rust')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        Self::new(SqliteRepository::new(pool))
+    }
+
+    pub async fn send(&self, request: Request<Body>) -> TestResponse {
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()))
+        };
+
+        TestResponse {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    pub async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> TestResponse {
+        let mut builder = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            builder = builder.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let request = match body {
+            Some(body) => builder
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => builder.body(Body::empty()),
+        };
+
+        self.send(request.unwrap()).await
+    }
+
+    pub async fn get(&self, uri: &str, token: Option<&str>) -> TestResponse {
+        self.request(Method::GET, uri, token, None).await
+    }
+
+    pub async fn post(&self, uri: &str, token: Option<&str>, body: Value) -> TestResponse {
+        self.request(Method::POST, uri, token, Some(body)).await
+    }
+
+    pub async fn patch(&self, uri: &str, token: Option<&str>, body: Value) -> TestResponse {
+        self.request(Method::PATCH, uri, token, Some(body)).await
+    }
+
+    pub async fn put(&self, uri: &str, token: Option<&str>, body: Value) -> TestResponse {
+        self.request(Method::PUT, uri, token, Some(body)).await
+    }
+
+    pub async fn delete(&self, uri: &str, token: Option<&str>) -> TestResponse {
+        self.request(Method::DELETE, uri, token, None).await
+    }
+
+    pub async fn register(&self, username: &str) -> TestResponse {
+        self.post(
+            "/api/auth/register",
+            None,
+            serde_json::json!({
+                "username": username,
+                "email": format!("{username}@example.com"),
+                "password": PASSWORD,
+            }),
+        )
+        .await
+    }
+
+    pub async fn login(&self, username: &str) -> TestResponse {
+        self.post(
+            "/api/auth/login",
+            None,
+            serde_json::json!({
+                "email": format!("{username}@example.com"),
+                "password": PASSWORD,
+            }),
+        )
+        .await
+    }
+
+    // registers and logs in, returning the bearer token
+    pub async fn signup(&self, username: &str) -> String {
+        assert_eq!(self.register(username).await.status, StatusCode::OK);
+        let response = self.login(username).await;
+        assert_eq!(response.status, StatusCode::OK);
+        response.body["token"].as_str().unwrap().to_string()
+    }
+
+    // admins can only be created outside the api
+    pub async fn signup_admin(&self, username: &str) -> String {
+        self.state
+            .users
+            .create(
+                NewUser {
+                    username: username.to_string(),
+                    email: format!("{username}@example.com"),
+                    password: hash_password(PASSWORD).unwrap(),
+                    is_admin: true,
+                },
+                &Actor::default(),
+            )
+            .await
+            .unwrap();
+        let response = self.login(username).await;
+        assert_eq!(response.status, StatusCode::OK);
+        response.body["token"].as_str().unwrap().to_string()
+    }
+
+    // a published post, so it shows up in the public listing
+    pub async fn create_post(&self, token: &str, slug: &str) -> TestResponse {
+        self.post(
+            "/api/post/create",
+            Some(token),
+            serde_json::json!({
+                "title": format!("Title of {slug}"),
+                "slug": slug,
+                "excerpt": "An excerpt",
+                "content": "Some content",
+                "status": "published",
+            }),
+        )
+        .await
+    }
+}
+
+impl TestResponse {
+    pub fn code(&self) -> &str {
+        self.body["code"].as_str().unwrap_or_default()
+    }
+}
@@ -0,0 +1,245 @@
+// layering and validation of `blogrs::config::Config`
+use std::path::Path;
+
+use blogrs::config::{Config, ConfigError};
+
+fn env(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+fn load(contents: &str, overrides: &[(&str, &str)]) -> Result<Config, ConfigError> {
+    Config::from_sources(contents, Some(Path::new("blogrs.toml")), env(overrides))
+}
+
+fn problems(contents: &str) -> Vec<String> {
+    match load(contents, &[("JWT_SECRET", "secret")]) {
+        Err(ConfigError::Invalid(problems)) => problems,
+        other => panic!("expected validation problems for {contents:?}, got {other:?}"),
+    }
+}
+
+#[test]
+fn the_environment_overrides_the_file() {
+    let file = r#"
+        [server]
+        bind_addr = "127.0.0.1:9000"
+
+        [auth]
+        jwt_secret = "from-the-file"
+        token_expires_in_hours = 12
+
+        [pagination]
+        default_page_size = 5
+    "#;
+    let config = load(
+        file,
+        &[
+            ("BLOGRS__AUTH__TOKEN_EXPIRES_IN_HOURS", "24"),
+            (
+                "BLOGRS__CORS__ALLOWED_ORIGINS",
+                r#"["https://example.com"]"#,
+            ),
+            ("BLOGRS__POST_CACHE__ENABLED", "false"),
+            ("UNRELATED", "1"),
+        ],
+    )
+    .unwrap();
+
+    assert_eq!(config.server.bind_addr, "127.0.0.1:9000");
+    assert_eq!(config.auth.jwt_secret, "from-the-file");
+    assert_eq!(config.auth.token_expires_in_hours, 24);
+    assert_eq!(config.pagination.default_page_size, 5);
+    // untouched settings keep their defaults
+    assert_eq!(config.pagination.max_page_size, 100);
+    assert_eq!(config.cors.allowed_origins, ["https://example.com"]);
+    assert!(!config.post_cache.enabled);
+}
+
+#[test]
+fn string_settings_keep_values_that_look_like_literals() {
+    for value in ["123456", "true", "1.5", "[1]"] {
+        let config = load("", &[("BLOGRS__AUTH__JWT_SECRET", value)]).unwrap();
+        assert_eq!(config.auth.jwt_secret, value);
+    }
+
+    // a section without defaults still gets its numbers parsed
+    let file = r#"
+        [rate_limit.auth]
+        key = "ip"
+        burst = 5
+        per_minute = 5
+    "#;
+    let config = load(
+        file,
+        &[
+            ("JWT_SECRET", "secret"),
+            ("BLOGRS__RATE_LIMIT__AUTH__BURST", "7"),
+        ],
+    )
+    .unwrap();
+    assert_eq!(config.rate_limit.auth.burst, 7);
+}
+
+#[test]
+fn prefixed_variables_win_over_aliases() {
+    let expected = "from-the-prefix";
+    let alias = ("JWT_SECRET", "from-the-alias");
+    let prefixed = ("BLOGRS__AUTH__JWT_SECRET", expected);
+
+    // whatever order the environment lists them in
+    for overrides in [[alias, prefixed], [prefixed, alias]] {
+        let config = load("", &overrides).unwrap();
+        assert_eq!(config.auth.jwt_secret, expected);
+    }
+
+    let config = load(
+        "[server]\nbind_addr = \"127.0.0.1:1\"",
+        &[("BIND_ADDR", "127.0.0.1:2"), alias],
+    )
+    .unwrap();
+    assert_eq!(config.server.bind_addr, "127.0.0.1:2");
+}
+
+#[test]
+fn parse_errors_name_their_source() {
+    let error = load("[server", &[]).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .starts_with("invalid config file blogrs.toml"),
+        "{error}"
+    );
+
+    let error = load("", &[("BLOGRS__PAGINATION__DEFAULT_PAGE_SIZE", "many")]).unwrap_err();
+    assert!(matches!(error, ConfigError::Parse { .. }), "{error}");
+
+    let error = load("[nope]", &[]).unwrap_err();
+    assert!(matches!(error, ConfigError::Parse { .. }), "{error}");
+}
+
+#[test]
+fn a_missing_jwt_secret_is_reported() {
+    let Err(ConfigError::Invalid(problems)) = load("", &[]) else {
+        panic!("expected a validation error");
+    };
+    assert_eq!(problems, ["auth.jwt_secret (or JWT_SECRET) must be set"]);
+
+    // only the server needs it
+    let config = Config::default();
+    assert!(config.validate().is_ok());
+    assert!(config.validate_for_server().is_err());
+}
+
+#[test]
+fn every_invalid_setting_is_reported() {
+    let cases = [
+        ("[server]\nbind_addr = \"nope\"", "server.bind_addr `nope`"),
+        (
+            "[server]\nshutdown_timeout_secs = 0",
+            "server.shutdown_timeout_secs",
+        ),
+        ("[database]\nurl = \"mysql://localhost\"", "database.url"),
+        (
+            "[database]\nmax_connections = 0",
+            "database.max_connections",
+        ),
+        (
+            "[auth]\ntoken_expires_in_hours = 0",
+            "auth.token_expires_in_hours",
+        ),
+        (
+            "[auth]\ncookie_max_age_hours = 0",
+            "auth.cookie_max_age_hours",
+        ),
+        (
+            "[auth]\ncookie_same_site = \"none\"\ncookie_secure = false",
+            "requires auth.cookie_secure",
+        ),
+        (
+            "[pagination]\ndefault_page_size = 0",
+            "pagination.default_page_size",
+        ),
+        (
+            "[pagination]\ndefault_page_size = 10\nmax_page_size = 5",
+            "pagination.max_page_size",
+        ),
+        (
+            "[cors]\nallowed_origins = [\"bad\\norigin\"]",
+            "is not a valid origin",
+        ),
+        (
+            "[cors]\nallowed_origins = [\"*\", \"https://example.com\"]",
+            "cannot be combined",
+        ),
+        (
+            "[cors]\nallowed_origin_patterns = [\"(\"]",
+            "is not a valid regex",
+        ),
+        (
+            "[cors]\nallowed_methods = [\"GE T\"]",
+            "is not a valid method",
+        ),
+        (
+            "[cors]\nallowed_headers = [\"bad header\"]",
+            "cors.allowed_headers",
+        ),
+        (
+            "[cors]\nexpose_headers = [\"bad header\"]",
+            "cors.expose_headers",
+        ),
+        (
+            "[security_headers]\nreferrer_policy = \"bad\\nvalue\"",
+            "security_headers.referrer_policy",
+        ),
+        (
+            "[[security_headers.routes]]\npath = \"api\"",
+            "must start with /",
+        ),
+        (
+            "[security_headers]\nframe_options = \"ALLOW\"",
+            "must be DENY, SAMEORIGIN or empty",
+        ),
+        (
+            "[http_cache]\npublic_cache_control = \"bad\\nvalue\"",
+            "http_cache.public_cache_control",
+        ),
+        (
+            "[rate_limit]\ntrusted_proxies = [\"not-an-ip\"]",
+            "is not an ip address or CIDR range",
+        ),
+        (
+            "[rate_limit.auth]\nkey = \"ip\"\nburst = 0\nper_minute = 1",
+            "rate_limit.auth: burst and per_minute",
+        ),
+        (
+            "[database]\nurl = \"sqlite::memory:\"\n[rate_limit]\nstore = \"postgres\"",
+            "requires a postgres database",
+        ),
+        ("[jobs]\nworkers = 0", "jobs.workers"),
+        ("[jobs]\npoll_interval_ms = 0", "jobs.poll_interval_ms"),
+        (
+            "[jobs]\nbackoff_base_secs = 10\nbackoff_max_secs = 5",
+            "jobs.backoff_max_secs",
+        ),
+        ("[post_cache]\nttl_secs = 0", "post_cache.ttl_secs"),
+        ("[feature_flags]\nttl_secs = 0", "feature_flags.ttl_secs"),
+        ("[idempotency]\nttl_secs = 0", "idempotency.ttl_secs"),
+        ("[idempotency]\nlease_secs = 0", "idempotency.lease_secs"),
+        ("[log]\nfilter = \"blogrs=loud\"", "log.filter"),
+    ];
+
+    for (contents, expected) in cases {
+        let problems = problems(contents);
+        assert!(
+            problems.iter().any(|problem| problem.contains(expected)),
+            "{contents:?}: {problems:?}"
+        );
+    }
+
+    // all of them at once, not only the first
+    let problems = problems("[database]\nmax_connections = 0\n[jobs]\nworkers = 0");
+    assert_eq!(problems.len(), 2, "{problems:?}");
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request},
+};
+use blogrs::{config::CookieSameSite, repository::MemoryRepository};
+
+use common::{test_config, TestApp, TestResponse};
+
+const FRONTEND: &str = "https://blog.example.com";
+
+fn allow_list_app() -> TestApp {
+    let mut config = test_config();
+    config.cors.allowed_origins = vec![FRONTEND.to_string()];
+    config.cors.allowed_origin_patterns =
+        vec![r"https://pr-\d+\.preview\.example\.com".to_string()];
+    TestApp::with_config(config, MemoryRepository::new())
+}
+
+async fn preflight(app: &TestApp, origin: &str) -> TestResponse {
+    let request = Request::builder()
+        .method(Method::OPTIONS)
+        .uri("/api/post/create")
+        .header(header::ORIGIN, origin)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+        .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+        .body(Body::empty())
+        .unwrap();
+    app.send(request).await
+}
+
+async fn get_from(app: &TestApp, origin: &str) -> TestResponse {
+    let request = Request::builder()
+        .uri("/api/post")
+        .header(header::ORIGIN, origin)
+        .body(Body::empty())
+        .unwrap();
+    app.send(request).await
+}
+
+fn header_value(response: &TestResponse, name: header::HeaderName) -> Option<&str> {
+    response
+        .headers
+        .get(name)
+        .map(|value| value.to_str().unwrap())
+}
+
+#[tokio::test]
+async fn preflight_from_allowed_origin_allows_credentials() {
+    let app = allow_list_app();
+
+    let response = preflight(&app, FRONTEND).await;
+
+    assert_eq!(
+        header_value(&response, header::ACCESS_CONTROL_ALLOW_ORIGIN),
+        Some(FRONTEND)
+    );
+    assert_eq!(
+        header_value(&response, header::ACCESS_CONTROL_ALLOW_CREDENTIALS),
+        Some("true")
+    );
+    assert_eq!(
+        header_value(&response, header::ACCESS_CONTROL_MAX_AGE),
+        Some("3600")
+    );
+}
+
+#[tokio::test]
+async fn origins_matching_a_pattern_are_allowed() {
+    let app = allow_list_app();
+
+    let response = get_from(&app, "https://pr-42.preview.example.com").await;
+    assert_eq!(
+        header_value(&response, header::ACCESS_CONTROL_ALLOW_ORIGIN),
+        Some("https://pr-42.preview.example.com")
+    );
+
+    // patterns must match the whole origin
+    let response = get_from(&app, "https://pr-42.preview.example.com.evil.test").await;
+    assert_eq!(
+        header_value(&response, header::ACCESS_CONTROL_ALLOW_ORIGIN),
+        None
+    );
+}
+
+#[tokio::test]
+async fn unknown_origins_are_not_allowed() {
+    let app = allow_list_app();
+
+    let response = preflight(&app, "https://evil.test").await;
+
+    assert_eq!(
+        header_value(&response, header::ACCESS_CONTROL_ALLOW_ORIGIN),
+        None
+    );
+}
+
+#[tokio::test]
+async fn configured_headers_are_exposed() {
+    let app = allow_list_app();
+
+    let response = get_from(&app, FRONTEND).await;
+
+    assert_eq!(
+        header_value(&response, header::ACCESS_CONTROL_EXPOSE_HEADERS),
+        Some("x-request-id,etag,deprecation,sunset,link,idempotent-replayed")
+    );
+}
+
+#[tokio::test]
+async fn wildcard_origin_never_allows_credentials() {
+    let app = TestApp::memory();
+
+    let response = preflight(&app, FRONTEND).await;
+
+    assert_eq!(
+        header_value(&response, header::ACCESS_CONTROL_ALLOW_ORIGIN),
+        Some("*")
+    );
+    assert_eq!(
+        header_value(&response, header::ACCESS_CONTROL_ALLOW_CREDENTIALS),
+        None
+    );
+}
+
+#[tokio::test]
+async fn cross_site_cookie_policy_is_configurable() {
+    let mut config = test_config();
+    config.auth.cookie_same_site = CookieSameSite::None;
+    config.auth.cookie_secure = true;
+    let app = TestApp::with_config(config, MemoryRepository::new());
+    app.register("alice").await;
+
+    let response = app.login("alice").await;
+
+    let cookie = header_value(&response, header::SET_COOKIE).unwrap();
+    assert!(cookie.contains("SameSite=None"));
+    assert!(cookie.contains("Secure"));
+}
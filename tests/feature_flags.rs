@@ -0,0 +1,417 @@
+mod common;
+
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use axum::http::StatusCode;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+
+use blogrs::{
+    feature_flags::PgFeatureFlagBus,
+    notify::{self, PgNotifyListener},
+    post_cache::PgPostCacheBus,
+    repository::{Actor, FeatureFlagData, PgRepository},
+    AppState,
+};
+use common::{test_config, TestApp};
+
+async fn user_id(app: &TestApp, username: &str) -> i32 {
+    let user = app.state.users.find_by_username(username).await.unwrap();
+    user.unwrap().id
+}
+
+async fn flag_values(app: &TestApp, token: Option<&str>) -> Value {
+    let response = app.get("/api/feature_flags", token).await;
+    assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+    response.body["data"].clone()
+}
+
+// the same admin workflow on every backend
+async fn flags_are_managed_by_admins(app: TestApp) {
+    let admin = app.signup_admin("admin").await;
+    let token = app.signup("alice").await;
+    let alice = user_id(&app, "alice").await;
+
+    let response = app
+        .put("/api/admin/feature_flags/comments", Some(&token), json!({}))
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+    // created with the defaults, then only the given fields change
+    let response = app
+        .put("/api/admin/feature_flags/comments", Some(&admin), json!({}))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+    assert_eq!(response.body["data"]["enabled"], false);
+    assert_eq!(response.body["data"]["rollout_percentage"], 100);
+    let response = app
+        .put(
+            "/api/admin/feature_flags/comments",
+            Some(&admin),
+            json!({ "description": "Comments under posts", "rollout_percentage": 0 }),
+        )
+        .await;
+    assert_eq!(response.body["data"]["rollout_percentage"], 0);
+    let response = app
+        .put(
+            "/api/admin/feature_flags/comments",
+            Some(&admin),
+            json!({ "enabled": true }),
+        )
+        .await;
+    assert_eq!(response.body["data"]["description"], "Comments under posts");
+    assert_eq!(response.body["data"]["enabled"], true);
+    assert_eq!(response.body["data"]["rollout_percentage"], 0);
+
+    let response = app
+        .put(
+            &format!("/api/admin/feature_flags/comments/overrides/{alice}"),
+            Some(&admin),
+            json!({ "enabled": true }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+    assert_eq!(flag_values(&app, Some(&token)).await["comments"], true);
+    assert_eq!(flag_values(&app, Some(&admin)).await["comments"], false);
+    assert_eq!(flag_values(&app, None).await["comments"], false);
+
+    let response = app.get("/api/admin/feature_flags", Some(&admin)).await;
+    assert_eq!(
+        response.body["data"][0]["overrides"],
+        json!([{ "user_id": alice, "enabled": true }])
+    );
+
+    let uri = format!("/api/admin/feature_flags/comments/overrides/{alice}");
+    assert_eq!(app.delete(&uri, Some(&admin)).await.status, StatusCode::OK);
+    let response = app.delete(&uri, Some(&admin)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    assert_eq!(response.code(), "FEATURE_FLAG_OVERRIDE_NOT_FOUND");
+    assert_eq!(flag_values(&app, Some(&token)).await["comments"], false);
+
+    let response = app
+        .put(
+            "/api/admin/feature_flags/missing/overrides/1",
+            Some(&admin),
+            json!({ "enabled": true }),
+        )
+        .await;
+    assert_eq!(response.code(), "FEATURE_FLAG_NOT_FOUND");
+    let response = app
+        .put(
+            "/api/admin/feature_flags/comments/overrides/9999",
+            Some(&admin),
+            json!({ "enabled": true }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.code(), "FEATURE_FLAG_USER_NOT_FOUND");
+
+    let response = app
+        .delete("/api/admin/feature_flags/comments", Some(&admin))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(flag_values(&app, Some(&token)).await, json!({}));
+    let response = app
+        .delete("/api/admin/feature_flags/comments", Some(&admin))
+        .await;
+    assert_eq!(response.code(), "FEATURE_FLAG_NOT_FOUND");
+
+    // every change is recorded with the admin, newest first; the failed ones aren't
+    let admin_id = user_id(&app, "admin").await;
+    let response = app
+        .get("/api/admin/audit?target_type=feature_flag", Some(&admin))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+    let entries = response.body["data"].as_array().unwrap();
+    let actions: Vec<_> = entries
+        .iter()
+        .map(|entry| entry["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        actions,
+        [
+            "feature_flag.delete",
+            "feature_flag.override_delete",
+            "feature_flag.override_set",
+            "feature_flag.upsert",
+            "feature_flag.upsert",
+            "feature_flag.upsert",
+        ]
+    );
+    for entry in entries {
+        assert_eq!(entry["actor_id"], admin_id);
+        assert_eq!(entry["target_id"], "comments");
+    }
+    assert_eq!(entries[0]["before"]["enabled"], true);
+    assert_eq!(entries[0]["after"], Value::Null);
+    assert_eq!(entries[1]["before"]["user_id"], alice);
+    assert_eq!(entries[2]["before"], Value::Null);
+    assert_eq!(entries[2]["after"]["enabled"], true);
+    assert_eq!(entries[3]["before"]["enabled"], false);
+    assert_eq!(entries[3]["after"]["enabled"], true);
+    assert_eq!(entries[5]["before"], Value::Null);
+}
+
+#[sqlx::test]
+async fn postgres_flags_are_managed_by_admins(pool: PgPool) {
+    flags_are_managed_by_admins(TestApp::postgres(pool)).await;
+}
+
+#[tokio::test]
+async fn memory_and_sqlite_flags_are_managed_by_admins() {
+    flags_are_managed_by_admins(TestApp::memory()).await;
+    flags_are_managed_by_admins(TestApp::sqlite().await).await;
+}
+
+#[tokio::test]
+async fn invalid_flags_are_rejected() {
+    let app = TestApp::memory();
+    let admin = app.signup_admin("admin").await;
+
+    for name in ["New-Editor", "a%20b", &"x".repeat(101)] {
+        let response = app
+            .put(
+                &format!("/api/admin/feature_flags/{name}"),
+                Some(&admin),
+                json!({}),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST, "{name}");
+        assert_eq!(response.code(), "FEATURE_FLAG_INVALID");
+    }
+
+    let response = app
+        .put(
+            "/api/admin/feature_flags/search",
+            Some(&admin),
+            json!({ "rollout_percentage": 101 }),
+        )
+        .await;
+    assert_eq!(response.code(), "FEATURE_FLAG_INVALID");
+}
+
+// the users out of the first thousand ids that have the flag
+async fn enabled_users(app: &TestApp, name: &str) -> HashSet<i32> {
+    let mut users = HashSet::new();
+    for user_id in 1..=1000 {
+        let flags = app.state.feature_flags.for_user(Some(user_id)).await;
+        if flags.unwrap().is_enabled(name) {
+            users.insert(user_id);
+        }
+    }
+    users
+}
+
+#[tokio::test]
+async fn rollouts_pick_a_stable_share_of_users() {
+    let app = TestApp::memory();
+    let flags = &app.state.feature_flag_store;
+    let cache = &app.state.feature_flags;
+    let rollout = |rollout_percentage| FeatureFlagData {
+        enabled: Some(true),
+        rollout_percentage: Some(rollout_percentage),
+        ..Default::default()
+    };
+    let actor = Actor::default();
+
+    flags.upsert("search", rollout(25), &actor).await.unwrap();
+    cache.invalidate_local();
+    let quarter = enabled_users(&app, "search").await;
+    flags.upsert("search", rollout(50), &actor).await.unwrap();
+    cache.invalidate_local();
+    let half = enabled_users(&app, "search").await;
+
+    assert!((200..300).contains(&quarter.len()), "{}", quarter.len());
+    assert!((450..550).contains(&half.len()), "{}", half.len());
+    // growing a rollout keeps everyone who already had the flag
+    assert!(quarter.is_subset(&half));
+    // anonymous requests wait for the full rollout
+    assert!(!cache.for_user(None).await.unwrap().is_enabled("search"));
+
+    flags.upsert("search", rollout(100), &actor).await.unwrap();
+    flags.upsert("other", rollout(50), &actor).await.unwrap();
+    cache.invalidate_local();
+    assert!(cache.for_user(None).await.unwrap().is_enabled("search"));
+    // every flag picks its own users
+    assert_ne!(enabled_users(&app, "other").await, half);
+}
+
+#[tokio::test]
+async fn flags_are_cached_until_changed() {
+    let app = TestApp::memory();
+    let admin = app.signup_admin("admin").await;
+    app.put(
+        "/api/admin/feature_flags/comments",
+        Some(&admin),
+        json!({ "enabled": true }),
+    )
+    .await;
+    assert_eq!(flag_values(&app, None).await["comments"], true);
+
+    // a write that bypasses the api isn't seen before the ttl runs out
+    let flag = FeatureFlagData {
+        enabled: Some(false),
+        ..Default::default()
+    };
+    app.state
+        .feature_flag_store
+        .upsert("comments", flag, &Actor::default())
+        .await
+        .unwrap();
+    assert_eq!(flag_values(&app, None).await["comments"], true);
+
+    app.put(
+        "/api/admin/feature_flags/search",
+        Some(&admin),
+        json!({ "enabled": true }),
+    )
+    .await;
+    assert_eq!(
+        flag_values(&app, None).await,
+        json!({ "comments": false, "search": true })
+    );
+}
+
+fn replica(pool: &PgPool) -> TestApp {
+    let state = AppState::new(test_config(), PgRepository::new(pool.clone()))
+        .with_feature_flag_bus(Arc::new(PgFeatureFlagBus::new(pool.clone())))
+        .with_post_cache_bus(Arc::new(PgPostCacheBus::new(pool.clone())))
+        .with_notify_listener(PgNotifyListener::new(pool.clone()));
+    TestApp::from_state(Arc::new(state))
+}
+
+#[sqlx::test]
+async fn changes_on_one_replica_reach_the_others(pool: PgPool) {
+    let writer = replica(&pool);
+    let reader = replica(&pool);
+    let shutdown = CancellationToken::new();
+    tokio::spawn(notify::listen_worker(
+        reader.state.clone(),
+        shutdown.clone(),
+    ));
+
+    // waits until the listener is subscribed, toggling a flag until the reader follows
+    let mut following = false;
+    for attempt in 0..100 {
+        let enabled = attempt % 2 == 0;
+        let flag = FeatureFlagData {
+            enabled: Some(enabled),
+            ..Default::default()
+        };
+        writer
+            .state
+            .feature_flag_store
+            .upsert("probe", flag, &Actor::default())
+            .await
+            .unwrap();
+        writer.state.feature_flags.invalidate("probe").await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let flags = reader.state.feature_flags.for_user(None).await.unwrap();
+        if flags.is_enabled("probe") == enabled {
+            following = true;
+            break;
+        }
+    }
+    assert!(following);
+
+    assert_eq!(flag_values(&reader, None).await.get("comments"), None);
+    let admin = writer.signup_admin("admin").await;
+    writer
+        .put(
+            "/api/admin/feature_flags/comments",
+            Some(&admin),
+            json!({ "enabled": true }),
+        )
+        .await;
+
+    let mut comments = Value::Null;
+    for _ in 0..100 {
+        comments = flag_values(&reader, None).await["comments"].clone();
+        if comments == true {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(comments, true);
+
+    shutdown.cancel();
+}
+
+async fn listening_connections(pool: &PgPool) -> i64 {
+    sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM pg_stat_activity
+        WHERE datname = current_database() AND query LIKE 'LISTEN%'
+        "#,
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn one_connection_listens_for_every_cache(pool: PgPool) {
+    let reader = replica(&pool);
+    let shutdown = CancellationToken::new();
+    tokio::spawn(notify::listen_worker(
+        reader.state.clone(),
+        shutdown.clone(),
+    ));
+
+    let mut listening = 0;
+    for _ in 0..100 {
+        listening = listening_connections(&pool).await;
+        if listening > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(listening, 1);
+
+    // both buses arrive on it
+    assert_eq!(flag_values(&reader, None).await.get("comments"), None);
+    let flag = FeatureFlagData {
+        enabled: Some(true),
+        ..Default::default()
+    };
+    reader
+        .state
+        .feature_flag_store
+        .upsert("comments", flag, &Actor::default())
+        .await
+        .unwrap();
+    assert_eq!(flag_values(&reader, None).await.get("comments"), None);
+    let admin = reader.signup_admin("admin").await;
+    reader.create_post(&admin, "hello").await;
+    // the create's own invalidation comes back through the listener as well
+    for _ in 0..100 {
+        reader.get("/api/post/hello", None).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        if reader.state.post_cache.stats().entries == 1 {
+            break;
+        }
+    }
+    assert_eq!(reader.state.post_cache.stats().entries, 1);
+
+    let writer = replica(&pool);
+    let flag_bus = writer.state.feature_flags.bus().unwrap();
+    flag_bus.publish("comments").await.unwrap();
+    let post_bus = writer.state.post_cache.bus().unwrap();
+    post_bus.publish(&["hello".to_string()]).await.unwrap();
+
+    let mut heard = false;
+    for _ in 0..100 {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        heard = reader.state.post_cache.stats().entries == 0
+            && flag_values(&reader, None).await["comments"] == true;
+        if heard {
+            break;
+        }
+    }
+    assert!(heard);
+    assert_eq!(listening_connections(&pool).await, 1);
+
+    shutdown.cancel();
+}
@@ -0,0 +1,2 @@
+INSERT INTO category (id, name) VALUES (1, 'General'), (2, 'Rust');
+SELECT setval(pg_get_serial_sequence('category', 'id'), 2);
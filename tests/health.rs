@@ -0,0 +1,73 @@
+mod common;
+
+use axum::http::StatusCode;
+use sqlx::PgPool;
+
+use common::TestApp;
+
+#[tokio::test]
+async fn liveness_is_always_ok() {
+    let app = TestApp::memory();
+
+    let response = app.get("/healthz", None).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["status"], "ok");
+}
+
+#[sqlx::test]
+async fn readiness_reports_every_check(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+
+    let response = app.get("/readyz", None).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["status"], "ok");
+    let checks = &response.body["checks"];
+    assert_eq!(checks["database"]["status"], "ok");
+    assert!(checks["database"]["latency_ms"].is_number());
+    assert_eq!(checks["migrations"]["status"], "ok");
+    assert_eq!(checks["migrations"]["pending"], serde_json::json!([]));
+    assert_eq!(checks["pool"]["status"], "ok");
+    assert!(checks["pool"]["max"].as_u64().unwrap() > 0);
+    assert!(checks["pool"]["saturation"].is_number());
+}
+
+#[sqlx::test]
+async fn readiness_fails_with_pending_migrations(pool: PgPool) {
+    sqlx::query("DELETE FROM _sqlx_migrations WHERE version = 3")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let app = TestApp::postgres(pool);
+
+    let response = app.get("/readyz", None).await;
+
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.body["status"], "fail");
+    let migrations = &response.body["checks"]["migrations"];
+    assert_eq!(migrations["status"], "fail");
+    assert_eq!(migrations["pending"], serde_json::json!(["3/create post"]));
+}
+
+#[sqlx::test]
+async fn readiness_fails_when_database_is_unreachable(pool: PgPool) {
+    let app = TestApp::postgres(pool.clone());
+    pool.close().await;
+
+    let response = app.get("/readyz", None).await;
+
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.body["checks"]["database"]["status"], "fail");
+    assert!(response.body["checks"]["database"]["error"].is_string());
+}
+
+#[tokio::test]
+async fn readiness_without_pool_omits_pool_check() {
+    let app = TestApp::memory();
+
+    let response = app.get("/readyz", None).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body["checks"].get("pool").is_none());
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use blogrs::repository::{Actor, CategoryRepository, MemoryRepository};
+use serde_json::json;
+use sqlx::PgPool;
+
+use common::{test_config, TestApp, TestResponse};
+
+async fn app_with_post() -> (TestApp, String) {
+    let repository = MemoryRepository::new();
+    repository
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+    let app = TestApp::new(repository);
+    let token = app.signup("alice").await;
+    app.create_post(&token, "post").await;
+    (app, token)
+}
+
+async fn get_with(app: &TestApp, uri: &str, name: header::HeaderName, value: &str) -> TestResponse {
+    let request = Request::builder()
+        .uri(uri)
+        .header(name, value)
+        .body(Body::empty())
+        .unwrap();
+    app.send(request).await
+}
+
+fn header_value(response: &TestResponse, name: header::HeaderName) -> &str {
+    response.headers[name].to_str().unwrap()
+}
+
+#[tokio::test]
+async fn reads_carry_validators_and_cache_control() {
+    let (app, _) = app_with_post().await;
+
+    for uri in ["/api/post/post", "/api/post"] {
+        let response = app.get(uri, None).await;
+        assert_eq!(response.status, StatusCode::OK);
+        let etag = header_value(&response, header::ETAG);
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+        assert_eq!(
+            header_value(&response, header::CACHE_CONTROL),
+            "public, max-age=0, must-revalidate"
+        );
+    }
+
+    let response = app.get("/api/post/post", None).await;
+    assert!(header_value(&response, header::LAST_MODIFIED).ends_with(" GMT"));
+}
+
+#[tokio::test]
+async fn matching_etag_is_not_modified() {
+    let (app, _) = app_with_post().await;
+    let response = app.get("/api/post/post", None).await;
+    let etag = header_value(&response, header::ETAG).to_string();
+
+    let response = get_with(&app, "/api/post/post", header::IF_NONE_MATCH, &etag).await;
+    assert_eq!(response.status, StatusCode::NOT_MODIFIED);
+    assert_eq!(response.body, serde_json::Value::Null);
+    assert_eq!(header_value(&response, header::ETAG), etag);
+
+    let weak = format!("\"other\", W/{etag}");
+    let response = get_with(&app, "/api/post/post", header::IF_NONE_MATCH, &weak).await;
+    assert_eq!(response.status, StatusCode::NOT_MODIFIED);
+
+    let response = get_with(&app, "/api/post/post", header::IF_NONE_MATCH, "\"other\"").await;
+    assert_eq!(response.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn if_modified_since_uses_last_modified() {
+    let (app, _) = app_with_post().await;
+    let response = app.get("/api/post/post", None).await;
+    let last_modified = header_value(&response, header::LAST_MODIFIED).to_string();
+
+    let response = get_with(
+        &app,
+        "/api/post/post",
+        header::IF_MODIFIED_SINCE,
+        &last_modified,
+    )
+    .await;
+    assert_eq!(response.status, StatusCode::NOT_MODIFIED);
+
+    let response = get_with(
+        &app,
+        "/api/post/post",
+        header::IF_MODIFIED_SINCE,
+        "Sat, 01 Jan 2000 00:00:00 GMT",
+    )
+    .await;
+    assert_eq!(response.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn the_list_is_not_stale_after_a_delete() {
+    let (app, token) = app_with_post().await;
+    app.create_post(&token, "other").await;
+    let response = app.get("/api/post", None).await;
+    assert!(!response.headers.contains_key(header::LAST_MODIFIED));
+    let since = blogrs::http_cache::http_date(chrono::Utc::now());
+
+    let response = app.delete("/api/post/delete/other", Some(&token)).await;
+    assert!(response.status.is_success(), "{:?}", response.body);
+
+    let response = get_with(&app, "/api/post", header::IF_MODIFIED_SINCE, &since).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["data"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn cache_control_is_configurable() {
+    let mut config = test_config();
+    config.http_cache.public_cache_control = "public, max-age=60".to_string();
+    let app = TestApp::with_config(config, MemoryRepository::new());
+
+    let response = app.get("/api/post", None).await;
+
+    assert_eq!(
+        header_value(&response, header::CACHE_CONTROL),
+        "public, max-age=60"
+    );
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn updates_change_the_validators(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+    let created = app.create_post(&token, "post").await;
+    let response = app.get("/api/post/post", None).await;
+    let etag = header_value(&response, header::ETAG).to_string();
+
+    let response = app
+        .patch(
+            "/api/post/update/post",
+            Some(&token),
+            json!({"title": "New"}),
+        )
+        .await;
+    assert_ne!(
+        response.body["data"]["post"]["updated_at"],
+        created.body["data"]["post"]["updated_at"]
+    );
+
+    let response = get_with(&app, "/api/post/post", header::IF_NONE_MATCH, &etag).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["data"]["title"], "New");
+}
@@ -0,0 +1,363 @@
+mod common;
+
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    middleware,
+    routing::post,
+    Extension, Router,
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+use blogrs::{
+    idempotency::idempotency_middleware,
+    repository::{Actor, CategoryRepository, MemoryRepository},
+};
+use common::{TestApp, TestResponse};
+
+fn post_body(slug: &str) -> Value {
+    json!({
+        "title": format!("Title of {slug}"),
+        "slug": slug,
+        "excerpt": "An excerpt",
+        "content": "Some content",
+        "status": "published",
+    })
+}
+
+async fn send_with_key(
+    app: &TestApp,
+    method: Method,
+    uri: &str,
+    token: &str,
+    key: &str,
+    body: Option<Value>,
+) -> TestResponse {
+    let builder = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .header("idempotency-key", key);
+    let request = match body {
+        Some(body) => builder
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string())),
+        None => builder.body(Body::empty()),
+    };
+    app.send(request.unwrap()).await
+}
+
+async fn create(app: &TestApp, token: &str, key: &str, slug: &str) -> TestResponse {
+    send_with_key(
+        app,
+        Method::POST,
+        "/api/post/create",
+        token,
+        key,
+        Some(post_body(slug)),
+    )
+    .await
+}
+
+fn is_replayed(response: &TestResponse) -> bool {
+    response.headers.contains_key("idempotent-replayed")
+}
+
+async fn post_count(app: &TestApp) -> usize {
+    app.get("/api/post", None).await.body["data"]
+        .as_array()
+        .unwrap()
+        .len()
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn retries_replay_the_first_response(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+
+    let first = create(&app, &token, "key-1", "hello").await;
+    assert_eq!(first.status, StatusCode::CREATED);
+    assert!(!is_replayed(&first));
+
+    // also through the versioned path, which is the same route
+    for uri in ["/api/post/create", "/api/v1/post/create"] {
+        let retry = send_with_key(
+            &app,
+            Method::POST,
+            uri,
+            &token,
+            "key-1",
+            Some(post_body("hello")),
+        )
+        .await;
+        assert_eq!(retry.status, StatusCode::CREATED);
+        assert!(is_replayed(&retry));
+        assert_eq!(retry.body, first.body);
+        assert_eq!(
+            retry.headers.get(header::CONTENT_TYPE),
+            first.headers.get(header::CONTENT_TYPE)
+        );
+    }
+    assert_eq!(post_count(&app).await, 1);
+
+    // without a key the retry fails like before
+    let response = app.create_post(&token, "hello").await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn a_reused_key_with_another_body_is_rejected(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+
+    create(&app, &token, "key-1", "hello").await;
+    let response = create(&app, &token, "key-1", "other").await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.code(), "IDEMPOTENCY_KEY_REUSED");
+
+    let response = send_with_key(
+        &app,
+        Method::DELETE,
+        "/api/post/delete/hello",
+        &token,
+        "key-1",
+        None,
+    )
+    .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(post_count(&app).await, 1);
+}
+
+async fn create_on(app: &TestApp, uri: &str, token: &str) -> TestResponse {
+    let body = json!({
+        "title": "Title of hello",
+        "slug": "hello",
+        "excerpt": "An excerpt",
+        "content": "Some content",
+    });
+    send_with_key(app, Method::POST, uri, token, "key-1", Some(body)).await
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn keys_are_bound_to_the_api_version(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+
+    let first = create_on(&app, "/api/v1/post/create", &token).await;
+    assert_eq!(first.status, StatusCode::CREATED);
+
+    // v2 creates a draft where v1 publishes, so the same body is another request there
+    let response = create_on(&app, "/api/v2/post/create", &token).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.code(), "IDEMPOTENCY_KEY_REUSED");
+    // while the unversioned alias is v1
+    let response = create_on(&app, "/api/post/create", &token).await;
+    assert!(is_replayed(&response));
+    assert_eq!(post_count(&app).await, 1);
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn keys_are_scoped_to_the_user(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let alice = app.signup("alice").await;
+    let bob = app.signup("bob").await;
+
+    create(&app, &alice, "key-1", "hello").await;
+    let response = create(&app, &bob, "key-1", "from-bob").await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert!(!is_replayed(&response));
+    assert_eq!(post_count(&app).await, 2);
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn updates_and_deletes_are_replayed(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+    app.create_post(&token, "hello").await;
+
+    let update = json!({ "title": "Renamed" });
+    for _ in 0..2 {
+        let response = send_with_key(
+            &app,
+            Method::PATCH,
+            "/api/post/update/hello",
+            &token,
+            "update-1",
+            Some(update.clone()),
+        )
+        .await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.body["data"]["post"]["title"], "Renamed");
+    }
+
+    for _ in 0..2 {
+        let response = send_with_key(
+            &app,
+            Method::DELETE,
+            "/api/post/delete/hello",
+            &token,
+            "delete-1",
+            None,
+        )
+        .await;
+        // the second delete would be a 404 without the key
+        assert_eq!(response.status, StatusCode::OK);
+    }
+    assert_eq!(post_count(&app).await, 0);
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn client_errors_are_replayed_and_expired_keys_run_again(pool: PgPool) {
+    let app = TestApp::postgres(pool.clone());
+    let token = app.signup("alice").await;
+    app.create_post(&token, "hello").await;
+
+    let first = create(&app, &token, "key-1", "hello").await;
+    assert_eq!(first.status, StatusCode::CONFLICT);
+    sqlx::query("DELETE FROM post")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let retry = create(&app, &token, "key-1", "hello").await;
+    assert_eq!(retry.status, StatusCode::CONFLICT);
+    assert!(is_replayed(&retry));
+
+    sqlx::query("UPDATE idempotency_key SET expires_at = NOW() - INTERVAL '1 second'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let response = create(&app, &token, "key-1", "hello").await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert!(!is_replayed(&response));
+
+    app.state.idempotency.prune().await.unwrap();
+    let (remaining,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM idempotency_key")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, 1);
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn a_retry_during_the_first_request_is_a_conflict(pool: PgPool) {
+    let app = TestApp::postgres(pool.clone());
+    let token = app.signup("alice").await;
+    create(&app, &token, "key-1", "hello").await;
+
+    // what the record looks like while the first request is still running
+    sqlx::query("UPDATE idempotency_key SET response_status = NULL, response_body = NULL")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let response = create(&app, &token, "key-1", "hello").await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.code(), "IDEMPOTENCY_KEY_IN_USE");
+}
+
+#[tokio::test]
+async fn invalid_keys_are_rejected() {
+    let repository = MemoryRepository::new();
+    repository
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+    let app = TestApp::new(repository);
+    let token = app.signup("alice").await;
+
+    let response = create(&app, &token, &"k".repeat(256), "hello").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.code(), "IDEMPOTENCY_KEY_INVALID");
+    assert_eq!(post_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn oversized_bodies_are_rejected() {
+    let repository = MemoryRepository::new();
+    repository
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+    let app = TestApp::new(repository);
+    let token = app.signup("alice").await;
+
+    let mut body = post_body("hello");
+    body["content"] = "x".repeat(2 * 1024 * 1024).into();
+    let response = send_with_key(
+        &app,
+        Method::POST,
+        "/api/post/create",
+        &token,
+        "key-1",
+        Some(body),
+    )
+    .await;
+    assert_eq!(response.status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(response.code(), "PAYLOAD_TOO_LARGE");
+}
+
+#[tokio::test]
+async fn dropped_requests_free_their_key() {
+    let app = TestApp::memory();
+    app.signup("alice").await;
+    let user = app
+        .state
+        .users
+        .find_by_username("alice")
+        .await
+        .unwrap()
+        .unwrap();
+    let user_id = user.id;
+
+    // a handler that never answers, like one whose client went away
+    let router = Router::new()
+        .route("/slow", post(std::future::pending::<StatusCode>))
+        .layer(middleware::from_fn_with_state(
+            app.state.clone(),
+            idempotency_middleware,
+        ))
+        .layer(Extension(user));
+    let request = Request::post("/slow")
+        .header("idempotency-key", "key-1")
+        .body(Body::empty())
+        .unwrap();
+    let result = tokio::time::timeout(Duration::from_millis(50), router.oneshot(request)).await;
+    assert!(result.is_err());
+
+    // released in the background
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let expires_at = chrono::Utc::now() + chrono::Duration::minutes(1);
+    let existing = app
+        .state
+        .idempotency
+        .begin(user_id, "key-1", "another request", expires_at)
+        .await
+        .unwrap();
+    assert!(existing.is_none());
+}
+
+#[tokio::test]
+async fn memory_and_sqlite_replay_the_same() {
+    let repository = MemoryRepository::new();
+    repository
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+
+    for app in [TestApp::new(repository), TestApp::sqlite().await] {
+        let token = app.signup("alice").await;
+
+        let first = create(&app, &token, "key-1", "hello").await;
+        assert_eq!(first.status, StatusCode::CREATED);
+        let retry = create(&app, &token, "key-1", "hello").await;
+        assert!(is_replayed(&retry));
+        assert_eq!(retry.body, first.body);
+
+        let response = create(&app, &token, "key-1", "other").await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(post_count(&app).await, 1);
+    }
+}
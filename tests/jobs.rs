@@ -0,0 +1,367 @@
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use blogrs::{
+    jobs::{self, Job, JobRegistry, PostCreated},
+    model::{JobState, PostStatus},
+    repository::{Actor, CategoryRepository, MemoryRepository, NewJob, PostData},
+    tasks::WorkerError,
+    AppState,
+};
+use common::TestApp;
+
+#[derive(Serialize, Deserialize)]
+struct Failing {
+    reason: String,
+}
+
+#[async_trait]
+impl Job for Failing {
+    const KIND: &'static str = "failing";
+    const MAX_ATTEMPTS: i32 = 2;
+
+    async fn run(self, _state: Arc<AppState>) -> Result<(), WorkerError> {
+        Err(self.reason.into())
+    }
+}
+
+fn registry() -> JobRegistry {
+    JobRegistry::builtin().register::<Failing>()
+}
+
+fn failing() -> NewJob {
+    NewJob::new(&Failing {
+        reason: "smtp is down".to_string(),
+    })
+    .unwrap()
+}
+
+async fn drain(app: &TestApp) -> usize {
+    let mut ran = 0;
+    while jobs::run_next(&app.state, &registry()).await.unwrap() {
+        ran += 1;
+    }
+    ran
+}
+
+async fn queued(app: &TestApp) -> Vec<blogrs::model::JobModel> {
+    app.state.jobs.list(JobState::Queued, 100).await.unwrap()
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn creating_a_post_enqueues_a_job(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+
+    app.create_post(&token, "hello").await;
+    // the slug is taken, so neither the post nor its job is written
+    let response = app.create_post(&token, "hello").await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+
+    let jobs = queued(&app).await;
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].kind, PostCreated::KIND);
+    assert_eq!(jobs[0].payload.0["slug"], "hello");
+    assert_eq!(jobs[0].unique_key.as_deref(), Some("post_created:hello"));
+
+    assert_eq!(drain(&app).await, 1);
+    assert!(queued(&app).await.is_empty());
+}
+
+fn post_data(slug: &str) -> PostData {
+    PostData {
+        title: format!("Title of {slug}"),
+        slug: slug.to_string(),
+        excerpt: "An excerpt".to_string(),
+        content: "Some content".to_string(),
+        category_id: Some(1),
+    }
+}
+
+fn queued_keys(jobs: &[blogrs::model::JobModel]) -> Vec<&str> {
+    let mut keys: Vec<_> = jobs
+        .iter()
+        .filter_map(|job| job.unique_key.as_deref())
+        .collect();
+    keys.sort();
+    keys
+}
+
+// the writes that change a post enqueue their jobs only if the change is made
+async fn post_writes_enqueue_jobs(app: TestApp) {
+    let token = app.signup("alice").await;
+    app.create_post(&token, "hello").await;
+    app.create_post(&token, "other").await;
+    assert_eq!(drain(&app).await, 2);
+    let (posts, actor) = (&app.state.posts, Actor::default());
+    let job = |key: &str| vec![failing().unique_key(key)];
+
+    posts
+        .update("hello", post_data("hello"), job("update"), &actor)
+        .await
+        .unwrap()
+        .unwrap();
+    let taken = posts
+        .update("hello", post_data("other"), job("update:taken"), &actor)
+        .await;
+    assert_eq!(taken.unwrap_err().code(), "POST_SLUG_TAKEN");
+    posts
+        .set_status("hello", PostStatus::Archived, job("archive"), &actor)
+        .await
+        .unwrap()
+        .unwrap();
+    posts
+        .delete("hello", job("delete"), &actor)
+        .await
+        .unwrap()
+        .unwrap();
+    let missing = posts
+        .delete("hello", job("delete:missing"), &actor)
+        .await
+        .unwrap();
+    assert!(missing.is_none());
+
+    assert_eq!(
+        queued_keys(&queued(&app).await),
+        ["archive", "delete", "update"]
+    );
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn postgres_post_writes_enqueue_jobs(pool: PgPool) {
+    post_writes_enqueue_jobs(TestApp::postgres(pool)).await;
+}
+
+#[tokio::test]
+async fn memory_and_sqlite_post_writes_enqueue_jobs() {
+    let repository = MemoryRepository::new();
+    repository
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+    post_writes_enqueue_jobs(TestApp::new(repository)).await;
+    post_writes_enqueue_jobs(TestApp::sqlite().await).await;
+}
+
+#[sqlx::test]
+async fn unique_keys_allow_one_pending_job(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+
+    let first = app
+        .state
+        .jobs
+        .enqueue(failing().unique_key("welcome:1"))
+        .await
+        .unwrap();
+    assert!(first.is_some());
+    let second = app
+        .state
+        .jobs
+        .enqueue(failing().unique_key("welcome:1"))
+        .await
+        .unwrap();
+    assert!(second.is_none());
+
+    // a dead job no longer blocks its key
+    let first = first.unwrap();
+    let claimed = app
+        .state
+        .jobs
+        .claim(1, Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert!(app
+        .state
+        .jobs
+        .dead_letter(first.id, claimed[0].attempts, "gave up")
+        .await
+        .unwrap());
+    let third = app
+        .state
+        .jobs
+        .enqueue(failing().unique_key("welcome:1"))
+        .await
+        .unwrap();
+    assert!(third.is_some());
+
+    // but can't be queued again while the new one is pending
+    let error = app.state.jobs.requeue(first.id).await.unwrap_err();
+    assert_eq!(error.code(), "JOB_ALREADY_QUEUED");
+}
+
+#[sqlx::test]
+async fn claimed_jobs_are_skipped_by_other_workers(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    for _ in 0..2 {
+        app.state.jobs.enqueue(failing()).await.unwrap();
+    }
+    let lease = Duration::from_secs(60);
+
+    let (first, second) = tokio::join!(
+        app.state.jobs.claim(1, lease),
+        app.state.jobs.claim(1, lease)
+    );
+    let (first, second) = (first.unwrap(), second.unwrap());
+    assert_eq!((first.len(), second.len()), (1, 1));
+    assert_ne!(first[0].id, second[0].id);
+    assert!(app.state.jobs.claim(1, lease).await.unwrap().is_empty());
+}
+
+#[sqlx::test]
+async fn expired_leases_are_claimed_again(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    app.state.jobs.enqueue(failing()).await.unwrap();
+
+    let claimed = app.state.jobs.claim(1, Duration::ZERO).await.unwrap();
+    assert_eq!(claimed[0].attempts, 1);
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let claimed = app.state.jobs.claim(1, Duration::ZERO).await.unwrap();
+    assert_eq!(claimed[0].attempts, 2);
+}
+
+// a worker that outlived its lease can't finish a job another worker claimed since
+async fn lost_leases_change_nothing(app: TestApp) {
+    let job = app
+        .state
+        .jobs
+        .enqueue(failing().unique_key("welcome:1"))
+        .await
+        .unwrap()
+        .unwrap();
+    let jobs = &app.state.jobs;
+
+    let first = jobs.claim(1, Duration::ZERO).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let second = jobs.claim(1, Duration::from_secs(60)).await.unwrap();
+    let (first, second) = (first[0].attempts, second[0].attempts);
+    assert_eq!((first, second), (1, 2));
+
+    assert!(!jobs.complete(job.id, first).await.unwrap());
+    let later = chrono::Utc::now();
+    assert!(!jobs.retry(job.id, first, "late", later).await.unwrap());
+    assert!(!jobs.dead_letter(job.id, first, "late").await.unwrap());
+    assert!(jobs.list(JobState::Dead, 10).await.unwrap().is_empty());
+
+    assert!(jobs.dead_letter(job.id, second, "gave up").await.unwrap());
+    // nor one that was finished already
+    assert!(!jobs.complete(job.id, second).await.unwrap());
+
+    jobs.enqueue(failing().unique_key("welcome:1"))
+        .await
+        .unwrap()
+        .unwrap();
+    let error = jobs.requeue(job.id).await.unwrap_err();
+    assert_eq!(error.code(), "JOB_ALREADY_QUEUED");
+}
+
+#[sqlx::test]
+async fn postgres_lost_leases_change_nothing(pool: PgPool) {
+    lost_leases_change_nothing(TestApp::postgres(pool)).await;
+}
+
+#[tokio::test]
+async fn memory_and_sqlite_lost_leases_change_nothing() {
+    lost_leases_change_nothing(TestApp::memory()).await;
+    lost_leases_change_nothing(TestApp::sqlite().await).await;
+}
+
+#[sqlx::test]
+async fn scheduled_jobs_wait_for_run_at(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let later = chrono::Utc::now() + chrono::Duration::hours(1);
+    app.state
+        .jobs
+        .enqueue(failing().run_at(later))
+        .await
+        .unwrap();
+
+    assert_eq!(drain(&app).await, 0);
+    assert_eq!(queued(&app).await.len(), 1);
+}
+
+#[sqlx::test]
+async fn failing_jobs_back_off_then_dead_letter(pool: PgPool) {
+    let app = TestApp::postgres(pool.clone());
+    app.state.jobs.enqueue(failing()).await.unwrap();
+
+    assert_eq!(drain(&app).await, 1);
+    let jobs = queued(&app).await;
+    assert_eq!(jobs[0].attempts, 1);
+    assert_eq!(jobs[0].last_error.as_deref(), Some("smtp is down"));
+    let delay = jobs[0].run_at - chrono::Utc::now();
+    assert!(delay > chrono::Duration::seconds(5) && delay <= chrono::Duration::seconds(10));
+
+    sqlx::query("UPDATE jobs SET run_at = NOW()")
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(drain(&app).await, 1);
+    assert!(queued(&app).await.is_empty());
+
+    let dead = app.state.jobs.list(JobState::Dead, 10).await.unwrap();
+    assert_eq!(dead.len(), 1);
+    assert_eq!(dead[0].attempts, 2);
+
+    let requeued = app.state.jobs.requeue(dead[0].id).await.unwrap().unwrap();
+    assert_eq!(requeued.attempts, 0);
+    assert!(app.state.jobs.requeue(dead[0].id).await.unwrap().is_none());
+}
+
+#[sqlx::test]
+async fn unknown_kinds_are_dead_lettered(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let mut job = failing();
+    job.kind = "removed_in_a_later_release".to_string();
+    app.state.jobs.enqueue(job).await.unwrap();
+
+    assert_eq!(drain(&app).await, 1);
+    let dead = app.state.jobs.list(JobState::Dead, 10).await.unwrap();
+    assert_eq!(dead[0].attempts, 1);
+    assert!(dead[0]
+        .last_error
+        .as_deref()
+        .unwrap()
+        .contains("no job registered"));
+}
+
+#[tokio::test]
+async fn memory_and_sqlite_queues_behave_the_same() {
+    let repository = MemoryRepository::new();
+    repository
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+
+    for app in [TestApp::new(repository), TestApp::sqlite().await] {
+        let token = app.signup("alice").await;
+        app.create_post(&token, "hello").await;
+        app.state.jobs.enqueue(failing()).await.unwrap();
+        assert!(app
+            .state
+            .jobs
+            .enqueue(
+                NewJob::new(&PostCreated {
+                    slug: "hello".to_string(),
+                    user_id: 1
+                })
+                .unwrap()
+                .unique_key("post_created:hello")
+            )
+            .await
+            .unwrap()
+            .is_none());
+
+        assert_eq!(drain(&app).await, 2);
+        let jobs = queued(&app).await;
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].kind, Failing::KIND);
+        assert!(jobs[0].run_at > chrono::Utc::now());
+    }
+}
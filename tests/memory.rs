@@ -0,0 +1,67 @@
+// the same business rules as the postgres suite, but without a database
+mod common;
+
+use axum::http::StatusCode;
+use blogrs::repository::{Actor, CategoryRepository, MemoryRepository};
+use serde_json::json;
+
+use common::TestApp;
+
+async fn app_with_category() -> TestApp {
+    let repository = MemoryRepository::new();
+    repository
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+    TestApp::new(repository)
+}
+
+#[tokio::test]
+async fn duplicate_registration_conflicts() {
+    let app = TestApp::memory();
+    app.register("alice").await;
+
+    let response = app.register("alice").await;
+
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.code(), "AUTH_USER_EXISTS");
+}
+
+#[tokio::test]
+async fn ownership_is_enforced() {
+    let app = app_with_category().await;
+    let alice = app.signup("alice").await;
+    let mallory = app.signup("mallory").await;
+    assert_eq!(
+        app.create_post(&alice, "post").await.status,
+        StatusCode::CREATED
+    );
+
+    let response = app
+        .patch(
+            "/api/post/update/post",
+            Some(&mallory),
+            json!({"title": "x"}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "POST_NOT_OWNER");
+
+    let response = app.delete("/api/post/delete/post", Some(&mallory)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.delete("/api/post/delete/post", Some(&alice)).await;
+    assert_eq!(response.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn slug_must_be_unique() {
+    let app = app_with_category().await;
+    let token = app.signup("alice").await;
+    app.create_post(&token, "post").await;
+
+    let response = app.create_post(&token, "post").await;
+
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.code(), "POST_SLUG_TAKEN");
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use axum::http::{header, StatusCode};
+use sqlx::PgPool;
+
+use common::TestApp;
+
+#[sqlx::test]
+async fn requests_are_labelled_by_matched_route(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    app.get("/api/post/does-not-exist", None).await;
+
+    let response = app.get("/metrics", None).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.headers[header::CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    let body = response.body.as_str().unwrap();
+    assert!(
+        body.contains(r#"http_requests_total{method="GET",route="/api/post/:slug",status="404"}"#)
+    );
+    assert!(body.contains(r#"http_request_duration_seconds_bucket{method="GET",route="/api/post/:slug",status="404",le="0.005"}"#));
+    assert!(!body.contains("does-not-exist"));
+}
+
+#[sqlx::test]
+async fn pool_gauges_are_exported(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+
+    let response = app.get("/metrics", None).await;
+
+    let body = response.body.as_str().unwrap();
+    assert!(body.contains(r#"db_pool_connections{state="idle"}"#));
+    assert!(body.contains(r#"db_pool_connections{state="active"}"#));
+    assert!(body.contains(r#"db_pool_connections{state="size"}"#));
+    assert!(body.contains("db_pool_max_connections"));
+}
+
+#[sqlx::test]
+async fn domain_events_are_counted(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    app.signup("alice").await;
+    app.post(
+        "/api/auth/login",
+        None,
+        serde_json::json!({"email": "alice@example.com", "password": "wrong"}),
+    )
+    .await;
+
+    let response = app.get("/metrics", None).await;
+
+    let body = response.body.as_str().unwrap();
+    for metric in [
+        "blogrs_registrations_total",
+        "blogrs_logins_total",
+        "blogrs_failed_logins_total",
+    ] {
+        assert!(body.contains(metric), "{metric} is missing");
+    }
+}
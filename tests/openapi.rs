@@ -0,0 +1,63 @@
+mod common;
+
+use axum::http::StatusCode;
+
+use common::TestApp;
+
+#[tokio::test]
+async fn spec_documents_every_route() {
+    let app = TestApp::memory();
+
+    let response = app.get("/api/openapi.json", None).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    let paths = response.body["paths"].as_object().unwrap();
+    for (path, method) in [
+        ("/api/v1/post", "get"),
+        ("/api/v1/post/{slug}", "get"),
+        ("/api/v1/post/create", "post"),
+        ("/api/v2/post/create", "post"),
+        ("/api/v1/post/update/{slug}", "patch"),
+        ("/api/v1/post/delete/{slug}", "delete"),
+        ("/api/v1/auth/register", "post"),
+        ("/api/v1/auth/login", "post"),
+        ("/api/v1/auth/logout", "post"),
+        ("/api/v1/auth/current_user", "get"),
+    ] {
+        assert!(
+            paths.get(path).and_then(|p| p.get(method)).is_some(),
+            "{method} {path} is missing from the spec"
+        );
+    }
+}
+
+#[tokio::test]
+async fn spec_describes_bearer_and_cookie_auth() {
+    let app = TestApp::memory();
+
+    let response = app.get("/api/openapi.json", None).await;
+
+    let schemes = &response.body["components"]["securitySchemes"];
+    assert_eq!(schemes["bearer_auth"]["scheme"], "bearer");
+    assert_eq!(schemes["cookie_auth"]["in"], "cookie");
+    assert_eq!(schemes["cookie_auth"]["name"], "token");
+    let security = &response.body["paths"]["/api/v1/post/create"]["post"]["security"];
+    assert_eq!(security.as_array().unwrap().len(), 2);
+    assert!(response.body["paths"]["/api/v1/post"]["get"]
+        .get("security")
+        .is_none());
+}
+
+#[tokio::test]
+async fn docs_ui_is_served() {
+    let app = TestApp::memory();
+
+    let response = app.get("/api/docs", None).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response
+        .body
+        .as_str()
+        .unwrap()
+        .contains("/api/openapi.json"));
+}
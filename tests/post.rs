@@ -0,0 +1,269 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use serde_json::json;
+use sqlx::PgPool;
+
+use common::TestApp;
+
+#[sqlx::test]
+async fn root_returns_welcome_message(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+
+    let response = app.get("/", None).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body, "Welcome to blogrs API!");
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn create_post_requires_authentication(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+
+    let response = app
+        .post("/api/post/create", None, json!({"title": "t"}))
+        .await;
+
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "AUTH_NOT_LOGGED_IN");
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn create_fetch_update_delete_post(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+
+    let response = app.create_post(&token, "my-first-post").await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert_eq!(response.body["status"], "success");
+    let post = &response.body["data"]["post"];
+    assert_eq!(post["slug"], "my-first-post");
+    assert_eq!(post["title"], "Title of my-first-post");
+    assert_eq!(post["category_id"], 1);
+
+    let response = app.get("/api/post/my-first-post", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["data"]["content"], "Some content");
+
+    let response = app
+        .patch(
+            "/api/post/update/my-first-post",
+            Some(&token),
+            json!({"title": "Renamed", "slug": "renamed", "category_id": 2}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    let post = &response.body["data"]["post"];
+    assert_eq!(post["title"], "Renamed");
+    assert_eq!(post["slug"], "renamed");
+    assert_eq!(post["excerpt"], "An excerpt");
+    assert_eq!(post["category_id"], 2);
+
+    let response = app.get("/api/post/my-first-post", None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = app.delete("/api/post/delete/renamed", Some(&token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body, json!({"status": "success"}));
+
+    let response = app.get("/api/post/renamed", None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    assert_eq!(response.code(), "POST_NOT_FOUND");
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn list_posts_is_paginated_newest_first(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+    for slug in ["first", "second", "third"] {
+        app.create_post(&token, slug).await;
+    }
+
+    let response = app.get("/api/post", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let slugs = response.body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|post| post["slug"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(slugs, ["third", "second", "first"]);
+    assert!(response.body["data"][0].get("content").is_none());
+
+    let response = app.get("/api/post?page=2&limit=2", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let data = response.body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0]["slug"], "first");
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn create_post_with_taken_slug_conflicts(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+    app.create_post(&token, "taken").await;
+
+    let response = app.create_post(&token, "taken").await;
+
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.code(), "POST_SLUG_TAKEN");
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn create_post_with_unknown_category_fails(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+
+    let response = app
+        .post(
+            "/api/post/create",
+            Some(&token),
+            json!({"title": "t", "slug": "s", "excerpt": "e", "content": "c", "category_id": 99}),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.code(), "POST_CATEGORY_NOT_FOUND");
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn update_post_to_taken_slug_conflicts(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+    app.create_post(&token, "one").await;
+    app.create_post(&token, "two").await;
+
+    let response = app
+        .patch("/api/post/update/two", Some(&token), json!({"slug": "one"}))
+        .await;
+
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.code(), "POST_SLUG_TAKEN");
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn update_and_delete_unknown_post_not_found(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+
+    let response = app
+        .patch(
+            "/api/post/update/missing",
+            Some(&token),
+            json!({"title": "t"}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    assert_eq!(response.code(), "POST_NOT_FOUND");
+
+    let response = app.delete("/api/post/delete/missing", Some(&token)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    assert_eq!(response.code(), "POST_NOT_FOUND");
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn only_the_author_can_update_or_delete(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let alice = app.signup("alice").await;
+    let mallory = app.signup("mallory").await;
+    app.create_post(&alice, "alices-post").await;
+
+    let response = app
+        .patch(
+            "/api/post/update/alices-post",
+            Some(&mallory),
+            json!({"title": "pwned"}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "POST_NOT_OWNER");
+
+    let response = app
+        .delete("/api/post/delete/alices-post", Some(&mallory))
+        .await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "POST_NOT_OWNER");
+
+    let response = app.get("/api/post/alices-post", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["data"]["title"], "Title of alices-post");
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn update_and_delete_require_authentication(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+
+    let response = app
+        .patch("/api/post/update/any", None, json!({"title": "t"}))
+        .await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "AUTH_NOT_LOGGED_IN");
+
+    let response = app.delete("/api/post/delete/any", Some("bad-token")).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "AUTH_INVALID_TOKEN");
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn malformed_requests_are_validation_errors(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup("alice").await;
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("/api/post/create")
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from("{\"title\": "))
+        .unwrap();
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.body["status"], "fail");
+    assert_eq!(response.code(), "VALIDATION_ERROR");
+    assert!(response.body["message"]
+        .as_str()
+        .is_some_and(|m| !m.is_empty()));
+
+    // well-formed json of the wrong shape
+    let response = app
+        .post("/api/post/create", Some(&token), json!({"title": 1}))
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.code(), "VALIDATION_ERROR");
+
+    let response = app
+        .get("/api/auth/current_user/posts?status=deleted", Some(&token))
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.code(), "VALIDATION_ERROR");
+
+    // without an idempotency key the body limit is axum's
+    let body = json!({
+        "title": "t",
+        "slug": "big",
+        "excerpt": "e",
+        "content": "x".repeat(3 * 1024 * 1024),
+    });
+    let response = app.post("/api/post/create", Some(&token), body).await;
+    assert_eq!(response.status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(response.code(), "PAYLOAD_TOO_LARGE");
+}
+
+#[sqlx::test]
+async fn malformed_path_parameters_are_validation_errors(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let token = app.signup_admin("root").await;
+
+    let response = app
+        .put(
+            "/api/admin/feature_flags/beta/overrides/not-a-number",
+            Some(&token),
+            json!({"enabled": true}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.code(), "VALIDATION_ERROR");
+}
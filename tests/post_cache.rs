@@ -0,0 +1,198 @@
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use axum::http::StatusCode;
+use serde_json::json;
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+
+use blogrs::{
+    config::Config,
+    notify::{self, PgNotifyListener},
+    post_cache::{PgPostCacheBus, PostCacheStats},
+    repository::{Actor, CategoryRepository, MemoryRepository, PgRepository},
+    AppState,
+};
+use common::{test_config, TestApp};
+
+async fn memory_app(configure: impl FnOnce(&mut Config)) -> TestApp {
+    let mut config = test_config();
+    configure(&mut config);
+    let repository = MemoryRepository::new();
+    repository
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+    TestApp::with_config(config, repository)
+}
+
+fn stats(app: &TestApp) -> PostCacheStats {
+    app.state.post_cache.stats()
+}
+
+#[tokio::test]
+async fn reads_are_served_from_the_cache() {
+    let app = memory_app(|_| {}).await;
+    let token = app.signup("alice").await;
+    app.create_post(&token, "hello").await;
+
+    for _ in 0..2 {
+        assert_eq!(app.get("/api/post", None).await.status, StatusCode::OK);
+        assert_eq!(
+            app.get("/api/post/hello", None).await.status,
+            StatusCode::OK
+        );
+    }
+    // another page is another entry
+    app.get("/api/post?limit=1", None).await;
+    assert_eq!(
+        stats(&app),
+        PostCacheStats {
+            hits: 2,
+            misses: 3,
+            entries: 3
+        }
+    );
+
+    // missing posts are not cached
+    for _ in 0..2 {
+        let response = app.get("/api/post/missing", None).await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+    }
+    assert_eq!(stats(&app).misses, 5);
+    assert_eq!(stats(&app).entries, 3);
+
+    let metrics = app.get("/metrics", None).await;
+    let metrics = metrics.body.as_str().unwrap();
+    assert!(metrics.contains("blogrs_post_cache_requests_total"));
+    assert!(metrics.contains("blogrs_post_cache_entries"));
+}
+
+#[tokio::test]
+async fn writes_invalidate_the_cache() {
+    let app = memory_app(|_| {}).await;
+    let token = app.signup("alice").await;
+    app.create_post(&token, "hello").await;
+    app.get("/api/post", None).await;
+    app.get("/api/post/hello", None).await;
+
+    app.create_post(&token, "second").await;
+    let response = app.get("/api/post", None).await;
+    assert_eq!(response.body["data"].as_array().unwrap().len(), 2);
+
+    // a rename drops the old slug, which would otherwise still be served
+    app.patch(
+        "/api/post/update/hello",
+        Some(&token),
+        json!({ "slug": "renamed", "title": "Renamed" }),
+    )
+    .await;
+    let response = app.get("/api/post/hello", None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = app.get("/api/post/renamed", None).await;
+    assert_eq!(response.body["data"]["title"], "Renamed");
+    let response = app.get("/api/post", None).await;
+    assert_eq!(response.body["data"][1]["title"], "Renamed");
+
+    app.delete("/api/post/delete/renamed", Some(&token)).await;
+    let response = app.get("/api/post/renamed", None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = app.get("/api/post", None).await;
+    assert_eq!(response.body["data"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn entries_are_bounded_and_expire() {
+    let app = memory_app(|config| {
+        config.post_cache.max_entries = 2;
+        config.post_cache.ttl_secs = 1;
+    })
+    .await;
+
+    for page in 1..=3 {
+        app.get(&format!("/api/post?page={page}"), None).await;
+    }
+    assert_eq!(stats(&app).entries, 2);
+    // the first page was evicted
+    app.get("/api/post?page=1", None).await;
+    assert_eq!(stats(&app).hits, 0);
+    app.get("/api/post?page=1", None).await;
+    assert_eq!(stats(&app).hits, 1);
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    app.get("/api/post?page=1", None).await;
+    assert_eq!(stats(&app).hits, 1);
+}
+
+#[tokio::test]
+async fn the_cache_can_be_disabled() {
+    let app = memory_app(|config| config.post_cache.enabled = false).await;
+
+    app.get("/api/post", None).await;
+    app.get("/api/post", None).await;
+    assert_eq!(
+        stats(&app),
+        PostCacheStats {
+            hits: 0,
+            misses: 0,
+            entries: 0
+        }
+    );
+}
+
+fn replica(pool: &PgPool) -> TestApp {
+    let state = AppState::new(test_config(), PgRepository::new(pool.clone()))
+        .with_post_cache_bus(Arc::new(PgPostCacheBus::new(pool.clone())))
+        .with_notify_listener(PgNotifyListener::new(pool.clone()));
+    TestApp::from_state(Arc::new(state))
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn writes_on_one_replica_invalidate_the_others(pool: PgPool) {
+    let writer = replica(&pool);
+    let reader = replica(&pool);
+    let shutdown = CancellationToken::new();
+    tokio::spawn(notify::listen_worker(
+        reader.state.clone(),
+        shutdown.clone(),
+    ));
+
+    let token = writer.signup("alice").await;
+    writer.create_post(&token, "hello").await;
+
+    // waits until the listener is subscribed
+    let bus = writer.state.post_cache.bus().unwrap();
+    for _ in 0..100 {
+        reader.get("/api/post/hello", None).await;
+        bus.publish(&["hello".to_string()]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        if reader.state.post_cache.stats().entries == 0 {
+            break;
+        }
+    }
+    assert_eq!(reader.state.post_cache.stats().entries, 0);
+
+    let response = reader.get("/api/post/hello", None).await;
+    assert_eq!(response.body["data"]["title"], "Title of hello");
+    writer
+        .patch(
+            "/api/post/update/hello",
+            Some(&token),
+            json!({ "title": "Renamed" }),
+        )
+        .await;
+
+    let mut title = String::new();
+    for _ in 0..100 {
+        let response = reader.get("/api/post/hello", None).await;
+        title = response.body["data"]["title"].as_str().unwrap().to_string();
+        if title == "Renamed" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(title, "Renamed");
+
+    shutdown.cancel();
+}
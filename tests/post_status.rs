@@ -0,0 +1,295 @@
+mod common;
+
+use std::time::Duration;
+
+use axum::http::{header, StatusCode};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use blogrs::repository::Actor;
+use common::{TestApp, TestResponse};
+
+// v2 creates drafts unless the body sets a status
+async fn create_draft(app: &TestApp, token: &str, slug: &str) -> TestResponse {
+    app.post(
+        "/api/v2/post/create",
+        Some(token),
+        json!({
+            "title": format!("Title of {slug}"),
+            "slug": slug,
+            "excerpt": "An excerpt",
+            "content": "Some content",
+        }),
+    )
+    .await
+}
+
+async fn public_slugs(app: &TestApp) -> Vec<String> {
+    slugs(&app.get("/api/post", None).await)
+}
+
+fn slugs(response: &TestResponse) -> Vec<String> {
+    assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+    response.body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|post| post["slug"].as_str().unwrap().to_string())
+        .collect()
+}
+
+// the same workflow on every backend
+async fn drafts_are_only_seen_by_their_author(app: TestApp) {
+    let alice = app.signup("alice").await;
+    let bob = app.signup("bob").await;
+
+    let response = create_draft(&app, &alice, "hello").await;
+    assert_eq!(response.status, StatusCode::CREATED, "{:?}", response.body);
+    assert_eq!(response.body["data"]["post"]["status"], "draft");
+    assert_eq!(response.body["data"]["post"]["published_at"], Value::Null);
+
+    assert!(public_slugs(&app).await.is_empty());
+    for token in [None, Some(bob.as_str())] {
+        let response = app.get("/api/post/hello", token).await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+        assert_eq!(response.code(), "POST_NOT_FOUND");
+    }
+    let response = app.get("/api/post/hello", Some(&alice)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[header::CACHE_CONTROL], "private, no-cache");
+
+    // the author keeps editing the draft
+    let response = app
+        .patch(
+            "/api/post/update/hello",
+            Some(&alice),
+            json!({ "title": "Still a draft" }),
+        )
+        .await;
+    assert_eq!(response.body["data"]["post"]["status"], "draft");
+
+    let response = app
+        .post("/api/post/publish/hello", Some(&bob), json!({}))
+        .await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "POST_NOT_OWNER");
+    let response = app
+        .post("/api/post/publish/missing", Some(&alice), json!({}))
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let response = app
+        .post("/api/post/publish/hello", Some(&alice), json!({}))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+    let published_at = response.body["data"]["post"]["published_at"].clone();
+    assert!(published_at.is_string());
+    assert_eq!(public_slugs(&app).await, ["hello"]);
+    let response = app.get("/api/post/hello", None).await;
+    assert_eq!(response.body["data"]["title"], "Still a draft");
+    assert_ne!(response.headers[header::CACHE_CONTROL], "private, no-cache");
+
+    // back to a draft and out again keeps the first publication date
+    let response = app
+        .post("/api/post/unpublish/hello", Some(&alice), json!({}))
+        .await;
+    assert_eq!(response.body["data"]["post"]["status"], "draft");
+    assert!(public_slugs(&app).await.is_empty());
+    assert_eq!(
+        app.get("/api/post/hello", None).await.status,
+        StatusCode::NOT_FOUND
+    );
+    let response = app
+        .post("/api/post/publish/hello", Some(&alice), json!({}))
+        .await;
+    assert_eq!(response.body["data"]["post"]["published_at"], published_at);
+
+    let response = app
+        .post("/api/post/archive/hello", Some(&alice), json!({}))
+        .await;
+    assert_eq!(response.body["data"]["post"]["status"], "archived");
+    assert!(public_slugs(&app).await.is_empty());
+    assert_eq!(
+        app.get("/api/post/hello", None).await.status,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        app.get("/api/post/hello", Some(&alice)).await.status,
+        StatusCode::OK
+    );
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn postgres_drafts_are_only_seen_by_their_author(pool: PgPool) {
+    drafts_are_only_seen_by_their_author(TestApp::postgres(pool)).await;
+}
+
+#[tokio::test]
+async fn memory_and_sqlite_drafts_are_only_seen_by_their_author() {
+    let app = TestApp::memory();
+    app.state
+        .categories
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+    drafts_are_only_seen_by_their_author(app).await;
+    drafts_are_only_seen_by_their_author(TestApp::sqlite().await).await;
+}
+
+// orders by `published_at` on every backend
+async fn lists_follow_the_publication_order(app: TestApp) {
+    let alice = app.signup("alice").await;
+    let bob = app.signup("bob").await;
+
+    create_draft(&app, &alice, "first-written").await;
+    app.create_post(&alice, "first-published").await;
+    // sqlite keeps milliseconds
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    app.post("/api/post/publish/first-written", Some(&alice), json!({}))
+        .await;
+    create_draft(&app, &alice, "older-draft").await;
+    create_draft(&app, &alice, "draft").await;
+    app.create_post(&bob, "from-bob").await;
+
+    assert_eq!(
+        public_slugs(&app).await,
+        ["from-bob", "first-written", "first-published"]
+    );
+
+    // never published posts first, newest first, then by publication; a post that went back to
+    // a draft keeps its place
+    app.post(
+        "/api/post/unpublish/first-published",
+        Some(&alice),
+        json!({}),
+    )
+    .await;
+    let response = app.get("/api/auth/current_user/posts", Some(&alice)).await;
+    assert_eq!(
+        slugs(&response),
+        ["draft", "older-draft", "first-written", "first-published"]
+    );
+    assert_eq!(response.headers[header::CACHE_CONTROL], "private, no-cache");
+    let response = app
+        .get("/api/auth/current_user/posts?status=draft", Some(&alice))
+        .await;
+    assert_eq!(
+        slugs(&response),
+        ["draft", "older-draft", "first-published"]
+    );
+    let response = app
+        .get("/api/auth/current_user/posts?status=deleted", Some(&alice))
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let response = app.get("/api/auth/current_user/posts", None).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test(fixtures("categories"))]
+async fn postgres_lists_follow_the_publication_order(pool: PgPool) {
+    lists_follow_the_publication_order(TestApp::postgres(pool)).await;
+}
+
+#[tokio::test]
+async fn memory_and_sqlite_lists_follow_the_publication_order() {
+    let app = TestApp::memory();
+    app.state
+        .categories
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+    lists_follow_the_publication_order(app).await;
+    lists_follow_the_publication_order(TestApp::sqlite().await).await;
+}
+
+#[tokio::test]
+async fn only_v2_creates_drafts_by_default() {
+    let app = TestApp::memory();
+    app.state
+        .categories
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+    let token = app.signup("alice").await;
+
+    for (i, (path, status)) in [
+        ("/api/post/create", "published"),
+        ("/api/v1/post/create", "published"),
+        ("/api/v2/post/create", "draft"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let slug = format!("post-{i}");
+        let response = app
+            .post(
+                path,
+                Some(&token),
+                json!({
+                    "title": "A title",
+                    "slug": slug,
+                    "excerpt": "An excerpt",
+                    "content": "Some content",
+                }),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED, "{:?}", response.body);
+        assert_eq!(response.body["data"]["post"]["status"], status, "{path}");
+    }
+
+    let response = app
+        .post(
+            "/api/v2/post/create",
+            None,
+            json!({ "title": "A title", "slug": "anonymous", "excerpt": "", "content": "" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn status_changes_are_audited() {
+    let app = TestApp::memory();
+    app.state
+        .categories
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+    let admin = app.signup_admin("admin").await;
+    let token = app.signup("alice").await;
+
+    create_draft(&app, &token, "hello").await;
+    for action in ["publish", "unpublish", "archive"] {
+        app.post(
+            &format!("/api/post/{action}/hello"),
+            Some(&token),
+            json!({}),
+        )
+        .await;
+    }
+
+    let response = app
+        .get("/api/admin/audit?target_type=post", Some(&admin))
+        .await;
+    let actions = response.body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["action"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        actions,
+        [
+            "post.archive",
+            "post.unpublish",
+            "post.publish",
+            "post.create"
+        ]
+    );
+    let response = app
+        .get("/api/admin/audit?action=post.publish", Some(&admin))
+        .await;
+    let entry = &response.body["data"][0];
+    assert_eq!(entry["before"]["status"], "draft");
+    assert_eq!(entry["after"]["status"], "published");
+}
@@ -0,0 +1,176 @@
+mod common;
+
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, Method, Request, StatusCode},
+};
+use serde_json::json;
+use sqlx::PgPool;
+
+use blogrs::{
+    config::{Config, RateLimitKey, RateLimitRule},
+    rate_limit::{PgRateLimitStore, RateLimitStore},
+    repository::{Actor, CategoryRepository, MemoryRepository, PgRepository},
+    AppState,
+};
+use common::{test_config, TestApp, TestResponse};
+
+fn limited_config() -> Config {
+    let mut config = test_config();
+    config.rate_limit.enabled = true;
+    config.rate_limit.trusted_proxies = vec!["10.0.0.0/8".to_string()];
+    config.rate_limit.auth = RateLimitRule {
+        key: RateLimitKey::Ip,
+        burst: 2,
+        per_minute: 1,
+    };
+    config.rate_limit.write = RateLimitRule {
+        key: RateLimitKey::User,
+        burst: 1,
+        per_minute: 1,
+    };
+    config
+}
+
+async fn login_from(app: &TestApp, peer: &str, forwarded_for: Option<&str>) -> TestResponse {
+    let peer: SocketAddr = format!("{peer}:40000").parse().unwrap();
+    let mut builder = Request::builder()
+        .method(Method::POST)
+        .uri("/api/auth/login")
+        .header(header::CONTENT_TYPE, "application/json")
+        .extension(ConnectInfo(peer));
+    if let Some(forwarded_for) = forwarded_for {
+        builder = builder.header("x-forwarded-for", forwarded_for);
+    }
+    let body = json!({"email": "nobody@example.com", "password": "wrong"});
+    app.send(builder.body(Body::from(body.to_string())).unwrap())
+        .await
+}
+
+fn assert_limited(response: &TestResponse) {
+    assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.code(), "RATE_LIMITED");
+    assert_eq!(response.headers[header::RETRY_AFTER], "60");
+}
+
+#[tokio::test]
+async fn auth_routes_are_limited_per_client_ip() {
+    let app = TestApp::with_config(limited_config(), MemoryRepository::new());
+
+    for _ in 0..2 {
+        let response = login_from(&app, "192.0.2.1", None).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    }
+    assert_limited(&login_from(&app, "192.0.2.1", None).await);
+
+    let response = login_from(&app, "192.0.2.2", None).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn forwarded_for_is_only_honored_from_trusted_proxies() {
+    let app = TestApp::with_config(limited_config(), MemoryRepository::new());
+
+    // behind two trusted proxies the client is the first untrusted hop from the right
+    for _ in 0..2 {
+        let response = login_from(&app, "10.0.0.1", Some("198.51.100.7, 10.0.0.2")).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    }
+    assert_limited(&login_from(&app, "10.0.0.1", Some("203.0.113.9, 198.51.100.7")).await);
+    let response = login_from(&app, "10.0.0.1", Some("198.51.100.8")).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    // an untrusted peer cannot pick its key by forging the header
+    for forwarded_for in ["198.51.100.20", "198.51.100.21"] {
+        let response = login_from(&app, "192.0.2.1", Some(forwarded_for)).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    }
+    assert_limited(&login_from(&app, "192.0.2.1", Some("198.51.100.22")).await);
+}
+
+#[tokio::test]
+async fn writes_are_limited_per_user() {
+    let mut config = limited_config();
+    // both sign-ups come from the same (unknown) address
+    config.rate_limit.auth.burst = 4;
+    let repository = MemoryRepository::new();
+    repository
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+    let app = TestApp::with_config(config, repository);
+    let alice = app.signup("alice").await;
+    let bob = app.signup("bob").await;
+
+    assert_eq!(
+        app.create_post(&alice, "first").await.status,
+        StatusCode::CREATED
+    );
+    assert_limited(&app.create_post(&alice, "second").await);
+    assert_limited(&app.delete("/api/post/delete/first", Some(&alice)).await);
+    assert_eq!(
+        app.create_post(&bob, "third").await.status,
+        StatusCode::CREATED
+    );
+
+    // reads are not limited
+    let response = app.get("/api/post", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn disabled_rate_limiting_allows_everything() {
+    let mut config = limited_config();
+    config.rate_limit.enabled = false;
+    let app = TestApp::with_config(config, MemoryRepository::new());
+
+    for _ in 0..5 {
+        let response = login_from(&app, "192.0.2.1", None).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    }
+}
+
+#[sqlx::test]
+async fn postgres_store_is_shared_between_replicas(pool: PgPool) {
+    let replica = || {
+        let state = AppState::new(limited_config(), PgRepository::new(pool.clone()))
+            .with_rate_limit_store(Arc::new(PgRateLimitStore::new(pool.clone())));
+        TestApp::from_state(Arc::new(state))
+    };
+    let (first, second) = (replica(), replica());
+
+    let response = login_from(&first, "192.0.2.1", None).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let response = login_from(&second, "192.0.2.1", None).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_limited(&login_from(&first, "192.0.2.1", None).await);
+    assert_limited(&login_from(&second, "192.0.2.1", None).await);
+}
+
+#[sqlx::test]
+async fn postgres_store_prunes_idle_buckets(pool: PgPool) {
+    let app = TestApp::from_state(Arc::new(
+        AppState::new(limited_config(), PgRepository::new(pool.clone()))
+            .with_rate_limit_store(Arc::new(PgRateLimitStore::new(pool.clone()))),
+    ));
+    login_from(&app, "192.0.2.1", None).await;
+    login_from(&app, "192.0.2.2", None).await;
+    sqlx::query(
+        "UPDATE rate_limit_bucket SET updated_at = NOW() - INTERVAL '2 hours' WHERE key = $1",
+    )
+    .bind("auth:ip:192.0.2.1")
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    PgRateLimitStore::new(pool.clone()).prune().await.unwrap();
+
+    let keys: Vec<String> = sqlx::query_scalar("SELECT key FROM rate_limit_bucket")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(keys, vec!["auth:ip:192.0.2.2".to_string()]);
+}
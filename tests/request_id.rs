@@ -0,0 +1,72 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+
+use common::TestApp;
+
+fn request_id(response: &common::TestResponse) -> &str {
+    response.headers["x-request-id"].to_str().unwrap()
+}
+
+#[tokio::test]
+async fn generates_request_id_when_missing() {
+    let app = TestApp::memory();
+
+    let response = app.get("/healthz", None).await;
+
+    assert!(uuid::Uuid::parse_str(request_id(&response)).is_ok());
+}
+
+#[tokio::test]
+async fn echoes_client_request_id() {
+    let app = TestApp::memory();
+
+    let request = Request::get("/healthz")
+        .header("x-request-id", "client-abc-123")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.send(request).await;
+
+    assert_eq!(request_id(&response), "client-abc-123");
+}
+
+#[tokio::test]
+async fn replaces_oversized_request_id() {
+    let app = TestApp::memory();
+
+    let request = Request::get("/healthz")
+        .header("x-request-id", "x".repeat(500))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.send(request).await;
+
+    assert!(uuid::Uuid::parse_str(request_id(&response)).is_ok());
+}
+
+#[tokio::test]
+async fn error_bodies_carry_the_request_id() {
+    let app = TestApp::memory();
+
+    let request = Request::get("/api/post/missing")
+        .header("x-request-id", "trace-me")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.send(request).await;
+
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    assert_eq!(response.body["request_id"], "trace-me");
+    assert_eq!(request_id(&response), "trace-me");
+}
+
+#[tokio::test]
+async fn guard_errors_carry_the_request_id() {
+    let app = TestApp::memory();
+
+    let response = app.get("/api/auth/current_user", None).await;
+
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.body["request_id"], request_id(&response));
+}
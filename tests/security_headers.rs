@@ -0,0 +1,221 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use serde_json::json;
+use sqlx::PgPool;
+
+use blogrs::{
+    config::{Config, SecurityHeadersRoute},
+    repository::{MemoryRepository, SqliteRepository},
+};
+use common::{sqlite_pool, test_config, TestApp, TestResponse};
+
+fn app_with(configure: impl FnOnce(&mut Config)) -> TestApp {
+    let mut config = test_config();
+    configure(&mut config);
+    TestApp::with_config(config, MemoryRepository::new())
+}
+
+fn header_value<'a>(response: &'a TestResponse, name: &str) -> Option<&'a str> {
+    response
+        .headers
+        .get(name)
+        .map(|value| value.to_str().unwrap())
+}
+
+async fn send_report(app: &TestApp, content_type: &str, body: String) -> TestResponse {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("/csp-report")
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::USER_AGENT, "Firefox/123.0")
+        .body(Body::from(body))
+        .unwrap();
+    app.send(request).await
+}
+
+#[tokio::test]
+async fn json_responses_get_the_strict_policy() {
+    let app = TestApp::memory();
+
+    for uri in ["/api/post", "/api/post/missing", "/no-such-route"] {
+        let response = app.get(uri, None).await;
+        assert_eq!(
+            header_value(&response, "content-security-policy"),
+            Some("default-src 'none'; frame-ancestors 'none'; report-uri /csp-report"),
+            "{uri}"
+        );
+        assert_eq!(
+            header_value(&response, "strict-transport-security"),
+            Some("max-age=31536000; includeSubDomains")
+        );
+        assert_eq!(
+            header_value(&response, "x-content-type-options"),
+            Some("nosniff")
+        );
+        assert_eq!(
+            header_value(&response, "referrer-policy"),
+            Some("no-referrer")
+        );
+        assert_eq!(header_value(&response, "x-frame-options"), Some("DENY"));
+        assert!(header_value(&response, "permissions-policy")
+            .unwrap()
+            .contains("camera=()"));
+    }
+}
+
+#[tokio::test]
+async fn html_pages_get_the_html_policy() {
+    let app = TestApp::memory();
+
+    let response = app.get("/api/docs", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let csp = header_value(&response, "content-security-policy").unwrap();
+    assert!(csp.contains("script-src 'self' https://unpkg.com"), "{csp}");
+    assert!(csp.ends_with("report-uri /csp-report"), "{csp}");
+}
+
+#[tokio::test]
+async fn routes_override_the_global_headers() {
+    let app = app_with(|config| {
+        config.security_headers.routes = vec![SecurityHeadersRoute {
+            path: "/api/docs".to_string(),
+            content_security_policy: Some("default-src 'self'".to_string()),
+            referrer_policy: None,
+            permissions_policy: Some(String::new()),
+            frame_options: Some("sameorigin".to_string()),
+        }];
+    });
+
+    let response = app.get("/api/docs", None).await;
+    assert_eq!(
+        header_value(&response, "content-security-policy"),
+        Some("default-src 'self'; report-uri /csp-report")
+    );
+    assert_eq!(
+        header_value(&response, "x-frame-options"),
+        Some("SAMEORIGIN")
+    );
+    assert_eq!(header_value(&response, "permissions-policy"), None);
+    assert_eq!(
+        header_value(&response, "referrer-policy"),
+        Some("no-referrer")
+    );
+
+    // matched on whole path segments
+    let response = app.get("/api/docsearch", None).await;
+    assert_eq!(header_value(&response, "x-frame-options"), Some("DENY"));
+}
+
+#[tokio::test]
+async fn report_only_mode_reports_without_enforcing() {
+    let app = app_with(|config| {
+        config.security_headers.csp_report_only = true;
+        config.security_headers.hsts_max_age_secs = 0;
+    });
+
+    let response = app.get("/api/post", None).await;
+    assert_eq!(header_value(&response, "content-security-policy"), None);
+    assert!(header_value(&response, "content-security-policy-report-only").is_some());
+    assert_eq!(header_value(&response, "strict-transport-security"), None);
+}
+
+#[tokio::test]
+async fn the_layer_can_be_disabled() {
+    let app = app_with(|config| config.security_headers.enabled = false);
+
+    let response = app.get("/api/post", None).await;
+    assert_eq!(header_value(&response, "content-security-policy"), None);
+    assert_eq!(header_value(&response, "x-content-type-options"), None);
+}
+
+#[tokio::test]
+async fn invalid_values_are_rejected_on_startup() {
+    let mut config = test_config();
+    config.security_headers.frame_options = "ALLOW-FROM https://example.com".to_string();
+    assert!(config.validate().is_err());
+
+    let mut config = test_config();
+    config.security_headers.routes = vec![SecurityHeadersRoute {
+        path: "docs".to_string(),
+        content_security_policy: Some("default-src\n'self'".to_string()),
+        referrer_policy: None,
+        permissions_policy: None,
+        frame_options: None,
+    }];
+    let error = config.validate().unwrap_err().to_string();
+    assert!(error.contains("routes[0].path"), "{error}");
+    assert!(
+        error.contains("routes[0].content_security_policy"),
+        "{error}"
+    );
+}
+
+#[sqlx::test]
+async fn violations_are_stored(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+
+    let legacy = json!({
+        "csp-report": {
+            "document-uri": "https://blog.example.com/api/docs",
+            "violated-directive": "script-src-elem",
+            "effective-directive": "script-src-elem",
+            "blocked-uri": "https://evil.example.com/x.js",
+            "line-number": 12,
+            "disposition": "report",
+        }
+    });
+    let response = send_report(&app, "application/csp-report", legacy.to_string()).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let batch = json!([
+        {
+            "type": "csp-violation",
+            "body": {
+                "documentURL": "https://blog.example.com/",
+                "effectiveDirective": "img-src",
+                "blockedURL": "data",
+                "disposition": "enforce",
+            }
+        },
+        { "type": "deprecation", "body": { "id": "something" } }
+    ]);
+    let response = send_report(&app, "application/reports+json", batch.to_string()).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let reports = app.state.csp_reports.list(10).await.unwrap();
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[0].violated_directive, "img-src");
+    assert_eq!(reports[0].disposition, "enforce");
+    assert_eq!(reports[1].violated_directive, "script-src-elem");
+    assert_eq!(
+        reports[1].blocked_uri.as_deref(),
+        Some("https://evil.example.com/x.js")
+    );
+    assert_eq!(reports[1].line_number, Some(12));
+    assert_eq!(reports[1].disposition, "report");
+    assert_eq!(reports[1].user_agent.as_deref(), Some("Firefox/123.0"));
+
+    let response = send_report(&app, "application/csp-report", "not json".to_string()).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.code(), "CSP_REPORT_INVALID");
+}
+
+#[tokio::test]
+async fn reports_are_rate_limited_and_stored_in_sqlite() {
+    let mut config = test_config();
+    config.rate_limit.enabled = true;
+    config.rate_limit.csp_report.burst = 1;
+    let app = TestApp::with_config(config, SqliteRepository::new(sqlite_pool().await));
+
+    let report = json!({ "csp-report": { "document-uri": "https://blog.example.com/" } });
+    let response = send_report(&app, "application/csp-report", report.to_string()).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let response = send_report(&app, "application/csp-report", report.to_string()).await;
+    assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+
+    assert_eq!(app.state.csp_reports.list(10).await.unwrap().len(), 1);
+}
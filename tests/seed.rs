@@ -0,0 +1,147 @@
+// loading fixture files with `blogrs::fixtures`
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use axum::http::StatusCode;
+use serde_json::json;
+use sqlx::PgPool;
+
+use blogrs::{
+    fixtures::{self, FixtureError, Fixtures, LoadReport},
+    repository::MemoryRepository,
+};
+use common::TestApp;
+
+const DEMO: &str = "seed/demo.yaml";
+
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(extension: &str, content: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "blogrs-fixtures-{}.{extension}",
+            uuid::Uuid::new_v4()
+        ));
+        std::fs::write(&path, content).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[sqlx::test]
+async fn demo_fixtures_load_once(pool: PgPool) {
+    let app = TestApp::postgres(pool);
+    let paths = [PathBuf::from(DEMO)];
+
+    let report = fixtures::load_paths(&app.state, &paths).await.unwrap();
+    assert_eq!(
+        report,
+        LoadReport {
+            users: 2,
+            categories: 2,
+            posts: 2
+        }
+    );
+    let report = fixtures::load_paths(&app.state, &paths).await.unwrap();
+    assert_eq!(report, LoadReport::default());
+
+    let response = app.get("/api/post/hello-rust", None).await;
+    assert_eq!(response.body["data"]["category_id"], 2);
+
+    // the plaintext password was hashed like on registration
+    let response = app
+        .post(
+            "/api/auth/login",
+            None,
+            json!({"email": "alice@example.com", "password": "password123"}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    let token = response.body["token"].as_str().unwrap();
+    let response = app.get("/api/auth/current_user", Some(token)).await;
+    assert_eq!(response.body["data"]["user"]["is_admin"], false);
+}
+
+#[tokio::test]
+async fn json_fixtures_reference_existing_records() {
+    let app = TestApp::sqlite().await;
+    app.signup("bob").await;
+
+    let file = TempFile::new(
+        "json",
+        r#"{"posts": [{"slug": "bobs-post", "title": "Bob", "content": "Hi", "author": "bob"}]}"#,
+    );
+    let report = Fixtures::from_path(&file.0)
+        .unwrap()
+        .load(&app.state)
+        .await
+        .unwrap();
+    assert_eq!(report.posts, 1);
+
+    let response = app.get("/api/post/bobs-post", None).await;
+    assert_eq!(response.body["data"]["user_id"], 1);
+    assert_eq!(
+        response.body["data"]["category_id"],
+        serde_json::Value::Null
+    );
+}
+
+#[tokio::test]
+async fn unknown_references_are_rejected() {
+    let app = TestApp::memory();
+    let file = TempFile::new(
+        "yml",
+        "posts:\n  - {slug: orphan, title: Orphan, content: x, author: nobody}\n",
+    );
+
+    let error = Fixtures::from_path(&file.0)
+        .unwrap()
+        .load(&app.state)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, FixtureError::Invalid(_)));
+    assert!(error.to_string().contains("no user named `nobody`"));
+}
+
+#[tokio::test]
+async fn emails_of_other_users_are_rejected() {
+    let app = TestApp::new(MemoryRepository::new());
+    app.signup("alice").await;
+    let file = TempFile::new(
+        "yaml",
+        "users:\n  - {username: alicia, email: Alice@Example.com, password: x}\n",
+    );
+
+    let error = Fixtures::from_path(&file.0)
+        .unwrap()
+        .load(&app.state)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("belongs to another user"));
+}
+
+#[test]
+fn unknown_fields_and_extensions_are_parse_errors() {
+    let file = TempFile::new("yaml", "users:\n  - {username: a, email: b, pasword: c}\n");
+    assert!(matches!(
+        Fixtures::from_path(&file.0),
+        Err(FixtureError::Parse { .. })
+    ));
+
+    let file = TempFile::new("toml", "");
+    assert!(matches!(
+        Fixtures::from_path(&file.0),
+        Err(FixtureError::Parse { .. })
+    ));
+
+    assert!(matches!(
+        Fixtures::from_path(Path::new("does/not/exist.yaml")),
+        Err(FixtureError::Read { .. })
+    ));
+}
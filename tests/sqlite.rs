@@ -0,0 +1,115 @@
+// the postgres suite's main flows, run against the sqlite backend
+mod common;
+
+use axum::http::StatusCode;
+use blogrs::{
+    config::{DatabaseConfig, DatabaseKind},
+    repository::Backend,
+};
+use serde_json::json;
+
+use common::TestApp;
+
+#[tokio::test]
+async fn create_fetch_update_delete_post() {
+    let app = TestApp::sqlite().await;
+    let token = app.signup("alice").await;
+
+    let response = app.create_post(&token, "my-first-post").await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let post = &response.body["data"]["post"];
+    assert_eq!(post["slug"], "my-first-post");
+    assert_eq!(post["category_id"], 1);
+    assert!(post["created_at"].is_string());
+
+    let response = app.get("/api/post/my-first-post", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["data"]["content"], "Some content");
+
+    let response = app
+        .patch(
+            "/api/post/update/my-first-post",
+            Some(&token),
+            json!({"title": "Renamed", "slug": "renamed", "category_id": 2}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["data"]["post"]["category_id"], 2);
+
+    let response = app.delete("/api/post/delete/renamed", Some(&token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let response = app.get("/api/post/renamed", None).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn list_posts_is_paginated_newest_first() {
+    let app = TestApp::sqlite().await;
+    let token = app.signup("alice").await;
+    for slug in ["first", "second", "third"] {
+        app.create_post(&token, slug).await;
+    }
+
+    let response = app.get("/api/post?page=1&limit=2", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let slugs = response.body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|post| post["slug"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(slugs, ["third", "second"]);
+}
+
+#[tokio::test]
+async fn constraint_violations_map_to_error_codes() {
+    let app = TestApp::sqlite().await;
+    let token = app.signup("alice").await;
+    app.create_post(&token, "post").await;
+
+    let response = app.register("alice").await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.code(), "AUTH_USER_EXISTS");
+
+    let response = app.create_post(&token, "post").await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.code(), "POST_SLUG_TAKEN");
+
+    let response = app
+        .post(
+            "/api/post/create",
+            Some(&token),
+            json!({"title": "t", "slug": "s", "excerpt": "e", "content": "c", "category_id": 99}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.code(), "POST_CATEGORY_NOT_FOUND");
+}
+
+#[tokio::test]
+async fn readiness_checks_sqlite_migrations() {
+    let app = TestApp::sqlite().await;
+
+    let response = app.get("/readyz", None).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["checks"]["database"]["status"], "ok");
+    assert_eq!(response.body["checks"]["migrations"]["pending"], json!([]));
+}
+
+#[tokio::test]
+async fn backend_is_chosen_from_the_url() {
+    let path = std::env::temp_dir().join(format!("blogrs-{}.db", uuid::Uuid::new_v4()));
+    let config = DatabaseConfig {
+        url: Some(format!("sqlite://{}", path.display())),
+        max_connections: 1,
+    };
+
+    let backend = Backend::connect(&config).await.unwrap();
+    assert_eq!(backend.kind(), DatabaseKind::Sqlite);
+    backend.migrate().await.unwrap();
+    assert!(path.exists());
+
+    let _ = std::fs::remove_file(path);
+}
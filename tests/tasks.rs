@@ -0,0 +1,152 @@
+mod common;
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::http::StatusCode;
+
+use blogrs::tasks::{TaskSupervisor, WorkerState};
+use common::TestApp;
+
+#[tokio::test(start_paused = true)]
+async fn restarts_a_worker_after_a_panic() {
+    let supervisor = TaskSupervisor::new();
+    let runs = Arc::new(AtomicU32::new(0));
+
+    let counter = runs.clone();
+    supervisor.spawn("flaky", move |shutdown| {
+        let counter = counter.clone();
+        async move {
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                panic!("boom");
+            }
+            shutdown.cancelled().await;
+            Ok(())
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let status = &supervisor.statuses()["flaky"];
+    assert_eq!(status.state, WorkerState::Restarting);
+    assert_eq!(status.last_error.as_deref(), Some("panicked: boom"));
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let status = &supervisor.statuses()["flaky"];
+    assert_eq!(status.state, WorkerState::Running);
+    assert_eq!(status.restarts, 1);
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn restarts_a_worker_after_an_error() {
+    let supervisor = TaskSupervisor::new();
+
+    supervisor.spawn("failing", |_| async { Err("nope".into()) });
+
+    tokio::time::sleep(Duration::from_secs(4)).await;
+    let status = &supervisor.statuses()["failing"];
+    // failed at 0s, 1s and 3s with the backoff doubling in between
+    assert_eq!(status.restarts, 2);
+    assert_eq!(status.last_error.as_deref(), Some("nope"));
+}
+
+#[tokio::test]
+async fn shutdown_stops_every_worker() {
+    let supervisor = TaskSupervisor::new();
+
+    supervisor.spawn("first", |shutdown| async move {
+        shutdown.cancelled().await;
+        Ok(())
+    });
+    supervisor.spawn("second", |shutdown| async move {
+        shutdown.cancelled().await;
+        Ok(())
+    });
+
+    supervisor.shutdown(Duration::from_secs(5)).await;
+
+    let statuses = supervisor.statuses();
+    assert_eq!(statuses.len(), 2);
+    assert!(statuses
+        .values()
+        .all(|status| status.state == WorkerState::Stopped));
+}
+
+#[tokio::test(start_paused = true)]
+async fn shutdown_aborts_workers_that_ignore_it() {
+    let supervisor = TaskSupervisor::new();
+
+    supervisor.spawn("stubborn", |_| async {
+        std::future::pending::<()>().await;
+        Ok(())
+    });
+
+    // returns once the timeout elapses instead of hanging
+    supervisor.shutdown(Duration::from_secs(1)).await;
+}
+
+// set when the worker's future is dropped, i.e. its task was aborted
+struct Stopped(Arc<AtomicBool>);
+
+impl Drop for Stopped {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn aborted_workers_stop_running() {
+    let supervisor = TaskSupervisor::new();
+    let stopped = Arc::new(AtomicBool::new(false));
+    let ticks = Arc::new(AtomicU32::new(0));
+
+    let (flag, counter) = (stopped.clone(), ticks.clone());
+    supervisor.spawn("stubborn", move |_| {
+        let guard = Stopped(flag.clone());
+        let counter = counter.clone();
+        async move {
+            let _guard = guard;
+            loop {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(250)).await;
+
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(stopped.load(Ordering::SeqCst));
+
+    let after_shutdown = ticks.load(Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(ticks.load(Ordering::SeqCst), after_shutdown);
+}
+
+#[tokio::test(start_paused = true)]
+async fn readiness_reports_worker_status() {
+    let app = TestApp::memory();
+    app.state.tasks.spawn("idle", |shutdown| async move {
+        shutdown.cancelled().await;
+        Ok(())
+    });
+    app.state
+        .tasks
+        .spawn("broken", |_| async { Err("unavailable".into()) });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let response = app.get("/readyz", None).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["status"], "degraded");
+    let workers = &response.body["checks"]["workers"];
+    assert_eq!(workers["status"], "degraded");
+    assert_eq!(workers["workers"]["idle"]["state"], "running");
+    assert_eq!(workers["workers"]["broken"]["state"], "restarting");
+    assert_eq!(workers["workers"]["broken"]["last_error"], "unavailable");
+}
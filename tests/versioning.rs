@@ -0,0 +1,119 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use chrono::{TimeZone, Utc};
+use serde_json::json;
+use tower::ServiceExt;
+
+use blogrs::{
+    deprecation::{deprecated, Deprecation},
+    repository::Actor,
+};
+use common::TestApp;
+
+fn deprecation() -> Deprecation {
+    Deprecation::new(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+        .sunset(Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap())
+        .successor("/api/v2/post")
+}
+
+#[tokio::test]
+async fn v1_and_the_unversioned_alias_serve_the_same_routes() {
+    let app = TestApp::memory();
+    let token = app.signup("alice").await;
+
+    for prefix in ["/api/v1", "/api"] {
+        let response = app
+            .get(&format!("{prefix}/auth/current_user"), Some(&token))
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{prefix}");
+        assert_eq!(response.body["data"]["user"]["username"], "alice");
+    }
+
+    let response = app
+        .post(
+            "/api/v1/auth/login",
+            None,
+            json!({"email": "alice@example.com", "password": common::PASSWORD}),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn v2_does_not_inherit_v1_routes() {
+    let app = TestApp::memory();
+
+    let response = app.get("/api/v2/post", None).await;
+
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn creating_posts_on_v1_is_deprecated() {
+    let app = TestApp::memory();
+    app.state
+        .categories
+        .create("General", &Actor::default())
+        .await
+        .unwrap();
+    let token = app.signup("alice").await;
+    let body =
+        |slug: &str| json!({ "title": "A title", "slug": slug, "excerpt": "", "content": "" });
+
+    for (uri, slug) in [("/api/v1/post/create", "v1"), ("/api/post/create", "alias")] {
+        let response = app.post(uri, Some(&token), body(slug)).await;
+        assert_eq!(response.status, StatusCode::CREATED, "{uri}");
+        assert!(response.headers.contains_key("deprecation"), "{uri}");
+        assert!(response.headers.contains_key("sunset"), "{uri}");
+        assert_eq!(
+            response.headers[header::LINK],
+            "</api/v2/post/create>; rel=\"successor-version\""
+        );
+    }
+
+    let response = app
+        .post("/api/v2/post/create", Some(&token), body("v2"))
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert!(!response.headers.contains_key("deprecation"));
+}
+
+#[tokio::test]
+async fn deprecated_routes_send_deprecation_and_sunset() {
+    let router = Router::new().route("/old", deprecated(get(|| async { "old" }), deprecation()));
+
+    let response = router
+        .oneshot(Request::builder().uri("/old").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    let headers = response.headers();
+    assert_eq!(headers["deprecation"], "@1704067200");
+    assert_eq!(headers["sunset"], "Mon, 01 Jul 2024 00:00:00 GMT");
+    assert_eq!(
+        headers[header::LINK],
+        "</api/v2/post>; rel=\"successor-version\""
+    );
+}
+
+#[tokio::test]
+async fn handlers_can_return_a_deprecation() {
+    let router = Router::new().route(
+        "/old",
+        get(|| async { (deprecation(), Json(json!({"status": "success"}))) }),
+    );
+
+    let response = router
+        .oneshot(Request::builder().uri("/old").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["deprecation"], "@1704067200");
+}